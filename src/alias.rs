@@ -0,0 +1,273 @@
+/// Aliases from the `[aliases]` table in lk.toml, e.g. `up = "docker.sh compose_up --build"`, so a
+/// project can give newcomers a short list of the things worth running. An alias runs one or more
+/// script functions, one after the other. They're a provider, so they come after the providers
+/// whose functions they run.
+use crate::provider::{Context, Found, Provider};
+use crate::runner::Job;
+use crate::script::{Function, Run, Script};
+use crate::Result;
+use std::collections::BTreeMap;
+use std::path::PathBuf;
+
+pub struct Aliases {
+    /// The steps of each alias, as they're written in lk.toml.
+    aliases: BTreeMap<String, Vec<String>>,
+}
+
+impl Aliases {
+    pub fn new(aliases: BTreeMap<String, Vec<String>>) -> Self {
+        Self { aliases }
+    }
+}
+
+impl Provider for Aliases {
+    fn name(&self) -> &str {
+        "aliases"
+    }
+
+    /// The aliases as functions of a script that isn't there, so they're listed alongside the real
+    /// ones, and can be run by their name alone. A step doesn't work if it names a script or
+    /// function we haven't got, but we only stop if the alias is run.
+    fn discover(&self, context: &Context) -> Result<Found> {
+        if self.aliases.is_empty() {
+            return Ok(Found::default());
+        }
+        let functions = self
+            .aliases
+            .iter()
+            .map(|(name, steps)| {
+                let steps: Vec<Result<Job, String>> = steps
+                    .iter()
+                    .map(|step| resolve(step, context.found))
+                    .collect();
+                Function {
+                    name: name.to_owned(),
+                    comment: self.aliases[name]
+                        .iter()
+                        .zip(&steps)
+                        .map(|(step, job)| match job {
+                            Ok(_) => format!("Runs {step}"),
+                            Err(err) => format!("Won't run: {err}"),
+                        })
+                        .collect(),
+                    run: Run::Steps(steps),
+                    ..Function::default()
+                }
+            })
+            .collect();
+        Ok(Found {
+            scripts: vec![Script {
+                path: PathBuf::from("aliases"),
+                absolute_path: PathBuf::from("lk.toml"),
+                description: Some("Aliases from lk.toml".to_string()),
+                functions,
+                by_name: true,
+                ..Script::default()
+            }],
+            failures: Vec::new(),
+        })
+    }
+}
+
+/// Finds the function a step like `docker.sh compose_up --build` runs. The script can be its file
+/// name or its path from the root.
+fn resolve(step: &str, scripts: &[Script]) -> Result<Job, String> {
+    let words = split_words(step)?;
+    let [script_name, function_name, params @ ..] = words.as_slice() else {
+        return Err(format!(
+            "'{step}' should be a script and a function, e.g. build.sh all"
+        ));
+    };
+    let script = scripts
+        .iter()
+        .find(|script| script.file_name() == *script_name || script.path() == *script_name)
+        .ok_or_else(|| format!("Didn't find a script with name {script_name}"))?;
+    let function = script.get(function_name).ok_or_else(|| {
+        format!("Didn't find a function with name {function_name} in {script_name}")
+    })?;
+    Ok(Job {
+        script: script.to_owned(),
+        function: function.to_owned(),
+        params: params.to_vec(),
+    })
+}
+
+/// Splits a step into words like bash would, with quotes and backslashes, but without running
+/// anything.
+fn split_words(step: &str) -> Result<Vec<String>, String> {
+    let mut words = Vec::new();
+    let mut word: Option<String> = None;
+    let mut chars = step.chars();
+    while let Some(c) = chars.next() {
+        match c {
+            c if c.is_whitespace() => words.extend(word.take()),
+            '\'' => {
+                let quoted = word.get_or_insert_with(String::new);
+                loop {
+                    match chars.next() {
+                        Some('\'') => break,
+                        Some(c) => quoted.push(c),
+                        None => return Err(format!("'{step}' has a ' without one to close it")),
+                    }
+                }
+            }
+            '"' => {
+                let quoted = word.get_or_insert_with(String::new);
+                loop {
+                    match chars.next() {
+                        Some('"') => break,
+                        Some('\\') => match chars.next() {
+                            Some(c @ ('"' | '\\' | '$' | '`')) => quoted.push(c),
+                            Some(c) => quoted.extend(['\\', c]),
+                            None => {
+                                return Err(format!("'{step}' has a \" without one to close it"))
+                            }
+                        },
+                        Some(c) => quoted.push(c),
+                        None => return Err(format!("'{step}' has a \" without one to close it")),
+                    }
+                }
+            }
+            '\\' => word.get_or_insert_with(String::new).extend(chars.next()),
+            c => word.get_or_insert_with(String::new).push(c),
+        }
+    }
+    words.extend(word);
+    Ok(words)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{Executables, NonExecutable, Workspace};
+
+    fn scripts() -> Vec<Script> {
+        let function = |name: &str| Function {
+            name: name.to_string(),
+            ..Function::default()
+        };
+        vec![
+            Script {
+                path: PathBuf::from("docker/docker.sh"),
+                functions: vec![function("compose_up"), function("_helper")],
+                ..Script::default()
+            },
+            Script {
+                path: PathBuf::from("deploy.sh"),
+                functions: vec![function("release")],
+                ..Script::default()
+            },
+        ]
+    }
+
+    /// The aliases, as the aliases provider finds them after `scripts()`.
+    fn discover(aliases: &[(&str, &[&str])]) -> Vec<Script> {
+        let dir = tempfile::tempdir().unwrap();
+        let executables = Executables::new(
+            &[format!("{}/*", dir.path().display())],
+            &[],
+            NonExecutable::Skip,
+        )
+        .unwrap();
+        let workspace = Workspace {
+            root: dir.path().to_owned(),
+            files: Vec::new(),
+        };
+        let scripts = scripts();
+        let context = Context {
+            executables: &executables,
+            workspace: &workspace,
+            config_files: &[],
+            follow_sources: false,
+            found: &scripts,
+        };
+        let aliases = aliases
+            .iter()
+            .map(|(name, steps)| {
+                let steps = steps.iter().map(|step| step.to_string()).collect();
+                (name.to_string(), steps)
+            })
+            .collect();
+        Aliases::new(aliases).discover(&context).unwrap().scripts
+    }
+
+    /// What each step of the alias runs, or what's wrong with it.
+    fn steps(alias: &Function) -> Vec<Result<(String, Vec<String>), String>> {
+        let Run::Steps(steps) = &alias.run else {
+            panic!("{} isn't an alias", alias.name);
+        };
+        steps
+            .iter()
+            .map(|step| {
+                step.as_ref()
+                    .map(|job| (job.name(), job.params.clone()))
+                    .map_err(String::clone)
+            })
+            .collect()
+    }
+
+    #[test]
+    fn resolves_aliases_to_functions() {
+        let found = discover(&[
+            ("up", &["docker.sh compose_up --build"]),
+            (
+                "ship",
+                &["docker/docker.sh _helper", "deploy.sh release 'prod eu'"],
+            ),
+            ("oops", &["deploy.sh rollback"]),
+        ]);
+
+        let aliases = &found[0];
+        assert!(aliases.by_name);
+        assert_eq!(
+            steps(aliases.get("up").unwrap()),
+            vec![Ok((
+                "docker.sh:compose_up".to_string(),
+                vec!["--build".to_string()]
+            ))]
+        );
+        assert_eq!(
+            steps(aliases.get("ship").unwrap()),
+            vec![
+                Ok(("docker.sh:_helper".to_string(), Vec::new())),
+                Ok(("deploy.sh:release".to_string(), vec!["prod eu".to_string()])),
+            ]
+        );
+        let oops = aliases.get("oops").unwrap();
+        assert_eq!(
+            steps(oops),
+            vec![Err(
+                "Didn't find a function with name rollback in deploy.sh".to_string()
+            )]
+        );
+        assert_eq!(
+            oops.comment,
+            vec!["Won't run: Didn't find a function with name rollback in deploy.sh"]
+        );
+    }
+
+    #[test]
+    fn lists_aliases_as_functions() {
+        let found = discover(&[("up", &["deploy.sh release"])]);
+
+        assert_eq!(found.len(), 1);
+        assert_eq!(found[0].file_name(), "aliases");
+        assert_eq!(found[0].functions[0].name, "up");
+        assert_eq!(
+            found[0].functions[0].comment,
+            vec!["Runs deploy.sh release"]
+        );
+        // No aliases, nothing to add.
+        assert!(discover(&[]).is_empty());
+    }
+
+    #[test]
+    fn splits_words_like_bash() {
+        assert_eq!(
+            split_words(r#"a.sh f  'one two' "three \"3\"" four\ five"#).unwrap(),
+            vec!["a.sh", "f", "one two", "three \"3\"", "four five"]
+        );
+        assert_eq!(split_words("a.sh f ''").unwrap(), vec!["a.sh", "f", ""]);
+        assert!(split_words("a.sh f 'oops").is_err());
+    }
+}
@@ -1,16 +1,37 @@
 /// A temporary file for executing the requested function.
+use crate::process;
+use crate::redact::Redactor;
 use crate::script::Function;
-use crate::script::Script;
-use crate::ui::print_complete_header;
-use anyhow::Result;
+use crate::script::{Run, Script, SymlinkCd};
+use crate::ui::{print_complete_header, print_timed_out};
+use crate::Result;
 use nanoid::nanoid;
-use std::io::Write;
+use std::fs::File;
+use std::io::{BufRead, BufReader, Read, Write};
 use std::os::unix::fs::OpenOptionsExt;
-use std::path::PathBuf;
+use std::path::{Path, PathBuf};
 use std::process::Command;
 use std::process::Stdio;
+use std::sync::{Arc, Mutex};
+use std::thread::JoinHandle;
+use std::time::Duration;
 use tempfile::TempDir;
 
+/// How to execute a bash file.
+pub struct ExecuteOptions<'a> {
+    pub redactor: &'a Redactor,
+    /// If this is set then the function's output is captured in this file too.
+    pub log_file: Option<&'a Path>,
+    /// How long the function gets before we stop it.
+    pub timeout: Option<Duration>,
+    /// How long a timed-out function gets to clean up after a SIGTERM, before we SIGKILL it.
+    pub grace: Duration,
+    /// Set when the function runs alongside others. Every line of output gets this prefix.
+    pub prefix: Option<String>,
+    /// Where to run the function from, if we found the script through a symlink.
+    pub symlink_cd: SymlinkCd,
+}
+
 pub struct BashFile {
     // This isn't read but if the TempDir goes out-of-scope it might get deleted by the operating system.
     #[allow(dead_code)]
@@ -22,30 +43,36 @@ pub struct BashFile {
 }
 
 impl BashFile {
-    pub fn new(script: Script, function: Function, params: Vec<String>) -> Self {
-        let dir = tempfile::tempdir().unwrap();
+    pub fn new(script: Script, function: Function, params: Vec<String>) -> Result<Self> {
+        let dir = tempfile::tempdir()?;
         let file_name = format!("./~lk_{}", nanoid!(10));
         let full_path = dir.path().join(file_name);
-        Self {
+        Ok(Self {
             dir,
             full_path,
             script,
             function,
             params,
-        }
+        })
     }
 
-    pub fn run(script: Script, function: Function, params: Vec<String>) -> Result<()> {
-        let bash_file = BashFile::new(script, function, params);
-        bash_file.write()?;
-        bash_file.execute()
+    /// Writes and executes the bash file, returning the function's exit code.
+    pub fn run(
+        script: Script,
+        function: Function,
+        params: Vec<String>,
+        options: &ExecuteOptions,
+    ) -> Result<i32> {
+        let bash_file = BashFile::new(script, function, params)?;
+        bash_file.write(options.symlink_cd)?;
+        bash_file.execute(options)
     }
 
     /// lk uses a temporary file in order to execute a function in a script. This temporary file
     /// sources the script we're going to execute and then it can run the function because it'll
     /// have been loaded into the shell. `std::process::Command` has no way to do this. An alternative
     /// would be adding `"$@"` to the end of the scripts but I'd rather avoid this stipulation.
-    pub fn write(&self) -> Result<()> {
+    pub fn write(&self, symlink_cd: SymlinkCd) -> Result<()> {
         let mut file = std::fs::OpenOptions::new()
             .create(true)
             .truncate(true)
@@ -61,33 +88,319 @@ impl BashFile {
 "#;
         writeln!(file, "{}", bash_file)?;
 
+        // A command from lk.toml, or an imported target, has no script to source. It runs as it
+        // is, with the params in "$@", from where it says.
+        if let Run::Inline(inline) = &self.function.run {
+            writeln!(file, "cd {}", quote(&inline.dir.to_string_lossy()))?;
+            for (name, value) in &inline.env {
+                writeln!(file, "export {name}={}", quote(value))?;
+            }
+            writeln!(file, "{}", inline.run)?;
+            return Ok(());
+        }
+
         // CD to the scripts dir. This is an assumption we're making here,
         // but we can't avoid making an assumption, and this is safer than
         // assuming that the script can be run from any directory,
         // although that should be possible in a well written-script.
-        let script_file_name = self.script.file_name();
-        let script_path = self.script.working_dir_absolute();
+        let script_file_name = self.script.file_name_to_run(symlink_cd);
+        let script_path = self.script.working_dir_absolute(symlink_cd);
         writeln!(file, "cd {script_path}")?;
 
         // Source the script so we can access its functions
         writeln!(file, "source ./{script_file_name}")?;
 
-        // Call the function the user asked for
-        writeln!(file, "{} {}", self.function.name, self.params.join(" "))?;
+        // Call the function the user asked for. The params are passed as arguments when we
+        // execute this file, rather than written into it, so secrets never touch the disk.
+        writeln!(file, "{} \"$@\"", self.function.name)?;
 
         Ok(())
     }
 
-    /// This executes the lk file, and then removes it.
-    pub fn execute(&self) -> Result<()> {
-        print_complete_header(&self.script, &self.function, &self.params);
+    /// This executes the lk file, and then removes it. If we've been given a log file then the
+    /// function's output goes to both the terminal and the log file. If we've been given a prefix
+    /// then every line of output is prefixed with it, and the function doesn't get the terminal's
+    /// input, because it's running alongside others. The function's own timeout annotation takes
+    /// second place to one passed in the options.
+    pub fn execute(&self, options: &ExecuteOptions) -> Result<i32> {
+        let redactor = options.redactor;
+        let redacted_params = redactor.redact_params(&self.function, &self.params);
+        if options.prefix.is_none() {
+            print_complete_header(&self.script, &self.function, &redacted_params);
+        }
+
+        let timeout = options.timeout.or(self.function.timeout);
+        let mut command = Command::new(&self.full_path);
+        command.args(&self.params);
+        let code = if options.log_file.is_none() && options.prefix.is_none() {
+            let mut running = process::spawn(
+                command.stdout(Stdio::inherit()).stderr(Stdio::inherit()),
+                true,
+            )?;
+            process::wait(&mut running, timeout, options.grace)?
+        } else {
+            let log = match options.log_file {
+                Some(log_file) => {
+                    let mut log = File::create(log_file)?;
+                    writeln!(
+                        log,
+                        "lk: {} -> {} ({})",
+                        self.script.path(),
+                        self.function.name,
+                        redacted_params.join(" ")
+                    )?;
+                    Some(Arc::new(Mutex::new(log)))
+                }
+                None => None,
+            };
+            if options.prefix.is_some() {
+                command.stdin(Stdio::null());
+            }
 
-        Command::new(&self.full_path)
-            .stdout(Stdio::inherit())
-            .stderr(Stdio::inherit())
-            .spawn()
-            .unwrap()
-            .wait()?;
-        Ok(())
+            let mut running = process::spawn(
+                command.stdout(Stdio::piped()).stderr(Stdio::piped()),
+                options.prefix.is_none(),
+            )?;
+            // The log gets the secret params masked too, in case the function echoes them.
+            let log_redactor = redactor.for_run(&self.function, &self.params);
+            let tee_to = |terminal: Box<dyn Write + Send>| Tee {
+                terminal,
+                prefix: options.prefix.clone(),
+                log: log.clone(),
+                redactor: log_redactor.clone(),
+            };
+            let stdout = running
+                .child
+                .stdout
+                .take()
+                .map(|stdout| tee_to(Box::new(std::io::stdout())).spawn(stdout));
+            let stderr = running
+                .child
+                .stderr
+                .take()
+                .map(|stderr| tee_to(Box::new(std::io::stderr())).spawn(stderr));
+            let code = process::wait(&mut running, timeout, options.grace)?;
+            // The pipes close when the child exits, so these finish once they've caught up.
+            stdout.into_iter().chain(stderr).for_each(|tee| {
+                let _ = tee.join();
+            });
+            code
+        };
+        if code == process::TIMED_OUT {
+            if let Some(timeout) = timeout {
+                print_timed_out(&self.function, timeout);
+            }
+        }
+        Ok(code)
+    }
+}
+
+/// Quotes the text so bash reads it as one word, as it is.
+pub fn quote(text: &str) -> String {
+    format!("'{}'", text.replace('\'', r"'\''"))
+}
+
+/// Copies everything from the child's output to the terminal, with an optional prefix on every
+/// line, and a redacted copy to the log if there is one.
+struct Tee {
+    terminal: Box<dyn Write + Send>,
+    prefix: Option<String>,
+    log: Option<Arc<Mutex<File>>>,
+    redactor: Redactor,
+}
+
+impl Tee {
+    fn spawn<R: Read + Send + 'static>(mut self, reader: R) -> JoinHandle<()> {
+        std::thread::spawn(move || {
+            let mut reader = BufReader::new(reader);
+            let mut line: Vec<u8> = Vec::new();
+            while let Ok(read) = reader.read_until(b'\n', &mut line) {
+                if read == 0 {
+                    break;
+                }
+                // Each line is written in one go, so lines from different functions don't mix.
+                let to_terminal = match &self.prefix {
+                    Some(prefix) => [prefix.as_bytes(), &line].concat(),
+                    None => line.clone(),
+                };
+                let _ = self.terminal.write_all(&to_terminal);
+                let _ = self.terminal.flush();
+                if let Some(Ok(mut log)) = self.log.as_ref().map(|log| log.lock()) {
+                    let redacted = self.redactor.redact(&String::from_utf8_lossy(&line));
+                    let _ = log.write_all(redacted.as_bytes());
+                }
+                line.clear();
+            }
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::executables::Executable;
+    use crate::script::{Inline, Run};
+    use std::collections::BTreeMap;
+
+    fn test_script() -> Script {
+        let path = PathBuf::from("tests/script.sh");
+        let executable = Executable {
+            short_name: "script.sh".to_string(),
+            absolute_path: path.canonicalize().unwrap(),
+            path,
+            non_executable: false,
+            via_symlink: false,
+        };
+        Script::new(&executable, true).unwrap()
+    }
+
+    fn options(redactor: &Redactor) -> ExecuteOptions<'_> {
+        ExecuteOptions {
+            redactor,
+            log_file: None,
+            timeout: None,
+            grace: Duration::from_secs(1),
+            prefix: None,
+            symlink_cd: SymlinkCd::Target,
+        }
+    }
+
+    #[test]
+    fn params_are_not_written_to_the_temporary_file() {
+        let script = test_script();
+        let function = script.get("printing_function").unwrap().to_owned();
+
+        let bash_file = BashFile::new(script, function, vec!["hunter2".to_string()]).unwrap();
+        bash_file.write(SymlinkCd::Target).unwrap();
+
+        let on_disk = std::fs::read_to_string(&bash_file.full_path).unwrap();
+        assert!(on_disk.contains("printing_function \"$@\""));
+        assert!(!on_disk.contains("hunter2"));
+    }
+
+    #[test]
+    fn runs_commands_from_the_config() {
+        let _serial = process::SERIAL.lock();
+        let dir = tempfile::tempdir().unwrap();
+        let log_file = dir.path().join("run.log");
+        let function = Function {
+            name: "greet".to_string(),
+            run: Run::Inline(Inline {
+                run: "echo \"$GREETING $1 from $(basename \"$PWD\")\"".to_string(),
+                dir: dir.path().join("it's here"),
+                env: BTreeMap::from([("GREETING".to_string(), "it's".to_string())]),
+            }),
+            ..Function::default()
+        };
+        std::fs::create_dir(dir.path().join("it's here")).unwrap();
+        let redactor = Redactor::default();
+
+        let code = BashFile::run(
+            Script::default(),
+            function,
+            vec!["me".to_string()],
+            &ExecuteOptions {
+                log_file: Some(&log_file),
+                ..options(&redactor)
+            },
+        )
+        .unwrap();
+
+        assert_eq!(code, 0);
+        let log = std::fs::read_to_string(log_file).unwrap();
+        assert!(log.ends_with("it's me from it's here\n"), "{log}");
+    }
+
+    #[test]
+    fn output_is_captured_in_the_log_file() {
+        let _serial = process::SERIAL.lock();
+        let script = test_script();
+        let function = script.get("printing_function").unwrap().to_owned();
+        let dir = tempfile::tempdir().unwrap();
+        let log_file = dir.path().join("run.log");
+        let redactor = Redactor::new(&["hunter[0-9]".to_string()]).unwrap();
+
+        let code = BashFile::run(
+            script,
+            function,
+            vec!["hello".to_string(), "hunter2".to_string()],
+            &ExecuteOptions {
+                log_file: Some(&log_file),
+                ..options(&redactor)
+            },
+        )
+        .unwrap();
+
+        assert_eq!(code, 0);
+        let log = std::fs::read_to_string(log_file).unwrap();
+        assert!(log.starts_with("lk: tests/script.sh -> printing_function (hello *****)"));
+        assert!(log.contains("You said hello *****"));
+        assert!(!log.contains("hunter2"));
+    }
+
+    #[test]
+    fn secret_params_are_masked_in_the_log_file() {
+        let _serial = process::SERIAL.lock();
+        let dir = tempfile::tempdir().unwrap();
+        let log_file = dir.path().join("run.log");
+        let path = dir.path().join("deploy.sh");
+        std::fs::write(
+            &path,
+            "#!/usr/bin/env bash\n\n# @secret token\ndeploy() {\n    echo \"got $2\"\n}\n",
+        )
+        .unwrap();
+        let executable = Executable {
+            short_name: "deploy.sh".to_string(),
+            absolute_path: path.clone(),
+            path,
+            non_executable: false,
+            via_symlink: false,
+        };
+        let script = Script::new(&executable, true).unwrap();
+        let function = script.get("deploy").unwrap().to_owned();
+        let redactor = Redactor::default();
+
+        let code = BashFile::run(
+            script,
+            function,
+            vec!["prod".to_string(), "token=hunter2".to_string()],
+            &ExecuteOptions {
+                log_file: Some(&log_file),
+                ..options(&redactor)
+            },
+        )
+        .unwrap();
+
+        assert_eq!(code, 0);
+        let log = std::fs::read_to_string(log_file).unwrap();
+        assert!(log.ends_with("got token=*****\n"), "{log}");
+        assert!(!log.contains("hunter2"));
+    }
+
+    #[test]
+    fn timeout_annotation_stops_the_function() {
+        let _serial = process::SERIAL.lock();
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("sleepy.sh");
+        std::fs::write(
+            &path,
+            "#!/usr/bin/env bash\n\n# @timeout 200ms\nnap() {\n    sleep 30\n}\n",
+        )
+        .unwrap();
+        let executable = Executable {
+            short_name: "sleepy.sh".to_string(),
+            absolute_path: path.clone(),
+            path,
+            non_executable: false,
+            via_symlink: false,
+        };
+        let script = Script::new(&executable, true).unwrap();
+        let function = script.get("nap").unwrap().to_owned();
+        assert_eq!(function.timeout, Some(Duration::from_millis(200)));
+
+        let redactor = Redactor::default();
+        let code = BashFile::run(script, function, vec![], &options(&redactor)).unwrap();
+
+        assert_eq!(code, process::TIMED_OUT);
     }
 }
@@ -0,0 +1,287 @@
+/// Narrows scripts down by tag, groups them by owner, and prints them for people or as JSON.
+use crate::script::{Function, Script};
+use crate::ui::{print_owner_header, print_root_header};
+use crate::Result;
+use pad::{Alignment, PadStr};
+use pastel_colours::{DARK_GREEN_FG, GREEN_FG, RESET_FG};
+use serde::Serialize;
+use std::collections::BTreeMap;
+
+/// Keeps the functions that have every one of the tags, and the scripts that still have functions.
+pub fn filter_by_tags(scripts: &mut Vec<Script>, tags: &[String]) {
+    if tags.is_empty() {
+        return;
+    }
+    scripts
+        .iter_mut()
+        .for_each(|script| retain_tagged(script, tags));
+    scripts.retain(|script| !script.functions.is_empty());
+}
+
+/// Keeps the script's functions that have every one of the tags.
+pub fn retain_tagged(script: &mut Script, tags: &[String]) {
+    let functions = std::mem::take(&mut script.functions);
+    script.functions = functions
+        .into_iter()
+        .filter(|function| script.has_tags(function, tags))
+        .collect();
+}
+
+/// Splits the scripts up by who owns their functions, in order of owner, with the functions
+/// nobody owns last. A script whose functions have different owners is in more than one group.
+pub fn group_by_owner(scripts: &[Script]) -> Vec<(Option<String>, Vec<Script>)> {
+    // Keys are (no owner, owner) so the functions with an owner come first.
+    let mut groups: BTreeMap<(bool, Option<String>), Vec<Script>> = BTreeMap::new();
+    for script in scripts {
+        let mut by_owner: BTreeMap<(bool, Option<String>), Vec<Function>> = BTreeMap::new();
+        for function in &script.functions {
+            let owner = script.owner_of(function).map(String::from);
+            by_owner
+                .entry((owner.is_none(), owner))
+                .or_default()
+                .push(function.clone());
+        }
+        for (owner, functions) in by_owner {
+            groups.entry(owner).or_default().push(Script {
+                functions,
+                ..script.clone()
+            });
+        }
+    }
+    groups
+        .into_iter()
+        .map(|((_, owner), scripts)| (owner, scripts))
+        .collect()
+}
+
+/// Lists every function in the scripts, one per line, under a heading for each owner if we're
+/// grouping by owner.
+pub fn pretty_print(scripts: &[Script], by_owner: bool) {
+    let groups = if by_owner {
+        group_by_owner(scripts)
+    } else {
+        print_root_header();
+        vec![(None, scripts.to_vec())]
+    };
+    const INDENT: usize = 2;
+    let script_padding = scripts
+        .iter()
+        .map(|script| script.path().len())
+        .max()
+        .unwrap_or(0)
+        + INDENT;
+    let function_padding = scripts
+        .iter()
+        .flat_map(|script| script.functions.iter().map(|f| f.name.len()))
+        .max()
+        .unwrap_or(0);
+    for (owner, scripts) in groups {
+        if by_owner {
+            print_owner_header(owner.as_deref());
+        }
+        for script in &scripts {
+            let path = script
+                .path()
+                .pad_to_width_with_alignment(script_padding, Alignment::Right);
+            for function in &script.functions {
+                let name = function
+                    .name
+                    .pad_to_width_with_alignment(function_padding, Alignment::Left);
+                let comment = function.comment.first().map(String::as_str).unwrap_or("");
+                println!("{DARK_GREEN_FG}{path}{RESET_FG} {GREEN_FG}{name}{RESET_FG} {comment}");
+            }
+        }
+    }
+}
+
+#[derive(Serialize)]
+struct ScriptJson<'a> {
+    path: String,
+    /// What found it, e.g. bash or make.
+    provider: &'a str,
+    description: Option<&'a str>,
+    comment: &'a [String],
+    tags: &'a [String],
+    owner: Option<&'a str>,
+    functions: Vec<FunctionJson<'a>>,
+}
+
+#[derive(Serialize)]
+struct FunctionJson<'a> {
+    name: &'a str,
+    comment: &'a [String],
+    params: &'a [String],
+    /// The function's own tags and its script's.
+    tags: Vec<&'a str>,
+    /// The function's owner, or its script's.
+    owner: Option<&'a str>,
+    /// The library the function is from, if the script sources it.
+    source: Option<&'a str>,
+    hidden: bool,
+    start_line: usize,
+    end_line: usize,
+}
+
+#[derive(Serialize)]
+struct OwnerJson<'a> {
+    owner: Option<&'a str>,
+    scripts: Vec<ScriptJson<'a>>,
+}
+
+fn script_json(script: &Script) -> ScriptJson<'_> {
+    ScriptJson {
+        path: script.path(),
+        provider: &script.provider,
+        description: script.description.as_deref(),
+        comment: &script.comment,
+        tags: &script.tags,
+        owner: script.owner.as_deref(),
+        functions: script
+            .functions
+            .iter()
+            .map(|function| {
+                let mut tags: Vec<&str> = Vec::new();
+                for tag in function.tags.iter().chain(&script.tags) {
+                    if !tags.contains(&tag.as_str()) {
+                        tags.push(tag);
+                    }
+                }
+                FunctionJson {
+                    name: &function.name,
+                    comment: &function.comment,
+                    params: &function.params,
+                    tags,
+                    owner: script.owner_of(function),
+                    source: function.source.as_deref(),
+                    hidden: function.hidden,
+                    start_line: function.start_line,
+                    end_line: function.end_line,
+                }
+            })
+            .collect(),
+    }
+}
+
+/// The scripts as pretty JSON, for other tools. That's a list of scripts, or if we're grouping by
+/// owner, a list of owners with their scripts.
+pub fn to_json(scripts: &[Script], by_owner: bool) -> Result<String> {
+    if by_owner {
+        let groups = group_by_owner(scripts);
+        let owners: Vec<OwnerJson> = groups
+            .iter()
+            .map(|(owner, scripts)| OwnerJson {
+                owner: owner.as_deref(),
+                scripts: scripts.iter().map(script_json).collect(),
+            })
+            .collect();
+        Ok(serde_json::to_string_pretty(&owners)?)
+    } else {
+        let scripts: Vec<ScriptJson> = scripts.iter().map(script_json).collect();
+        Ok(serde_json::to_string_pretty(&scripts)?)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn function(name: &str, tags: &[&str], owner: Option<&str>) -> Function {
+        Function {
+            name: name.to_string(),
+            tags: tags.iter().map(|t| t.to_string()).collect(),
+            owner: owner.map(String::from),
+            ..Default::default()
+        }
+    }
+
+    fn scripts() -> Vec<Script> {
+        vec![
+            Script {
+                path: "db.sh".into(),
+                tags: vec!["db".to_string()],
+                owner: Some("team-data".to_string()),
+                functions: vec![
+                    function("backup", &[], None),
+                    function("migrate", &["deploy"], Some("team-platform")),
+                ],
+                ..Default::default()
+            },
+            Script {
+                path: "web.sh".into(),
+                functions: vec![
+                    function("deploy", &["deploy"], None),
+                    function("serve", &[], None),
+                ],
+                ..Default::default()
+            },
+        ]
+    }
+
+    fn names(scripts: &[Script]) -> Vec<String> {
+        scripts
+            .iter()
+            .flat_map(|script| {
+                script
+                    .functions
+                    .iter()
+                    .map(move |f| format!("{}:{}", script.path(), f.name))
+            })
+            .collect()
+    }
+
+    #[test]
+    fn filters_by_tags() {
+        let mut tagged = scripts();
+        filter_by_tags(&mut tagged, &["deploy".to_string()]);
+        assert_eq!(names(&tagged), vec!["db.sh:migrate", "web.sh:deploy"]);
+
+        let mut tagged = scripts();
+        filter_by_tags(&mut tagged, &["DB".to_string(), "deploy".to_string()]);
+        assert_eq!(names(&tagged), vec!["db.sh:migrate"]);
+
+        let mut tagged = scripts();
+        filter_by_tags(&mut tagged, &[]);
+        assert_eq!(tagged, scripts());
+    }
+
+    #[test]
+    fn groups_by_owner() {
+        let groups: Vec<(Option<String>, Vec<String>)> = group_by_owner(&scripts())
+            .into_iter()
+            .map(|(owner, scripts)| (owner, names(&scripts)))
+            .collect();
+        assert_eq!(
+            groups,
+            vec![
+                (
+                    Some("team-data".to_string()),
+                    vec!["db.sh:backup".to_string()]
+                ),
+                (
+                    Some("team-platform".to_string()),
+                    vec!["db.sh:migrate".to_string()]
+                ),
+                (
+                    None,
+                    vec!["web.sh:deploy".to_string(), "web.sh:serve".to_string()]
+                ),
+            ]
+        );
+    }
+
+    #[test]
+    fn json_has_the_metadata() {
+        let json: serde_json::Value =
+            serde_json::from_str(&to_json(&scripts(), false).unwrap()).unwrap();
+        let migrate = &json[0]["functions"][1];
+        assert_eq!(migrate["name"], "migrate");
+        assert_eq!(migrate["tags"], serde_json::json!(["deploy", "db"]));
+        assert_eq!(migrate["owner"], "team-platform");
+        assert_eq!(json[0]["functions"][0]["owner"], "team-data");
+
+        let json: serde_json::Value =
+            serde_json::from_str(&to_json(&scripts(), true).unwrap()).unwrap();
+        assert_eq!(json[2]["owner"], serde_json::Value::Null);
+        assert_eq!(json[2]["scripts"][0]["path"], "web.sh");
+    }
+}
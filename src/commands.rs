@@ -0,0 +1,229 @@
+/// Commands defined in lk.toml, e.g. `[commands.build]` with `run = "cargo build"`, for the things
+/// that don't deserve a script of their own. They're listed and run like a script's functions.
+use crate::provider::{self, Found, Provider};
+use crate::script::{Function, Inline, Run, Script};
+use crate::workspace::Workspace;
+use crate::{Error, Result};
+use serde::Deserialize;
+use std::collections::BTreeMap;
+use std::path::{Path, PathBuf};
+
+/// The script the commands are listed under, e.g. `lk commands build`.
+const SCRIPT_NAME: &str = "commands";
+
+/// Finds the commands in the config files.
+pub struct Commands;
+
+impl Provider for Commands {
+    fn name(&self) -> &str {
+        "commands"
+    }
+
+    fn discover(&self, context: &provider::Context) -> Result<Found> {
+        let commands = load(context.config_files, context.workspace)?;
+        Ok(Found {
+            scripts: script(commands, &context.workspace.root)
+                .into_iter()
+                .collect(),
+            failures: Vec::new(),
+        })
+    }
+}
+
+/// A command as it's written in lk.toml.
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize)]
+#[serde(deny_unknown_fields)]
+#[non_exhaustive]
+pub struct InlineCommand {
+    pub run: String,
+    pub description: Option<String>,
+    /// Relative to the directory of the lk.toml it's in, or the project root for the user config.
+    pub cwd: Option<PathBuf>,
+    #[serde(default)]
+    pub env: BTreeMap<String, String>,
+}
+
+/// Reads the commands from the config files. A command in a later file replaces one with the same
+/// name in an earlier one. We read the files ourselves, rather than through config-rs, because it
+/// lowercases keys and environment variable names are case sensitive.
+pub fn load(files: &[PathBuf], workspace: &Workspace) -> Result<Vec<Function>> {
+    let mut commands: BTreeMap<String, Function> = BTreeMap::new();
+    for file in files {
+        let Some(table) = read(file)? else {
+            continue;
+        };
+        // Paths in the user config are relative to the project root, like its globs.
+        let dir = match file.parent() {
+            Some(dir) if workspace.files.contains(file) => dir,
+            _ => workspace.root.as_path(),
+        };
+        for (name, command) in table {
+            let inline = to_inline(&command, dir).map_err(|problem| {
+                Error::Config(format!(
+                    "commands.{name} in {} is wrong: {problem}",
+                    file.display()
+                ))
+            })?;
+            let function = Function {
+                name: name.clone(),
+                comment: match &command.description {
+                    Some(description) => description.lines().map(String::from).collect(),
+                    None => command
+                        .run
+                        .lines()
+                        .map(|line| format!("Runs {line}"))
+                        .collect(),
+                },
+                run: Run::Inline(inline),
+                ..Function::default()
+            };
+            commands.insert(name, function);
+        }
+    }
+    Ok(commands.into_values().collect())
+}
+
+fn read(file: &Path) -> Result<Option<BTreeMap<String, InlineCommand>>> {
+    if !file.is_file() {
+        return Ok(None);
+    }
+    let text = std::fs::read_to_string(file).map_err(|source| Error::Read {
+        path: file.to_owned(),
+        source,
+    })?;
+    let mut table: toml::Table = toml::from_str(&text)
+        .map_err(|e| Error::Config(format!("Couldn't read {}: {e}", file.display())))?;
+    match table.remove("commands") {
+        Some(commands) => commands
+            .try_into()
+            .map(Some)
+            .map_err(|e| Error::Config(format!("commands in {} is wrong: {e}", file.display()))),
+        None => Ok(None),
+    }
+}
+
+/// The command, ready to run, or what's wrong with it.
+fn to_inline(command: &InlineCommand, dir: &Path) -> Result<Inline, String> {
+    for name in command.env.keys() {
+        let mut chars = name.chars();
+        let valid = chars
+            .next()
+            .is_some_and(|c| c.is_ascii_alphabetic() || c == '_')
+            && chars.all(|c| c.is_ascii_alphanumeric() || c == '_');
+        if !valid {
+            return Err(format!(
+                "{name} isn't a name bash can use for an environment variable"
+            ));
+        }
+    }
+    Ok(Inline {
+        run: command.run.clone(),
+        dir: match &command.cwd {
+            Some(cwd) => dir.join(cwd),
+            None => dir.to_owned(),
+        },
+        env: command.env.clone(),
+    })
+}
+
+/// The commands as the functions of a script that isn't there, so they're listed and run with the
+/// rest. They can be run by their name alone, too. None if there aren't any.
+fn script(commands: Vec<Function>, root: &Path) -> Option<Script> {
+    if commands.is_empty() {
+        return None;
+    }
+    Some(Script {
+        path: PathBuf::from(SCRIPT_NAME),
+        absolute_path: root.join("lk.toml"),
+        description: Some("Commands from lk.toml".to_string()),
+        functions: commands,
+        by_name: true,
+        ..Script::default()
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn loads_commands_from_each_file() {
+        let dir = tempfile::tempdir().unwrap();
+        let root = dir.path().join("repo");
+        let tools = root.join("tools");
+        std::fs::create_dir_all(&tools).unwrap();
+        let user = dir.path().join("user.toml");
+        std::fs::write(
+            &user,
+            "[commands.hello]\nrun = \"echo hi\"\n\n[commands.build]\nrun = \"make\"\n",
+        )
+        .unwrap();
+        std::fs::write(
+            tools.join("lk.toml"),
+            r#"[commands.build]
+run = "cargo build --release"
+description = "Builds it"
+cwd = "app"
+env = { RUST_LOG = "debug" }
+"#,
+        )
+        .unwrap();
+        let workspace = Workspace {
+            root: root.clone(),
+            files: vec![tools.join("lk.toml")],
+        };
+
+        let commands = load(&[user, tools.join("lk.toml")], &workspace).unwrap();
+
+        let names: Vec<&str> = commands.iter().map(|f| f.name.as_str()).collect();
+        assert_eq!(names, vec!["build", "hello"]);
+        let inline = |function: &Function| match &function.run {
+            Run::Inline(inline) => inline.clone(),
+            run => panic!("{run:?} isn't inline"),
+        };
+        let build = inline(&commands[0]);
+        assert_eq!(build.run, "cargo build --release");
+        assert_eq!(build.dir, tools.join("app"));
+        assert_eq!(build.env["RUST_LOG"], "debug");
+        assert_eq!(commands[0].comment, vec!["Builds it"]);
+        assert_eq!(inline(&commands[1]).dir, root);
+        assert_eq!(commands[1].comment, vec!["Runs echo hi"]);
+
+        let script = script(commands, &root).unwrap();
+        assert_eq!(script.file_name(), "commands");
+        assert!(script.by_name);
+        assert_eq!(
+            inline(script.get("build").unwrap()).run,
+            "cargo build --release"
+        );
+    }
+
+    #[test]
+    fn says_what_is_wrong_with_a_command() {
+        let dir = tempfile::tempdir().unwrap();
+        let file = dir.path().join("lk.toml");
+        let workspace = Workspace {
+            root: dir.path().to_owned(),
+            files: vec![file.clone()],
+        };
+
+        std::fs::write(
+            &file,
+            "[commands.build]\nrun = \"make\"\ndescripton = \"x\"\n",
+        )
+        .unwrap();
+        let error = load(&workspace.files, &workspace).unwrap_err().to_string();
+        assert!(error.contains("unknown field `descripton`"), "{error}");
+
+        std::fs::write(
+            &file,
+            "[commands.build]\nrun = \"make\"\nenv = { \"A-B\" = \"1\" }\n",
+        )
+        .unwrap();
+        let error = load(&workspace.files, &workspace).unwrap_err().to_string();
+        assert!(error.starts_with("commands.build in"), "{error}");
+        assert!(error.ends_with("A-B isn't a name bash can use for an environment variable"));
+
+        assert!(script(Vec::new(), dir.path()).is_none());
+    }
+}
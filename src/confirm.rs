@@ -0,0 +1,185 @@
+/// Guards dangerous functions behind a confirmation prompt.
+use crate::script::{Confirm, Function, Run, Script};
+use crate::{Error, Result};
+use glob::Pattern;
+use pastel_colours::{RED_FG, RESET_FG};
+use std::io::{BufRead, Write};
+
+/// Function name globs from the config that always need confirming.
+#[derive(Debug, Clone, Default)]
+pub struct ConfirmRules {
+    /// These get a y/N prompt.
+    confirm: Vec<Pattern>,
+    /// These make the user type the function's name.
+    confirm_name: Vec<Pattern>,
+}
+
+impl ConfirmRules {
+    pub fn new(confirm: &[String], confirm_name: &[String]) -> Result<Self> {
+        Ok(Self {
+            confirm: to_patterns(confirm)?,
+            confirm_name: to_patterns(confirm_name)?,
+        })
+    }
+
+    /// Marks every function matched by the config globs as needing confirmation. An annotation in
+    /// the script wins over a y/N glob, but typing the name is always the stricter of the two.
+    pub fn apply(&self, scripts: &mut [Script]) {
+        scripts
+            .iter_mut()
+            .for_each(|script| script.functions.iter_mut().for_each(|f| self.apply_to(f)));
+    }
+
+    /// The functions an alias's steps run are marked too, because each one asks when it runs.
+    pub fn apply_to(&self, function: &mut Function) {
+        if let Run::Steps(steps) = &mut function.run {
+            steps
+                .iter_mut()
+                .flatten()
+                .for_each(|step| self.apply_to(&mut step.function));
+        }
+        let name = function.name.as_str();
+        if self.confirm_name.iter().any(|p| p.matches(name)) {
+            let message = match function.confirm.take() {
+                Some(Confirm::YesNo(message)) | Some(Confirm::TypeName(message)) => message,
+                None => None,
+            };
+            function.confirm = Some(Confirm::TypeName(message));
+        } else if function.confirm.is_none() && self.confirm.iter().any(|p| p.matches(name)) {
+            function.confirm = Some(Confirm::YesNo(None));
+        }
+    }
+}
+
+fn to_patterns(globs: &[String]) -> Result<Vec<Pattern>> {
+    globs
+        .iter()
+        .map(|glob| {
+            Pattern::new(glob).map_err(|source| Error::BadGlob {
+                pattern: glob.to_string(),
+                source,
+            })
+        })
+        .collect()
+}
+
+/// Asks the user to confirm they want to run the function, if it needs confirming. Returns
+/// `true` if we should go ahead. No answer at all, e.g. because stdin is closed, is a no.
+pub fn confirm(
+    function: &Function,
+    mut input: impl BufRead,
+    mut output: impl Write,
+) -> Result<bool> {
+    let (message, type_name) = match &function.confirm {
+        None => return Ok(true),
+        Some(Confirm::YesNo(message)) => (message, false),
+        Some(Confirm::TypeName(message)) => (message, true),
+    };
+    if let Some(message) = message {
+        writeln!(output, "{RED_FG}{message}{RESET_FG}")?;
+    }
+    if type_name {
+        write!(
+            output,
+            "Type the function name ({}) to run it: ",
+            function.name
+        )?;
+    } else {
+        write!(output, "Run {}? [y/N] ", function.name)?;
+    }
+    output.flush()?;
+
+    let mut answer = String::new();
+    input.read_line(&mut answer)?;
+    let answer = answer.trim();
+    Ok(if type_name {
+        answer == function.name
+    } else {
+        answer.eq_ignore_ascii_case("y") || answer.eq_ignore_ascii_case("yes")
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn function(confirm: Option<Confirm>) -> Function {
+        Function {
+            name: "drop_production_db".to_string(),
+            confirm,
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn no_prompt_when_not_needed() {
+        let mut output = Vec::new();
+        assert!(confirm(&function(None), "".as_bytes(), &mut output).unwrap());
+        assert!(output.is_empty());
+    }
+
+    #[test]
+    fn yes_no_prompt() {
+        let f = function(Some(Confirm::YesNo(Some(
+            "This will wipe prod".to_string(),
+        ))));
+        let mut output = Vec::new();
+        assert!(confirm(&f, "y\n".as_bytes(), &mut output).unwrap());
+        assert!(String::from_utf8(output)
+            .unwrap()
+            .contains("This will wipe prod"));
+        assert!(!confirm(&f, "\n".as_bytes(), Vec::new()).unwrap());
+        assert!(!confirm(&f, "".as_bytes(), Vec::new()).unwrap());
+    }
+
+    #[test]
+    fn type_name_prompt() {
+        let f = function(Some(Confirm::TypeName(None)));
+        assert!(confirm(&f, "drop_production_db\n".as_bytes(), Vec::new()).unwrap());
+        assert!(!confirm(&f, "y\n".as_bytes(), Vec::new()).unwrap());
+    }
+
+    #[test]
+    fn rules_mark_matching_functions() {
+        let rules =
+            ConfirmRules::new(&["drop_*".to_string()], &["*_production_*".to_string()]).unwrap();
+
+        let mut yes_no = Function {
+            name: "drop_staging_db".to_string(),
+            ..Default::default()
+        };
+        rules.apply_to(&mut yes_no);
+        assert_eq!(yes_no.confirm, Some(Confirm::YesNo(None)));
+
+        let message = Some("This will wipe prod".to_string());
+        let mut typed = function(Some(Confirm::YesNo(message.clone())));
+        rules.apply_to(&mut typed);
+        assert_eq!(typed.confirm, Some(Confirm::TypeName(message)));
+
+        let mut safe = Function {
+            name: "list_dbs".to_string(),
+            ..Default::default()
+        };
+        rules.apply_to(&mut safe);
+        assert_eq!(safe.confirm, None);
+
+        let mut alias = Function {
+            name: "reset".to_string(),
+            run: Run::Steps(vec![Ok(crate::Job {
+                script: Script::default(),
+                function: function(None),
+                params: Vec::new(),
+            })]),
+            ..Default::default()
+        };
+        rules.apply_to(&mut alias);
+        assert_eq!(alias.confirm, None);
+        let Run::Steps(steps) = &alias.run else {
+            unreachable!()
+        };
+        assert_eq!(
+            steps[0].as_ref().unwrap().function.confirm,
+            Some(Confirm::TypeName(None))
+        );
+    }
+}
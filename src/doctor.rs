@@ -0,0 +1,136 @@
+use crate::executables::Executables;
+use crate::shells::UserShell;
+/// Explains what lk can see and why, and checks it has what it needs to run functions.
+use crate::xdg::LkDirs;
+use crate::Result;
+use pastel_colours::{BLUE_FG, DARK_BLUE_BG, GREEN_FG, ORANGE_FG, RED_FG, RESET_BG, RESET_FG};
+use std::fs::OpenOptions;
+use std::io::Write;
+use std::path::{Path, PathBuf};
+use std::process::Command;
+
+/// What lk was set up with, so the doctor can report it.
+pub struct Setup<'a> {
+    /// The root of the project, where we look for scripts from.
+    pub root: &'a Path,
+    /// The config files lk looks for, in the order they're loaded.
+    pub config_files: &'a [PathBuf],
+    pub includes: &'a [String],
+    pub excludes: &'a [String],
+    pub dirs: &'a LkDirs,
+    pub log_file: &'a Path,
+}
+
+/// Prints the report. Returns 1 if any of the checks failed, so lk can't run functions properly.
+pub fn doctor(setup: &Setup, executables: &Executables) -> Result<i32> {
+    heading("Config");
+    println!("  project root: {}", setup.root.to_string_lossy());
+    for file in setup.config_files {
+        let status = if file.exists() {
+            format!("{GREEN_FG}found{RESET_FG}")
+        } else {
+            "not found".to_string()
+        };
+        println!("  {BLUE_FG}{}{RESET_FG}: {status}", file.to_string_lossy());
+    }
+    println!("  includes: {}", setup.includes.join(", "));
+    println!("  excludes: {}", setup.excludes.join(", "));
+    println!("  config dir: {}", setup.dirs.config.to_string_lossy());
+    println!(
+        "  state dir (logs and history): {}",
+        setup.dirs.state.to_string_lossy()
+    );
+    println!("  cache dir: {}", setup.dirs.cache.to_string_lossy());
+
+    heading("Files");
+    for (path, verdict) in &executables.considered {
+        let status = if verdict.included() {
+            format!("{GREEN_FG}included{RESET_FG}")
+        } else {
+            format!("{ORANGE_FG}skipped{RESET_FG} ")
+        };
+        println!("  {status} {}: {verdict}", path.to_string_lossy());
+    }
+    for failure in &executables.failures {
+        println!(
+            "  {RED_FG}failed{RESET_FG}   {}: {}",
+            failure.path.to_string_lossy(),
+            failure.reason
+        );
+    }
+
+    heading("Shell");
+    println!(
+        "  SHELL is {}",
+        std::env::var("SHELL").unwrap_or_else(|_| "not set".to_string())
+    );
+    match UserShell::new() {
+        Some(shell) => println!("  lk adds what you run to {}", shell.history_file()),
+        None => println!(
+            "  lk couldn't find your shell's history file, so it won't add what you run to it. {} says why.",
+            setup.log_file.to_string_lossy()
+        ),
+    }
+
+    heading("Checks");
+    let checks = [
+        ("bash runs", check_bash()),
+        ("the temp dir is writable", check_temp_dir()),
+        ("the log file is writable", check_writable(setup.log_file)),
+    ];
+    let mut code = 0;
+    for (name, result) in checks {
+        match result {
+            Ok(detail) => println!("  {GREEN_FG}ok{RESET_FG}     {name}: {detail}"),
+            Err(err) => {
+                println!("  {RED_FG}failed{RESET_FG} {name}: {err}");
+                code = 1;
+            }
+        }
+    }
+    Ok(code)
+}
+
+fn heading(text: &str) {
+    println!("{DARK_BLUE_BG}lk doctor: {text}{RESET_BG}");
+}
+
+/// lk runs every function with bash, so it had better be there. A check that fails says why.
+fn check_bash() -> Result<String, String> {
+    let output = Command::new("bash")
+        .arg("--version")
+        .output()
+        .map_err(|e| format!("Couldn't run bash: {e}"))?;
+    let version = String::from_utf8_lossy(&output.stdout);
+    Ok(version.lines().next().unwrap_or_default().to_string())
+}
+
+/// lk writes a temporary file for every function it runs.
+fn check_temp_dir() -> Result<String, String> {
+    let dir =
+        tempfile::tempdir().map_err(|e| format!("Couldn't make a temporary directory: {e}"))?;
+    check_writable(&dir.path().join("lk_doctor"))?;
+    Ok(std::env::temp_dir().to_string_lossy().to_string())
+}
+
+fn check_writable(path: &Path) -> Result<String, String> {
+    OpenOptions::new()
+        .create(true)
+        .append(true)
+        .open(path)
+        .and_then(|mut file| file.flush())
+        .map_err(|e| format!("Couldn't write to {}: {e}", path.to_string_lossy()))?;
+    Ok(path.to_string_lossy().to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn checks_files_are_writable() {
+        let dir = tempfile::tempdir().unwrap();
+        assert!(check_writable(&dir.path().join("lk.log")).is_ok());
+        assert!(check_writable(&dir.path().join("missing").join("lk.log")).is_err());
+    }
+}
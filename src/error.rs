@@ -0,0 +1,92 @@
+/// What can go wrong when lk finds or runs functions. The messages are written for people, because
+/// the lk binary shows them as they are.
+use std::path::PathBuf;
+
+#[derive(Debug, thiserror::Error)]
+#[non_exhaustive]
+pub enum Error {
+    /// `Executables::new` needs at least one glob to look in.
+    #[error("No includes specified.")]
+    NoIncludes,
+    /// One of the includes, excludes or confirm globs isn't a glob.
+    #[error("Invalid glob: {pattern}")]
+    BadGlob {
+        pattern: String,
+        #[source]
+        source: glob::PatternError,
+    },
+    #[error("Invalid hide pattern: {pattern}")]
+    BadHidePattern {
+        pattern: String,
+        #[source]
+        source: regex::Error,
+    },
+    #[error("Invalid redact pattern: {pattern}")]
+    BadRedactPattern {
+        pattern: String,
+        #[source]
+        source: regex::Error,
+    },
+    #[error("Couldn't read {}", path.display())]
+    Read {
+        path: PathBuf,
+        #[source]
+        source: std::io::Error,
+    },
+    #[error("Couldn't write {}", path.display())]
+    Write {
+        path: PathBuf,
+        #[source]
+        source: std::io::Error,
+    },
+    #[error("Couldn't create {}", path.display())]
+    CreateDir {
+        path: PathBuf,
+        #[source]
+        source: std::io::Error,
+    },
+    /// Something in lk.toml is wrong, e.g. one of its commands.
+    #[error("{0}")]
+    Config(String),
+    /// The settings couldn't be put together, e.g. from a file config-rs couldn't read.
+    #[error(transparent)]
+    Settings(#[from] config::ConfigError),
+    /// The script runs commands whenever it's sourced, and the runner's been told not to run
+    /// functions from scripts like that.
+    #[error("Didn't run {function} because {script} runs commands whenever it's sourced: {side_effects}. Put them in a function or behind a guard, or use --force.")]
+    SideEffects {
+        function: String,
+        script: String,
+        side_effects: String,
+    },
+    /// One of an alias's steps names a script or function lk didn't find.
+    #[error("Alias {alias}: {problem}")]
+    BadStep { alias: String, problem: String },
+    /// Functions that run other functions one after the other can't run alongside others.
+    #[error(
+        "{function} runs {steps} functions one after the other, so it can't run alongside others"
+    )]
+    NotParallel { function: String, steps: usize },
+    /// The function needed confirming, and the user didn't.
+    #[error("Didn't run {function} because it wasn't confirmed. Use --yes to skip confirmation.")]
+    NotConfirmed { function: String },
+    #[error(transparent)]
+    Io(#[from] std::io::Error),
+    #[error(transparent)]
+    Json(#[from] serde_json::Error),
+}
+
+impl Error {
+    /// The error and what caused it, e.g. "Couldn't read a.sh: Permission denied".
+    pub fn report(&self) -> String {
+        let mut report = self.to_string();
+        let mut source = std::error::Error::source(self);
+        while let Some(error) = source {
+            report.push_str(&format!(": {error}"));
+            source = error.source();
+        }
+        report
+    }
+}
+
+pub type Result<T, E = Error> = std::result::Result<T, E>;
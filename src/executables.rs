@@ -1,158 +1,544 @@
 /// Finds executables in the current directory.
-use crate::ui::print_root_header;
-use anyhow::{bail, Result};
+use crate::{Error, Result};
 use content_inspector::{inspect, ContentType};
-use glob::glob;
-use log::info;
+use glob::{MatchOptions, Pattern};
 #[cfg(not(test))]
-use log::{debug, error};
-use pad::{Alignment, PadStr};
-use pastel_colours::{DARK_GREEN_FG, RESET_FG};
-use std::{fs::Permissions, io::Read, os::unix::fs::PermissionsExt, path::PathBuf};
+use log::debug;
+use log::info;
+use serde::{Deserialize, Serialize};
 #[cfg(test)]
-use std::{println as debug, println as error}; //
+use std::println as debug;
+use std::{
+    collections::HashMap,
+    fs::Permissions,
+    io::Read,
+    os::unix::fs::PermissionsExt,
+    path::{Path, PathBuf},
+}; //
 
 #[derive(Debug)]
+#[non_exhaustive]
 pub struct Executable {
     pub short_name: String,
     pub path: PathBuf,
     pub absolute_path: PathBuf,
+    /// We only found this because we're including scripts without the execute bit.
+    pub non_executable: bool,
+    /// The path goes through a symlink, to the file or one of its directories, so the script is
+    /// really at `absolute_path`.
+    pub via_symlink: bool,
 }
 
+impl Executable {
+    /// The script at `path`, for when you know where it is rather than looking for it with
+    /// `Executables::new`.
+    pub fn new(path: &Path) -> Self {
+        Self {
+            short_name: path
+                .file_name()
+                .map(|name| name.to_string_lossy().to_string())
+                .unwrap_or_default(),
+            path: path.to_owned(),
+            absolute_path: path.canonicalize().unwrap_or_else(|_| path.to_owned()),
+            non_executable: false,
+            via_symlink: false,
+        }
+    }
+}
+
+/// What to do with shell scripts that don't have the execute bit set. This is `non_executable`
+/// in lk.toml.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize, Serialize)]
+pub enum NonExecutable {
+    /// Leave them out. This is the default.
+    #[serde(rename = "skip")]
+    Skip,
+    /// Include `.sh` and `.bash` files.
+    #[serde(rename = "extension")]
+    ByExtension,
+    /// Include files with a bash or sh hashbang, whatever they're called.
+    #[serde(rename = "shebang")]
+    ByShebang,
+}
+
+#[non_exhaustive]
 pub struct Executables {
     // root: String,
     pub executables: Vec<Executable>,
+    /// Shell scripts we left out because they aren't executable, so we can tell the user.
+    pub skipped: Vec<PathBuf>,
+    /// Files and directories we couldn't look at, and why. We carry on without them.
+    pub failures: Vec<Failure>,
+    /// Every file we looked at, and whether we included it, so `lk doctor` can explain.
+    pub considered: Vec<(PathBuf, Verdict)>,
+}
+
+/// Why we did or didn't include a file.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Verdict {
+    Included,
+    /// Included, but it's too small to tell whether it's binary.
+    IncludedTiny,
+    /// Included without the execute bit, because of the `non_executable` setting.
+    IncludedNotExecutable,
+    /// It's in, or is, this excluded path.
+    Excluded(PathBuf),
+    Directory,
+    NotExecutable,
+    NoPermission,
+    Binary,
+    /// A symlink back to a directory we're already in, so we didn't follow it.
+    SymlinkLoop,
+    /// The same file as this one, which we did include.
+    Duplicate(PathBuf),
+}
+
+impl Verdict {
+    pub fn included(&self) -> bool {
+        matches!(
+            self,
+            Self::Included | Self::IncludedTiny | Self::IncludedNotExecutable
+        )
+    }
+}
+
+impl std::fmt::Display for Verdict {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Included => write!(f, "an executable text file"),
+            Self::IncludedTiny => write!(f, "too small to tell if it's binary, so we assume not"),
+            Self::IncludedNotExecutable => {
+                write!(f, "not executable, but non_executable lets it in")
+            }
+            Self::Excluded(exclude) => write!(f, "excluded by {}", exclude.to_string_lossy()),
+            Self::Directory => write!(f, "a directory"),
+            Self::NotExecutable => write!(f, "not executable"),
+            Self::NoPermission => write!(f, "we don't have permission to use it"),
+            Self::Binary => write!(f, "a binary file"),
+            Self::SymlinkLoop => write!(f, "a symlink loop, so we didn't follow it"),
+            Self::Duplicate(of) => write!(f, "the same file as {}", of.to_string_lossy()),
+        }
+    }
+}
+
+/// Something lk couldn't use, and why, e.g. a broken symlink or a file it can't read.
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[non_exhaustive]
+pub struct Failure {
+    pub path: PathBuf,
+    pub reason: String,
+}
+
+impl Failure {
+    pub fn new(path: &Path, reason: impl std::fmt::Display) -> Self {
+        let failure = Self {
+            path: path.to_owned(),
+            reason: reason.to_string(),
+        };
+        log::warn!("Skipping {:?}: {}", failure.path, failure.reason);
+        failure
+    }
 }
 
 impl Executables {
-    pub fn new(includes: &[String], excludes: &[String]) -> Result<Self> {
+    /// Finds the files matching the includes, less those matching the excludes. Relative globs
+    /// are relative to the current directory, so pass absolute ones if that might not be the
+    /// project.
+    ///
+    /// ```
+    /// use lk::{Executables, NonExecutable};
+    ///
+    /// # fn main() -> lk::Result<()> {
+    /// let project = tempfile::tempdir()?;
+    /// std::fs::create_dir(project.path().join("target"))?;
+    /// std::fs::write(project.path().join("target/build.sh"), "")?;
+    ///
+    /// let root = project.path().display();
+    /// let executables = Executables::new(
+    ///     &[format!("{root}/**/*.sh")],
+    ///     &[format!("{root}/target")],
+    ///     NonExecutable::Skip,
+    /// )?;
+    /// assert!(executables.executables.is_empty());
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn new(includes: &[String], excludes: &[String], mode: NonExecutable) -> Result<Self> {
         // Validate to ensure there is at least on include.
         if includes.is_empty() {
-            bail!("No includes specified.");
+            return Err(Error::NoIncludes);
         }
 
         // Get all the excluded files
+        let mut failures: Vec<Failure> = Vec::new();
+        let mut considered: Vec<(PathBuf, Verdict)> = Vec::new();
         let mut files_to_exclude: Vec<PathBuf> = Vec::new();
         for exclude in excludes {
             debug!("Excluding: {}", exclude);
-            for entry in glob(exclude)? {
-                match entry {
-                    Ok(path) => files_to_exclude.push(path),
-                    Err(e) => error!("{:?}", e),
-                }
-            }
+            files_to_exclude.extend(expand_glob(exclude, &[], &mut failures, &mut considered)?);
         }
 
         // Get all the included files but not the excluded ones.
         let mut files_to_include: Vec<PathBuf> = Vec::new();
+        let mut non_executable: Vec<PathBuf> = Vec::new();
+        let mut skipped: Vec<PathBuf> = Vec::new();
         for include in includes {
             debug!("Including: {}", include);
-            for entry in glob(include)? {
-                match entry {
-                    Ok(path) => {
-                        debug!("Checking path: {:?}", path);
-                        // Exclude subpaths and full paths that are in the excludes list.
-                        let is_subpath = files_to_exclude
-                            .iter()
-                            .any(|exclude| path.starts_with(exclude));
-
-                        if !files_to_exclude.contains(&path)
-                            && !is_subpath
-                            && should_include_file(&path)
-                        {
+            for path in expand_glob(include, &files_to_exclude, &mut failures, &mut considered)? {
+                debug!("Checking path: {:?}", path);
+                // Exclude subpaths and full paths that are in the excludes list.
+                if let Some(exclude) = files_to_exclude
+                    .iter()
+                    .find(|exclude| path.starts_with(exclude))
+                {
+                    considered.push((path, Verdict::Excluded(exclude.to_owned())));
+                    continue;
+                }
+                let verdict = match check_file(&path) {
+                    Ok(Verdict::NotExecutable) if is_non_executable_script(&path, mode) => {
+                        debug!("Including non-executable {:?}", path);
+                        non_executable.push(path.clone());
+                        Verdict::IncludedNotExecutable
+                    }
+                    Ok(Verdict::NotExecutable) => {
+                        if is_non_executable_script(&path, NonExecutable::ByExtension) {
+                            skipped.push(path.clone());
+                        }
+                        Verdict::NotExecutable
+                    }
+                    Ok(verdict) => {
+                        if verdict.included() {
                             debug!("Including {:?}", path);
-                            files_to_include.push(path);
+                            files_to_include.push(path.clone());
                         }
+                        verdict
+                    }
+                    Err(reason) => {
+                        failures.push(Failure::new(&path, reason));
+                        continue;
                     }
-                    Err(e) => error!("{:?}", e),
+                };
+                // There are a lot of directories, and they're not interesting.
+                if verdict != Verdict::Directory {
+                    considered.push((path, verdict));
                 }
             }
         }
 
+        non_executable.sort();
+        non_executable.dedup();
+        files_to_include.extend(non_executable.iter().cloned());
         files_to_include.sort();
         files_to_include.dedup();
         info!("Excluding {:?}", files_to_exclude);
         info!("Including {:?}", files_to_include);
 
-        let executables: Vec<Executable> = files_to_include
+        let mut executables: Vec<Executable> = files_to_include
             .into_iter()
-            .map(|include| {
-                let path = include.into_boxed_path();
-                let absolute_path = path.canonicalize().unwrap();
-                let short_name = path.file_name().unwrap().to_string_lossy().to_string();
+            .filter_map(|path| {
+                let non_executable = non_executable.binary_search(&path).is_ok();
+                // The file can go away, or lose its name, between finding it and getting here.
+                let absolute_path = match path.canonicalize() {
+                    Ok(absolute_path) => absolute_path,
+                    Err(e) => {
+                        failures.push(Failure::new(
+                            &path,
+                            format!("Couldn't find where it is: {e}"),
+                        ));
+                        return None;
+                    }
+                };
+                let Some(short_name) = path.file_name() else {
+                    failures.push(Failure::new(&path, "It doesn't have a file name"));
+                    return None;
+                };
+                let short_name = short_name.to_string_lossy().to_string();
+                let via_symlink = path.ancestors().any(Path::is_symlink);
 
-                Executable {
+                Some(Executable {
                     short_name,
-                    path: path.into_path_buf(),
+                    path,
                     absolute_path,
-                }
+                    non_executable,
+                    via_symlink,
+                })
             })
             .collect();
 
+        // A script we can reach through a symlink is found once for every way there, so we keep
+        // just one of them. That's the real path if we found it, or else the first link.
+        executables.sort_by_key(|executable| executable.via_symlink);
+        let mut kept: HashMap<PathBuf, PathBuf> = HashMap::new();
+        let mut duplicates: Vec<(PathBuf, PathBuf)> = Vec::new();
+        executables.retain(|executable| match kept.get(&executable.absolute_path) {
+            Some(first) => {
+                duplicates.push((executable.path.clone(), first.clone()));
+                false
+            }
+            None => {
+                kept.insert(executable.absolute_path.clone(), executable.path.clone());
+                true
+            }
+        });
+        executables.sort_by(|a, b| a.path.cmp(&b.path));
+
+        skipped.sort();
+        skipped.dedup();
+        // Includes can overlap, so we might have tried the same file more than once.
+        failures.sort_by(|a, b| a.path.cmp(&b.path));
+        failures.dedup();
+        considered.sort_by(|a, b| a.0.cmp(&b.0));
+        considered.dedup_by(|a, b| a.0 == b.0);
+        considered.retain(|(path, _)| !failures.iter().any(|failure| &failure.path == path));
+        for (path, first) in duplicates {
+            if let Some(entry) = considered.iter_mut().find(|(p, _)| *p == path) {
+                entry.1 = Verdict::Duplicate(first);
+            }
+        }
+
         debug!("{:?}", executables);
-        Ok(Self { executables })
+        Ok(Self {
+            executables,
+            skipped,
+            failures,
+            considered,
+        })
     }
 
-    pub fn get(&self, name: &str) -> Option<&Executable> {
-        self.executables
+    /// The scripts that need the execute bit: the ones we skipped, and the ones we only included
+    /// because of the `non_executable` setting.
+    pub fn without_execute_bit(&self) -> Vec<&Path> {
+        let mut paths: Vec<&Path> = self
+            .skipped
             .iter()
-            .find(|&executable| executable.short_name == name)
+            .map(PathBuf::as_path)
+            .chain(
+                self.executables
+                    .iter()
+                    .filter(|executable| executable.non_executable)
+                    .map(|executable| executable.path.as_path()),
+            )
+            .collect();
+        paths.sort();
+        paths
     }
+}
 
-    /// Pretty-prints the executables we found on the path, so the
-    /// user can select one to run.
-    pub fn pretty_print(&self) {
-        print_root_header();
-        // Get the longest executable name
-        const INDENT: usize = 2;
-        let padding = if self.executables.is_empty() {
-            0 // If we don't have any executables we don't care about the padding, so just use 0.
-        } else {
-            self.executables
-                .iter()
-                .max_by(|x, y| x.short_name.len().cmp(&y.short_name.len()))
-                .unwrap() // Will always be Some because the name String must exist.
-                .short_name
-                .len()
-                + INDENT
-        };
-        self.executables.iter().for_each(|executable| {
-            let path = executable.path.as_os_str().to_string_lossy().to_string();
-            // We'll pad right so everything aligns nicely.
-            let to_print = executable
-                .short_name
-                .pad_to_width_with_alignment(padding, Alignment::Right);
-            println!("{DARK_GREEN_FG}{to_print}{RESET_FG} - {path}");
-        });
-    }
+/// Sets the execute bit for whoever can read the file, like `chmod +x` with the usual umask. This
+/// is `lk fix-perms`.
+pub fn make_executable(path: &Path) -> Result<()> {
+    let mut permissions = std::fs::metadata(path)?.permissions();
+    permissions.set_mode(permissions.mode() | (permissions.mode() & 0o444) >> 2);
+    std::fs::set_permissions(path, permissions)?;
+    Ok(())
 }
 
-/// Determines whether or not we should include this entry in our search results
-fn should_include_file(path: &PathBuf) -> bool {
+/// Determines whether or not we should include this entry in our search results. It's an error,
+/// with the reason, if we can't tell.
+fn check_file(path: &PathBuf) -> Result<Verdict, String> {
     // We'll need to check file permissions
     let permissions = match path.metadata() {
         Ok(metadata) => metadata.permissions(),
-        Err(_) => panic!("Couldn't get file metadata for {:?}!", path),
+        Err(_) if path.is_symlink() => {
+            return Err("It's a symlink to something that isn't there".to_string())
+        }
+        Err(e) => return Err(format!("Couldn't get its metadata: {e}")),
     };
 
+    // We're ignoring dirs, obviously
+    if path.is_dir() {
+        return Ok(Verdict::Directory);
+    }
     // If we don't have permissions to access the file we're not going to get very far.
-    if has_permissions(&permissions)
-        // We're ignoring dirs, obviously
-        && !path.is_dir()
-        // We're including executables
-        && is_executable(&permissions)
-        // We're ignoring symlinks (for now)
-        && !path.is_symlink()
-    {
-        // This involves reading the first few bytes if the file, and for performance reasons
-        // we want to do this as little as possible. So it's the last thing we check.
-        if !is_binary(path)
+    if !has_permissions(&permissions) {
+        return Ok(Verdict::NoPermission);
+    }
+    // We're including executables
+    if !is_executable(&permissions) {
+        return Ok(Verdict::NotExecutable);
+    }
+    // This involves reading the first few bytes if the file, and for performance reasons
+    // we want to do this as little as possible. So it's the last thing we check.
+    Ok(match inspect_start(path) {
+        Content::Text => Verdict::Included,
+        Content::Tiny => Verdict::IncludedTiny,
         // We're ignoring binary files
-        {
-            return true;
+        Content::Binary => Verdict::Binary,
+    })
+}
+
+/// What the start of a file tells us about it.
+enum Content {
+    Text,
+    Binary,
+    /// There isn't enough of it to tell.
+    Tiny,
+}
+
+/// Whether this is a shell script without the execute bit that we'd include with this setting.
+fn is_non_executable_script(path: &Path, mode: NonExecutable) -> bool {
+    let is_shell = match mode {
+        NonExecutable::Skip => false,
+        NonExecutable::ByExtension => matches!(
+            path.extension().and_then(|e| e.to_str()),
+            Some("sh") | Some("bash")
+        ),
+        NonExecutable::ByShebang => has_shell_shebang(path),
+    };
+    is_shell
+        && path.is_file()
+        && path
+            .metadata()
+            .map(|metadata| !is_executable(&metadata.permissions()))
+            .unwrap_or(false)
+}
+
+/// Whether the first line is a bash or sh hashbang, e.g. `#!/bin/sh` or `#!/usr/bin/env bash`.
+fn has_shell_shebang(path: &Path) -> bool {
+    let Ok(file) = std::fs::File::open(path) else {
+        return false;
+    };
+    // A hashbang line is short, so there's no need to read any more than this.
+    let mut first_line = String::new();
+    if std::io::BufRead::read_line(
+        &mut std::io::BufReader::new(file.take(128)),
+        &mut first_line,
+    )
+    .is_err()
+    {
+        return false;
+    }
+    let Some(interpreter) = first_line.trim().strip_prefix("#!") else {
+        return false;
+    };
+    let mut words = interpreter.split_whitespace();
+    let program = match words.next() {
+        Some(env) if env.ends_with("/env") => words.next(),
+        program => program,
+    };
+    matches!(
+        program.and_then(|p| p.rsplit('/').next()),
+        Some("bash") | Some("sh")
+    )
+}
+
+/// Finds the paths that match the glob. This is what `glob::glob` does, except that it follows
+/// symlinked directories without going round in circles, and doesn't go into excluded
+/// directories at all.
+fn expand_glob(
+    pattern: &str,
+    excludes: &[PathBuf],
+    failures: &mut Vec<Failure>,
+    considered: &mut Vec<(PathBuf, Verdict)>,
+) -> Result<Vec<PathBuf>> {
+    let matcher = Pattern::new(pattern).map_err(|source| Error::BadGlob {
+        pattern: pattern.to_string(),
+        source,
+    })?;
+    // Everything up to the first wildcard is where we start looking.
+    let mut base = PathBuf::new();
+    let mut rest = Vec::new();
+    for component in Path::new(pattern).components() {
+        let text = component.as_os_str().to_string_lossy();
+        if rest.is_empty() && !text.contains(['*', '?', '[']) {
+            base.push(component);
+        } else {
+            rest.push(text.to_string());
         }
     }
-    false
+    if rest.is_empty() {
+        // No wildcards, so it's just a path.
+        return Ok(if base.symlink_metadata().is_ok() {
+            vec![base]
+        } else {
+            vec![]
+        });
+    }
+    // Without a `**` we don't need to look any deeper than the pattern goes.
+    let max_depth = (!rest.iter().any(|part| part == "**")).then_some(rest.len());
+
+    let mut walk = Walk {
+        matcher,
+        options: MatchOptions {
+            require_literal_separator: true,
+            ..MatchOptions::new()
+        },
+        excludes,
+        max_depth,
+        found: Vec::new(),
+        ancestors: Vec::new(),
+        failures,
+        considered,
+    };
+    walk.dir(&base, 1);
+    Ok(walk.found)
+}
+
+/// The state of a walk through the file system for `expand_glob`.
+struct Walk<'a> {
+    matcher: Pattern,
+    options: MatchOptions,
+    excludes: &'a [PathBuf],
+    max_depth: Option<usize>,
+    found: Vec<PathBuf>,
+    /// Where the directories we're in really are, so we can spot a symlink back to one of them.
+    ancestors: Vec<PathBuf>,
+    failures: &'a mut Vec<Failure>,
+    considered: &'a mut Vec<(PathBuf, Verdict)>,
+}
+
+impl Walk<'_> {
+    fn dir(&mut self, dir: &Path, depth: usize) {
+        // An empty path is the current directory, but we want the paths we find to be relative.
+        let to_read = if dir.as_os_str().is_empty() {
+            Path::new(".")
+        } else {
+            dir
+        };
+        let real_dir = match to_read.canonicalize() {
+            Ok(real_dir) => real_dir,
+            Err(e) => {
+                self.failures
+                    .push(Failure::new(dir, format!("Couldn't look in it: {e}")));
+                return;
+            }
+        };
+        if self.ancestors.contains(&real_dir) {
+            debug!("Not following {:?} because it's a symlink loop", dir);
+            self.considered.push((dir.to_owned(), Verdict::SymlinkLoop));
+            return;
+        }
+        let entries = match std::fs::read_dir(to_read) {
+            Ok(entries) => entries,
+            Err(e) => {
+                self.failures
+                    .push(Failure::new(dir, format!("Couldn't look in it: {e}")));
+                return;
+            }
+        };
+        let mut paths: Vec<PathBuf> = entries
+            .filter_map(|entry| entry.ok())
+            .map(|entry| dir.join(entry.file_name()))
+            .collect();
+        paths.sort();
+
+        self.ancestors.push(real_dir);
+        for path in paths {
+            if self.matcher.matches_path_with(&path, self.options) {
+                self.found.push(path.clone());
+            }
+            let excluded = self
+                .excludes
+                .iter()
+                .any(|exclude| path.starts_with(exclude));
+            let deeper = self.max_depth.is_none_or(|max_depth| depth < max_depth);
+            // is_dir follows symlinks, which is what we want.
+            if deeper && !excluded && path.is_dir() {
+                self.dir(&path, depth + 1);
+            }
+        }
+        self.ancestors.pop();
+    }
 }
 
 fn has_permissions(permissions: &Permissions) -> bool {
@@ -165,7 +551,7 @@ fn is_executable(permissions: &Permissions) -> bool {
     permissions.mode() & 0o111 != 0
 }
 
-fn is_binary(path: &PathBuf) -> bool {
+fn inspect_start(path: &PathBuf) -> Content {
     // let path = entry.path();
     let path_str = path.to_string_lossy();
 
@@ -179,7 +565,7 @@ fn is_binary(path: &PathBuf) -> bool {
                 path_str,
                 err
             );
-            return false;
+            return Content::Text;
         }
     };
 
@@ -189,16 +575,15 @@ fn is_binary(path: &PathBuf) -> bool {
 
     let head = std::io::BufReader::new(file).read_exact(&mut buffer);
     match head {
-        Ok(_) => inspect(&buffer) == ContentType::BINARY,
+        Ok(_) if inspect(&buffer) == ContentType::BINARY => Content::Binary,
+        Ok(_) => Content::Text,
+        Err(err) if err.kind() == std::io::ErrorKind::UnexpectedEof => {
+            log::debug!("Found a tiny file and didn't read it all. Path: {path_str}");
+            Content::Tiny
+        }
         Err(err) => {
-            if err.to_string().as_str() == "failed to fill whole buffer" {
-                log::debug!(
-                    "Found a tiny file and didn't read it all. Ignoring it. Path: {path_str}"
-                );
-            } else {
-                log::error!("Unable to read file: {path_str}. The error was: {err}");
-            }
-            false
+            log::error!("Unable to read file: {path_str}. The error was: {err}");
+            Content::Text
         }
     }
 }
@@ -209,14 +594,18 @@ mod tests {
 
     #[test]
     fn default_should_include_all_files() {
-        let executables = Executables::new(&["tests/**/*".to_string()], &[]);
+        let executables = Executables::new(&["tests/**/*".to_string()], &[], NonExecutable::Skip);
         // This depends on the number of scripts in the tests directory - so please take care when changing those files.
         assert_eq!(executables.unwrap().executables.len(), 10);
     }
 
     #[test]
     fn should_include_only_specific_folder() {
-        let executables = Executables::new(&["tests/executables_tests/**/*".to_string()], &[]);
+        let executables = Executables::new(
+            &["tests/executables_tests/**/*".to_string()],
+            &[],
+            NonExecutable::Skip,
+        );
         // This depends on the number of scripts in the tests directory - so please take care when changing those files.
         assert_eq!(executables.unwrap().executables.len(), 4);
     }
@@ -229,6 +618,7 @@ mod tests {
                 "tests/depends_on_file/**/*".to_string(),
             ],
             &[],
+            NonExecutable::Skip,
         );
         // This depends on the number of scripts in the tests directory - so please take care when changing those files.
         assert_eq!(executables.unwrap().executables.len(), 6);
@@ -242,6 +632,7 @@ mod tests {
                 "tests/depends_on_file/**/*".to_string(),
                 "tests/executables_tests/**/*".to_string(),
             ],
+            NonExecutable::Skip,
         );
         // This depends on the number of scripts in the tests directory - so please take care when changing those files.
         assert_eq!(executables.unwrap().executables.len(), 4);
@@ -253,6 +644,7 @@ mod tests {
             &["tests/**/*.*".to_string()],
             // FIXME: this feels like an invalid glob. When this was in the excludes in lk.toml the whole thing hung. That' needs fixing.
             &["tests/**/exclude_me".to_string()],
+            NonExecutable::Skip,
         );
         // This depends on the number of scripts in the tests directory - so please take care when changing those files.
         assert_eq!(executables.unwrap().executables.len(), 9);
@@ -263,21 +655,183 @@ mod tests {
         let executables = Executables::new(
             &["tests/**/*".to_string()],
             &["tests/**/exclude_me/should_not_be_included.sh".to_string()],
+            NonExecutable::Skip,
         );
         // This depends on the number of scripts in the tests directory - so please take care when changing those files.
         assert_eq!(executables.unwrap().executables.len(), 9);
     }
 
+    #[test]
+    fn remembers_non_executable_scripts() {
+        let executables = Executables::new(
+            &["tests/executables_tests/*".to_string()],
+            &[],
+            NonExecutable::Skip,
+        );
+        assert_eq!(
+            executables.unwrap().skipped,
+            vec![PathBuf::from("tests/executables_tests/script03.sh")]
+        );
+    }
+
+    #[test]
+    fn includes_non_executable_scripts() {
+        let dir = tempfile::tempdir().unwrap();
+        let write = |name: &str, contents: &str| {
+            std::fs::write(dir.path().join(name), contents).unwrap();
+        };
+        write("build.sh", "# No hashbang\n");
+        write("deploy", "#!/usr/bin/env bash\n");
+        write("notes.txt", "#!/usr/bin/python3\n");
+        let include = vec![format!("{}/*", dir.path().display())];
+        let included = |mode| {
+            let executables = Executables::new(&include, &[], mode).unwrap();
+            let names: Vec<(String, bool)> = executables
+                .executables
+                .iter()
+                .map(|e| (e.short_name.clone(), e.non_executable))
+                .collect();
+            (names, executables.skipped.len())
+        };
+
+        assert_eq!(included(NonExecutable::Skip), (vec![], 1));
+        assert_eq!(
+            included(NonExecutable::ByExtension),
+            (vec![("build.sh".to_string(), true)], 0)
+        );
+        assert_eq!(
+            included(NonExecutable::ByShebang),
+            (vec![("deploy".to_string(), true)], 1)
+        );
+        assert_eq!(
+            Executables::new(&include, &[], NonExecutable::ByShebang)
+                .unwrap()
+                .without_execute_bit()
+                .len(),
+            2
+        );
+    }
+
+    #[test]
+    fn follows_symlinks_once() {
+        use std::os::unix::fs::symlink;
+        let dir = tempfile::tempdir().unwrap();
+        let shared = dir.path().join("shared");
+        std::fs::create_dir(&shared).unwrap();
+        let script = shared.join("tool.sh");
+        std::fs::write(&script, "#!/usr/bin/env bash\n").unwrap();
+        std::fs::set_permissions(&script, Permissions::from_mode(0o755)).unwrap();
+        let project = dir.path().join("project");
+        std::fs::create_dir(&project).unwrap();
+        symlink(&shared, project.join("tools")).unwrap();
+        symlink(&script, project.join("run.sh")).unwrap();
+        // A loop back to the top, which glob would otherwise go round until the path's too long.
+        symlink(dir.path(), shared.join("loop")).unwrap();
+
+        let found = |include: String| {
+            let executables = Executables::new(&[include], &[], NonExecutable::Skip).unwrap();
+            executables
+                .executables
+                .iter()
+                .map(|e| {
+                    let path = e.path.strip_prefix(dir.path()).unwrap();
+                    (path.to_string_lossy().to_string(), e.via_symlink)
+                })
+                .collect::<Vec<_>>()
+        };
+
+        assert_eq!(
+            found(format!("{}/**/*", dir.path().display())),
+            vec![("shared/tool.sh".to_string(), false)]
+        );
+        assert_eq!(
+            found(format!("{}/project/**/*", dir.path().display())),
+            vec![("project/run.sh".to_string(), true)]
+        );
+    }
+
+    #[test]
+    fn carries_on_past_broken_symlinks() {
+        let dir = tempfile::tempdir().unwrap();
+        let script = dir.path().join("good.sh");
+        std::fs::write(&script, "#!/usr/bin/env bash\n").unwrap();
+        std::fs::set_permissions(&script, Permissions::from_mode(0o755)).unwrap();
+        let broken = dir.path().join("broken.sh");
+        std::os::unix::fs::symlink(dir.path().join("gone.sh"), &broken).unwrap();
+
+        let executables = Executables::new(
+            &[format!("{}/*", dir.path().display())],
+            &[],
+            NonExecutable::Skip,
+        )
+        .unwrap();
+
+        assert_eq!(executables.executables.len(), 1);
+        assert_eq!(executables.executables[0].path, script);
+        assert_eq!(
+            executables.failures,
+            vec![Failure {
+                path: broken,
+                reason: "It's a symlink to something that isn't there".to_string()
+            }]
+        );
+    }
+
+    #[test]
+    fn explains_every_file() {
+        let dir = tempfile::tempdir().unwrap();
+        let write = |name: &str, contents: &[u8], mode: u32| {
+            let path = dir.path().join(name);
+            std::fs::write(&path, contents).unwrap();
+            std::fs::set_permissions(&path, Permissions::from_mode(mode)).unwrap();
+        };
+        write("binary", &[0, 1, 2, 3, 4, 5, 6, 7, 8, 9, 10], 0o755);
+        write("build.sh", b"#!/usr/bin/env bash\n", 0o755);
+        write("notes.txt", b"Some notes\n", 0o644);
+        write("tiny", b"x", 0o755);
+        std::fs::create_dir(dir.path().join("vendor")).unwrap();
+        write("vendor/lib.sh", b"#!/usr/bin/env bash\n", 0o755);
+
+        let executables = Executables::new(
+            &[format!("{}/**/*", dir.path().display())],
+            &[format!("{}/vendor", dir.path().display())],
+            NonExecutable::Skip,
+        )
+        .unwrap();
+
+        let verdicts: Vec<(String, Verdict)> = executables
+            .considered
+            .into_iter()
+            .map(|(path, verdict)| {
+                let path = path.strip_prefix(dir.path()).unwrap();
+                (path.to_string_lossy().to_string(), verdict)
+            })
+            .collect();
+        assert_eq!(
+            verdicts,
+            vec![
+                ("binary".to_string(), Verdict::Binary),
+                ("build.sh".to_string(), Verdict::Included),
+                ("notes.txt".to_string(), Verdict::NotExecutable),
+                ("tiny".to_string(), Verdict::IncludedTiny),
+                (
+                    "vendor".to_string(),
+                    Verdict::Excluded(dir.path().join("vendor"))
+                ),
+            ]
+        );
+    }
+
     #[test]
     fn should_fail_when_no_includes() {
-        let executables = Executables::new(&[], &[]);
+        let executables = Executables::new(&[], &[], NonExecutable::Skip);
         assert!(executables.is_err());
     }
 
     #[test]
     fn should_include_scripts_in_pwd() {
         // Should include everything in the current dir.
-        let executables = Executables::new(&["*".to_string()], &[]);
+        let executables = Executables::new(&["*".to_string()], &[], NonExecutable::Skip);
         // This depends on the number of scripts in the tests directory - so please take care when changing those files.
 
         assert!(!executables.unwrap().executables.is_empty());
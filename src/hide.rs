@@ -0,0 +1,122 @@
+/// Hides helper functions, e.g. ones starting with `_`, so the lists only show what's worth running.
+use crate::script::{Function, Script};
+use crate::{Error, Result};
+use regex::Regex;
+
+/// Name prefixes and regexes from the config that hide functions.
+#[derive(Debug, Clone, Default)]
+pub struct HideRules {
+    prefixes: Vec<String>,
+    patterns: Vec<Regex>,
+    /// Show hidden functions anyway, dimmed. This is `--show-hidden`.
+    show_hidden: bool,
+}
+
+impl HideRules {
+    pub fn new(prefixes: &[String], patterns: &[String], show_hidden: bool) -> Result<Self> {
+        let patterns = patterns
+            .iter()
+            .map(|pattern| {
+                Regex::new(pattern).map_err(|source| Error::BadHidePattern {
+                    pattern: pattern.to_string(),
+                    source,
+                })
+            })
+            .collect::<Result<Vec<Regex>>>()?;
+        Ok(Self {
+            prefixes: prefixes.to_vec(),
+            patterns,
+            show_hidden,
+        })
+    }
+
+    /// Marks every function matched by the rules as hidden, and then takes the hidden functions
+    /// out of the scripts, unless we're showing them.
+    pub fn apply(&self, scripts: &mut [Script]) {
+        for script in scripts {
+            script.functions.iter_mut().for_each(|f| self.apply_to(f));
+            if !self.show_hidden {
+                script.functions.retain(|f| !f.hidden);
+            }
+        }
+    }
+
+    /// Marks the function as hidden if the rules match it. A `# @private` or `# @hidden`
+    /// annotation has already hidden it, whatever the rules say.
+    pub fn apply_to(&self, function: &mut Function) {
+        let name = function.name.as_str();
+        if self.prefixes.iter().any(|prefix| name.starts_with(prefix))
+            || self.patterns.iter().any(|pattern| pattern.is_match(name))
+        {
+            function.hidden = true;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn script(names: &[&str]) -> Script {
+        Script {
+            path: "script.sh".into(),
+            absolute_path: "/script.sh".into(),
+            functions: names
+                .iter()
+                .map(|name| Function {
+                    name: name.to_string(),
+                    hidden: name.ends_with("annotated"),
+                    ..Default::default()
+                })
+                .collect(),
+            ..Default::default()
+        }
+    }
+
+    fn names(script: &Script) -> Vec<&str> {
+        script.functions.iter().map(|f| f.name.as_str()).collect()
+    }
+
+    #[test]
+    fn hides_prefixes_patterns_and_annotations() {
+        let rules = HideRules::new(
+            &["__".to_string(), "private_".to_string()],
+            &["^test_.*_helper$".to_string()],
+            false,
+        )
+        .unwrap();
+        let mut scripts = [script(&[
+            "deploy",
+            "_single",
+            "__double",
+            "private_thing",
+            "test_db_helper",
+            "test_db",
+            "annotated",
+        ])];
+
+        rules.apply(&mut scripts);
+
+        assert_eq!(names(&scripts[0]), vec!["deploy", "_single", "test_db"]);
+    }
+
+    #[test]
+    fn show_hidden_keeps_them_but_marks_them() {
+        let rules = HideRules::new(&["_".to_string()], &[], true).unwrap();
+        let mut scripts = [script(&["deploy", "_helper"])];
+
+        rules.apply(&mut scripts);
+
+        let hidden: Vec<(&str, bool)> = scripts[0]
+            .functions
+            .iter()
+            .map(|f| (f.name.as_str(), f.hidden))
+            .collect();
+        assert_eq!(hidden, vec![("deploy", false), ("_helper", true)]);
+    }
+
+    #[test]
+    fn bad_patterns_are_an_error() {
+        assert!(HideRules::new(&[], &["(".to_string()], false).is_err());
+    }
+}
@@ -0,0 +1,96 @@
+/// A record of the functions lk has run, so they can be looked up again later.
+use crate::Result;
+use serde::{Deserialize, Serialize};
+use std::{
+    fs::OpenOptions,
+    io::{BufRead, BufReader, Write},
+    path::{Path, PathBuf},
+};
+
+/// One run of one function. Params are always stored redacted.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[non_exhaustive]
+pub struct RunRecord {
+    pub time: String,
+    pub script: String,
+    pub function: String,
+    pub params: Vec<String>,
+    /// Where the function's output was captured, if it was.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub log: Option<String>,
+}
+
+pub struct RunHistory {
+    path: PathBuf,
+}
+
+impl RunHistory {
+    /// The history lives in `history.jsonl`, one run per line, in the given directory.
+    pub fn new(dir: &Path) -> Self {
+        Self {
+            path: dir.join("history.jsonl"),
+        }
+    }
+
+    pub fn record(&self, record: &RunRecord) -> Result<()> {
+        let mut file = OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(&self.path)?;
+        writeln!(file, "{}", serde_json::to_string(record)?)?;
+        Ok(())
+    }
+
+    /// All the runs we know about, oldest first. Lines we can't parse are skipped.
+    pub fn records(&self) -> Result<Vec<RunRecord>> {
+        if !self.path.exists() {
+            return Ok(Vec::new());
+        }
+        let file = std::fs::File::open(&self.path)?;
+        Ok(BufReader::new(file)
+            .lines()
+            .map_while(Result::ok)
+            .filter_map(|line| serde_json::from_str(&line).ok())
+            .collect())
+    }
+
+    /// Runs that captured their output, most recent first.
+    pub fn logged_records(&self) -> Result<Vec<RunRecord>> {
+        Ok(self
+            .records()?
+            .into_iter()
+            .rev()
+            .filter(|record| record.log.is_some())
+            .collect())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn logged_records_are_most_recent_first() {
+        let dir = tempfile::tempdir().unwrap();
+        let history = RunHistory::new(dir.path());
+        let record = |function: &str, log: Option<&str>| RunRecord {
+            time: "2024-01-01T00:00:00".to_string(),
+            script: "deploy.sh".to_string(),
+            function: function.to_string(),
+            params: vec![],
+            log: log.map(String::from),
+        };
+        history.record(&record("first", Some("first.log"))).unwrap();
+        history.record(&record("second", None)).unwrap();
+        history.record(&record("third", Some("third.log"))).unwrap();
+
+        let logged = history.logged_records().unwrap();
+        assert_eq!(
+            logged,
+            vec![
+                record("third", Some("third.log")),
+                record("first", Some("first.log"))
+            ]
+        );
+    }
+}
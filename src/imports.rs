@@ -0,0 +1,376 @@
+/// Targets from other task runners' files, i.e. Makefiles, the scripts in package.json and
+/// justfiles, so a project's `make test` shows up next to its bash functions. Each file is listed
+/// like a script, and its targets run through the tool they're written for. Each tool is a provider,
+/// so it's off until `providers` in lk.toml asks for it.
+use crate::bash_file::quote;
+use crate::executables::{Failure, Verdict};
+use crate::provider::{self, Found, Provider};
+use crate::script::{Function, Inline, Run, Script};
+use crate::{Error, Result};
+use serde::Deserialize;
+use std::path::Path;
+
+/// A task runner lk can import targets from.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Tool {
+    Make,
+    /// The scripts in package.json. They run with yarn or pnpm if there's a lock file for one.
+    Npm,
+    Just,
+}
+
+impl Tool {
+    /// The file names the tool looks for, which are the ones we look for too.
+    fn file_names(self) -> &'static [&'static str] {
+        match self {
+            Tool::Make => &["GNUmakefile", "makefile", "Makefile"],
+            Tool::Npm => &["package.json"],
+            Tool::Just => &["justfile", "Justfile", ".justfile"],
+        }
+    }
+
+    fn targets(self, text: &str) -> Result<Vec<Function>> {
+        match self {
+            Tool::Make => Ok(make_targets(text)),
+            Tool::Npm => npm_scripts(text),
+            Tool::Just => Ok(just_recipes(text)),
+        }
+    }
+
+    /// The command that runs a target from `file`, which is in the directory it runs from.
+    fn command(self, file: &Path, target: &str) -> String {
+        let name = quote(&file.file_name().unwrap_or_default().to_string_lossy());
+        let target = quote(target);
+        match self {
+            Tool::Make => format!("make -f {name} {target} \"$@\""),
+            Tool::Npm if file.with_file_name("pnpm-lock.yaml").exists() => {
+                format!("pnpm run {target} \"$@\"")
+            }
+            Tool::Npm if file.with_file_name("yarn.lock").exists() => {
+                format!("yarn run {target} \"$@\"")
+            }
+            Tool::Npm => format!("npm run {target} -- \"$@\""),
+            Tool::Just => format!("just --justfile {name} {target} \"$@\""),
+        }
+    }
+}
+
+impl Provider for Tool {
+    fn name(&self) -> &str {
+        match self {
+            Tool::Make => "make",
+            Tool::Npm => "npm",
+            Tool::Just => "just",
+        }
+    }
+
+    /// A script for each of the tool's files, from the files lk came across while it looked for
+    /// scripts, so they're included and excluded like scripts are.
+    fn discover(&self, context: &provider::Context) -> Result<Found> {
+        let mut found = Found::default();
+        for (path, verdict) in &context.executables.considered {
+            if matches!(verdict, Verdict::Excluded(_) | Verdict::Duplicate(_)) {
+                continue;
+            }
+            let is_ours = path
+                .file_name()
+                .and_then(|name| name.to_str())
+                .is_some_and(|name| self.file_names().contains(&name));
+            if !is_ours {
+                continue;
+            }
+            match script(*self, path) {
+                Ok(Some(script)) => found.scripts.push(script),
+                Ok(None) => {}
+                Err(err) => found.failures.push(Failure::new(path, err.report())),
+            }
+        }
+        Ok(found)
+    }
+}
+
+/// The file as a script whose functions are its targets. None if it hasn't got any.
+fn script(tool: Tool, path: &Path) -> Result<Option<Script>> {
+    let read = |source| Error::Read {
+        path: path.to_owned(),
+        source,
+    };
+    let absolute_path = path.canonicalize().map_err(read)?;
+    let text = std::fs::read_to_string(path).map_err(read)?;
+    let dir = absolute_path.parent().unwrap_or(Path::new("/")).to_owned();
+    let mut functions = tool.targets(&text)?;
+    if functions.is_empty() {
+        return Ok(None);
+    }
+    for function in &mut functions {
+        function.run = Run::Inline(Inline {
+            run: tool.command(&absolute_path, &function.name),
+            dir: dir.clone(),
+            env: Default::default(),
+        });
+    }
+    Ok(Some(Script {
+        path: path.strip_prefix("./").unwrap_or(path).to_owned(),
+        absolute_path,
+        description: Some(format!("Targets run with {}", tool.name())),
+        functions,
+        ..Script::default()
+    }))
+}
+
+/// The targets in a Makefile. A target's description is the `## text` after it, which is what
+/// most `make help` recipes use, or else the comment above it. Special targets like `.PHONY`,
+/// pattern rules and anything with a variable in it aren't something you'd run by name.
+fn make_targets(text: &str) -> Vec<Function> {
+    let mut targets: Vec<Function> = Vec::new();
+    let mut comment: Vec<String> = Vec::new();
+    for (i, line) in text.lines().enumerate() {
+        if let Some(text) = line.strip_prefix('#') {
+            comment.push(text.trim_start_matches('#').trim().to_string());
+            continue;
+        }
+        let rule = line
+            .split_once(':')
+            .filter(|_| !line.starts_with(|c: char| c.is_whitespace()))
+            .filter(|(names, rest)| {
+                !names.contains(['=', '$', '%'])
+                    && !rest.starts_with('=')
+                    && !rest.starts_with(":=")
+                    && !rest.starts_with("::=")
+            });
+        let Some((names, rest)) = rule else {
+            comment.clear();
+            continue;
+        };
+        let description = match rest.split_once("##") {
+            Some((_, description)) => vec![description.trim().to_string()],
+            None => std::mem::take(&mut comment),
+        };
+        for name in names.split_whitespace() {
+            if name.starts_with('.') || targets.iter().any(|target| target.name == name) {
+                continue;
+            }
+            targets.push(Function {
+                name: name.to_string(),
+                comment: description.clone(),
+                start_line: i + 1,
+                end_line: i + 1,
+                ..Function::default()
+            });
+        }
+        comment.clear();
+    }
+    targets
+}
+
+/// The scripts in package.json. They don't have descriptions, so we show what they run.
+fn npm_scripts(text: &str) -> Result<Vec<Function>> {
+    #[derive(Deserialize)]
+    struct Package {
+        #[serde(default)]
+        scripts: serde_json::Map<String, serde_json::Value>,
+    }
+    let package: Package = serde_json::from_str(text)
+        .map_err(|e| Error::Config(format!("It isn't the JSON npm expects: {e}")))?;
+    Ok(package
+        .scripts
+        .into_iter()
+        .filter_map(|(name, run)| {
+            let run = run.as_str()?.to_owned();
+            Some(Function {
+                name,
+                comment: vec![format!("Runs {run}")],
+                ..Function::default()
+            })
+        })
+        .collect())
+}
+
+/// The recipes in a justfile, with the comment above each one as its description, and their
+/// params, e.g. `target="debug"`. Recipes that
+/// start with `_` or have a `[private]` attribute are hidden, like `just --list` does.
+fn just_recipes(text: &str) -> Vec<Function> {
+    const KEYWORDS: &[&str] = &["alias", "export", "import", "mod", "set"];
+    let mut recipes = Vec::new();
+    let mut comment: Vec<String> = Vec::new();
+    let mut private = false;
+    for (i, line) in text.lines().enumerate() {
+        if let Some(text) = line.strip_prefix('#') {
+            if !text.starts_with('!') {
+                comment.push(text.trim().to_string());
+            }
+            continue;
+        }
+        if let Some(attributes) = line.strip_prefix('[') {
+            private |= attributes.contains("private");
+            continue;
+        }
+        let header = line
+            .strip_prefix('@')
+            .unwrap_or(line)
+            .split_once(':')
+            .filter(|(_, rest)| !rest.starts_with('='))
+            .map(|(header, _)| header);
+        let mut words = header
+            .filter(|_| !line.starts_with(|c: char| c.is_whitespace()))
+            .map(str::split_whitespace)
+            .into_iter()
+            .flatten();
+        let name = words
+            .next()
+            .filter(|name| !KEYWORDS.contains(name) && !name.contains(":="));
+        if let Some(name) = name {
+            recipes.push(Function {
+                name: name.to_string(),
+                comment: std::mem::take(&mut comment),
+                params: words.map(String::from).collect(),
+                hidden: private || name.starts_with('_'),
+                start_line: i + 1,
+                end_line: i + 1,
+                ..Function::default()
+            });
+        }
+        comment.clear();
+        private = false;
+    }
+    recipes
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::executables::{Executables, NonExecutable};
+    use crate::workspace::Workspace;
+
+    #[test]
+    fn reads_make_targets() {
+        let targets = make_targets(
+            r#"CC := gcc
+.PHONY: build test
+export PATH := bin:$(PATH)
+
+# Builds it
+build: src/main.c
+	$(CC) -o app src/main.c
+
+test: build ## Runs the tests
+	./app --test
+
+%.o: %.c
+	$(CC) -c $<
+$(OUT): build
+clean install::
+	rm -rf app
+"#,
+        );
+
+        let names: Vec<&str> = targets.iter().map(|t| t.name.as_str()).collect();
+        assert_eq!(names, vec!["build", "test", "clean", "install"]);
+        assert_eq!(targets[0].comment, vec!["Builds it"]);
+        assert_eq!(targets[0].start_line, 6);
+        assert_eq!(targets[1].comment, vec!["Runs the tests"]);
+        assert!(targets[2].comment.is_empty());
+    }
+
+    #[test]
+    fn reads_npm_scripts() {
+        let scripts = npm_scripts(
+            r#"{"name": "web", "scripts": {"build:prod": "vite build", "test": "vitest"}}"#,
+        )
+        .unwrap();
+
+        assert_eq!(scripts[0].name, "build:prod");
+        assert_eq!(scripts[0].comment, vec!["Runs vite build"]);
+        assert_eq!(scripts[1].name, "test");
+        assert!(npm_scripts(r#"{"name": "web"}"#).unwrap().is_empty());
+        assert!(npm_scripts("{").is_err());
+    }
+
+    #[test]
+    fn reads_just_recipes() {
+        let recipes = just_recipes(
+            r#"#!/usr/bin/env just --justfile
+set shell := ["bash", "-c"]
+alias b := build
+version := "1.0"
+
+# Builds it
+build target="debug":
+    cargo build --{{target}}
+
+[private]
+[no-cd]
+helper:
+    echo help
+
+@_quiet *args: build
+    echo {{args}}
+"#,
+        );
+
+        let names: Vec<&str> = recipes.iter().map(|r| r.name.as_str()).collect();
+        assert_eq!(names, vec!["build", "helper", "_quiet"]);
+        assert_eq!(recipes[0].comment, vec!["Builds it"]);
+        assert_eq!(recipes[0].params, vec!["target=\"debug\""]);
+        assert_eq!(recipes[2].params, vec!["*args"]);
+        assert!(!recipes[0].hidden);
+        assert!(recipes[1].hidden);
+        assert!(recipes[2].hidden);
+    }
+
+    #[test]
+    fn imports_the_files_it_finds() {
+        let dir = tempfile::tempdir().unwrap();
+        let root = dir.path().canonicalize().unwrap();
+        std::fs::create_dir_all(root.join("web")).unwrap();
+        std::fs::create_dir_all(root.join("node_modules/dep")).unwrap();
+        std::fs::write(root.join("Makefile"), "build:\n\techo built\n").unwrap();
+        std::fs::write(
+            root.join("web/package.json"),
+            r#"{"scripts":{"dev":"vite"}}"#,
+        )
+        .unwrap();
+        std::fs::write(root.join("web/yarn.lock"), "").unwrap();
+        std::fs::write(
+            root.join("node_modules/dep/package.json"),
+            r#"{"scripts":{"postinstall":"rm -rf /"}}"#,
+        )
+        .unwrap();
+        let executables = Executables::new(
+            &[format!("{}/**/*", root.display())],
+            &[format!("{}/node_modules", root.display())],
+            NonExecutable::Skip,
+        )
+        .unwrap();
+
+        let workspace = Workspace {
+            root: root.clone(),
+            files: Vec::new(),
+        };
+        let context = provider::Context {
+            executables: &executables,
+            workspace: &workspace,
+            config_files: &[],
+            follow_sources: false,
+            found: &[],
+        };
+
+        let make = Tool::Make.discover(&context).unwrap();
+        let npm = Tool::Npm.discover(&context).unwrap();
+
+        assert!(make.failures.is_empty() && npm.failures.is_empty());
+        let inline = |script: &Script, name: &str| match &script.get(name).unwrap().run {
+            Run::Inline(inline) => inline.clone(),
+            run => panic!("{run:?} isn't inline"),
+        };
+        let make = inline(&make.scripts[0], "build");
+        assert_eq!(make.run, "make -f 'Makefile' 'build' \"$@\"");
+        assert_eq!(make.dir, root);
+        assert_eq!(npm.scripts.len(), 1);
+        assert!(npm.scripts[0].path.ends_with("web/package.json"));
+        let yarn = inline(&npm.scripts[0], "dev");
+        assert_eq!(yarn.run, "yarn run 'dev' \"$@\"");
+        assert_eq!(yarn.dir, root.join("web"));
+        assert!(Tool::Just.discover(&context).unwrap().scripts.is_empty());
+    }
+}
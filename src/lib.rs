@@ -0,0 +1,71 @@
+//! lk finds the functions in your bash scripts, and the other things a project has to run, and
+//! runs them. This is the library the `lk` binary is built on, so other tools can find and run
+//! functions the same way.
+//!
+//! [`Executables`] finds the scripts, [`Script::new`] reads their functions, and a [`Runner`] runs
+//! one. Providers, in [`provider`], find functions everywhere else too, e.g. in Makefiles.
+//!
+//! ```
+//! use lk::{Executables, NonExecutable, Runner, Script};
+//! use std::os::unix::fs::PermissionsExt;
+//!
+//! # fn main() -> lk::Result<()> {
+//! let project = tempfile::tempdir()?;
+//! let path = project.path().join("greet.sh");
+//! std::fs::write(&path, "#!/usr/bin/env bash\n\n# Says hello\nhello() {\n    echo \"hello $1\"\n}\n")?;
+//! std::fs::set_permissions(&path, std::fs::Permissions::from_mode(0o755))?;
+//!
+//! let includes = [format!("{}/*", project.path().display())];
+//! let executables = Executables::new(&includes, &[], NonExecutable::Skip)?;
+//! let script = Script::new(&executables.executables[0], true)?;
+//! let hello = script.get("hello").unwrap();
+//! assert_eq!(hello.comment, vec!["Says hello"]);
+//!
+//! let runner = Runner::new(project.path());
+//! let code = runner.run(&script, hello, vec!["world".to_string()], false)?;
+//! assert_eq!(code, 0);
+//! # Ok(())
+//! # }
+//! ```
+pub mod alias;
+mod bash_file;
+pub mod catalogue;
+pub mod commands;
+pub mod confirm;
+mod error;
+pub mod executables;
+pub mod hide;
+pub mod history;
+pub mod imports;
+pub mod lint;
+mod parallel;
+mod parser;
+mod process;
+pub mod provider;
+pub mod redact;
+pub mod runner;
+pub mod script;
+pub mod settings;
+pub mod workspace;
+pub mod xdg;
+
+/// For the lk binary: checking lk's setup, finding the user's shell history, and printing for
+/// people. They're not part of the API.
+#[doc(hidden)]
+pub mod doctor;
+#[doc(hidden)]
+pub mod shells;
+#[doc(hidden)]
+pub mod ui;
+
+pub use alias::Aliases;
+pub use error::{Error, Result};
+pub use executables::{Executable, Executables, Failure, NonExecutable, Verdict};
+pub use process::TIMED_OUT;
+pub use provider::{Found, Provider, Providers};
+pub use redact::Redactor;
+pub use runner::{Job, Runner};
+pub use script::{BashFunction, Confirm, Function, Inline, Run, Script, SymlinkCd};
+pub use settings::LkConfig;
+pub use workspace::Workspace;
+pub use xdg::LkDirs;
@@ -0,0 +1,275 @@
+/// Checks scripts for things that trip lk up, or trip up the people using them through lk.
+use crate::executables::Executables;
+use crate::script::{self, Script};
+use crate::Result;
+use pastel_colours::{BLUE_FG, ORANGE_FG, RED_FG, RESET_FG};
+use serde::Serialize;
+use std::path::Path;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Severity {
+    Warning,
+    Error,
+}
+
+/// Something lint found. `check` is a short, stable name for what we checked, so tools can pick
+/// out the ones they care about.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+#[non_exhaustive]
+pub struct Diagnostic {
+    pub severity: Severity,
+    pub check: &'static str,
+    pub path: String,
+    pub line: Option<usize>,
+    pub message: String,
+}
+
+impl Diagnostic {
+    fn new(severity: Severity, check: &'static str, path: &Path, message: String) -> Self {
+        Self {
+            severity,
+            check,
+            path: path.to_string_lossy().to_string(),
+            line: None,
+            message,
+        }
+    }
+
+    fn at(self, line: usize) -> Self {
+        Self {
+            line: Some(line),
+            ..self
+        }
+    }
+}
+
+/// Lints the scripts. `executables` is everything lk found, so we can spot functions that share
+/// a name with a script, and the scripts we skipped.
+pub fn lint(executables: &Executables, scripts: &[Script]) -> Vec<Diagnostic> {
+    let mut diagnostics: Vec<Diagnostic> = executables
+        .skipped
+        .iter()
+        .map(|path| {
+            Diagnostic::new(
+                Severity::Warning,
+                "not-executable",
+                path,
+                "This isn't executable, so lk skips it. Run `lk fix-perms` to fix it.".to_string(),
+            )
+        })
+        .collect();
+
+    for script in scripts {
+        if script.comment.is_empty() && script.description.is_none() {
+            diagnostics.push(Diagnostic::new(
+                Severity::Warning,
+                "no-header",
+                &script.path,
+                "There's no header comment after the hashbang, so lk can't say what this script is for."
+                    .to_string(),
+            ));
+        }
+
+        let side_effects = match script::side_effects(&script.absolute_path) {
+            Ok(side_effects) => side_effects,
+            Err(err) => {
+                log::warn!("Unable to look for side effects: {}", err.report());
+                Vec::new()
+            }
+        };
+        for side_effect in &side_effects {
+            diagnostics.push(
+                Diagnostic::new(
+                    Severity::Error,
+                    "side-effect",
+                    &script.path,
+                    format!(
+                        "`{}` runs every time lk runs a function, because lk sources the whole script. Move it into a function.",
+                        side_effect.words.join(" ")
+                    ),
+                )
+                .at(side_effect.line),
+            );
+        }
+
+        // Libraries get linted on their own, if they're executable.
+        let functions: Vec<_> = script
+            .functions
+            .iter()
+            .filter(|function| function.source.is_none())
+            .collect();
+        for (i, function) in functions.iter().enumerate() {
+            if let Some(first) = functions[..i].iter().find(|f| f.name == function.name) {
+                diagnostics.push(
+                    Diagnostic::new(
+                        Severity::Error,
+                        "duplicate-function",
+                        &script.path,
+                        format!(
+                            "{} is already defined on line {}. Bash uses the last one, which probably isn't what you meant.",
+                            function.name, first.start_line
+                        ),
+                    )
+                    .at(function.start_line),
+                );
+            }
+
+            if function.comment.is_empty() && !function.hidden {
+                diagnostics.push(
+                    Diagnostic::new(
+                        Severity::Warning,
+                        "no-comment",
+                        &script.path,
+                        format!(
+                            "{} has no comment, so nobody can tell what it does from lk.",
+                            function.name
+                        ),
+                    )
+                    .at(function.start_line),
+                );
+            }
+
+            let same_name = executables.executables.iter().find(|executable| {
+                executable.absolute_path != script.absolute_path
+                    && (executable.short_name == function.name
+                        || executable.path.file_stem().and_then(|s| s.to_str())
+                            == Some(function.name.as_str()))
+            });
+            if let Some(executable) = same_name {
+                diagnostics.push(
+                    Diagnostic::new(
+                        Severity::Warning,
+                        "shadowed-script",
+                        &script.path,
+                        format!(
+                            "{} has the same name as the script {}, and shadows it inside this script.",
+                            function.name,
+                            executable.path.to_string_lossy()
+                        ),
+                    )
+                    .at(function.start_line),
+                );
+            }
+        }
+    }
+    diagnostics
+}
+
+/// The exit code for lint: 1 if there are any errors, otherwise 0. Warnings don't fail.
+pub fn exit_code(diagnostics: &[Diagnostic]) -> i32 {
+    if diagnostics.iter().any(|d| d.severity == Severity::Error) {
+        1
+    } else {
+        0
+    }
+}
+
+pub fn to_json(diagnostics: &[Diagnostic]) -> Result<String> {
+    Ok(serde_json::to_string_pretty(diagnostics)?)
+}
+
+pub fn pretty_print(diagnostics: &[Diagnostic]) {
+    for diagnostic in diagnostics {
+        let location = match diagnostic.line {
+            Some(line) => format!("{}:{line}", diagnostic.path),
+            None => diagnostic.path.clone(),
+        };
+        let severity = match diagnostic.severity {
+            Severity::Warning => format!("{ORANGE_FG}warning{RESET_FG}"),
+            Severity::Error => format!("{RED_FG}error{RESET_FG}"),
+        };
+        println!(
+            "{BLUE_FG}{location}{RESET_FG}: {severity}[{}]: {}",
+            diagnostic.check, diagnostic.message
+        );
+    }
+    let errors = diagnostics
+        .iter()
+        .filter(|d| d.severity == Severity::Error)
+        .count();
+    println!(
+        "lk lint: {errors} error(s), {} warning(s)",
+        diagnostics.len() - errors
+    );
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::executables::Executable;
+
+    fn checks(diagnostics: &[Diagnostic]) -> Vec<(&str, Option<usize>)> {
+        diagnostics
+            .iter()
+            .map(|d| (d.check, d.line))
+            .collect::<Vec<_>>()
+    }
+
+    #[test]
+    fn finds_the_problems() {
+        let dir = tempfile::tempdir().unwrap();
+        let write = |name: &str, contents: &str| {
+            let path = dir.path().join(name);
+            std::fs::write(&path, contents).unwrap();
+            Executable {
+                short_name: name.to_string(),
+                absolute_path: path.canonicalize().unwrap(),
+                path,
+                non_executable: false,
+                via_symlink: false,
+            }
+        };
+        let messy = write(
+            "messy.sh",
+            "#!/usr/bin/env bash\nset -e\ncat test.sh\n\n# Builds\nbuild() { :; }\nbuild() { :; }\n_helper() { :; }\nmain \"$@\"\n",
+        );
+        let build = write("build.sh", "#!/usr/bin/env bash\n# Builds things\n");
+        let scripts: Vec<Script> = [&messy, &build]
+            .iter()
+            .map(|executable| {
+                let mut script = Script::new(executable, true).unwrap();
+                script
+                    .functions
+                    .iter_mut()
+                    .for_each(|f| f.hidden = f.name.starts_with('_'));
+                script
+            })
+            .collect();
+        let executables = Executables {
+            executables: vec![messy, build],
+            skipped: vec!["skipped.sh".into()],
+            failures: vec![],
+            considered: vec![],
+        };
+
+        let diagnostics = lint(&executables, &scripts);
+
+        assert_eq!(
+            checks(&diagnostics),
+            vec![
+                ("not-executable", None),
+                ("no-header", None),
+                ("side-effect", Some(3)),
+                ("side-effect", Some(9)),
+                ("shadowed-script", Some(6)),
+                ("duplicate-function", Some(7)),
+                ("no-comment", Some(7)),
+                ("shadowed-script", Some(7)),
+            ]
+        );
+        assert_eq!(exit_code(&diagnostics), 1);
+    }
+
+    #[test]
+    fn warnings_dont_fail() {
+        let diagnostics = vec![Diagnostic::new(
+            Severity::Warning,
+            "no-header",
+            Path::new("a.sh"),
+            "".to_string(),
+        )];
+        assert_eq!(exit_code(&diagnostics), 0);
+        assert_eq!(exit_code(&[]), 0);
+    }
+}
@@ -1,27 +1,29 @@
-mod bash_file;
-mod executables;
-mod script;
-mod shells;
-mod ui;
-
-use anyhow::Result;
-use bash_file::BashFile;
-use config::{Config, File};
-use executables::Executables;
+use anyhow::{Context, Result};
 use fuzzy_finder::item::Item;
 use fuzzy_finder::FuzzyFinder;
-
-use log::{debug, info, LevelFilter};
-use log4rs::append::file::FileAppender;
+use lk::confirm::ConfirmRules;
+use lk::hide::HideRules;
+use lk::settings::{self, CommandLine, LkConfig, Mode};
+use lk::shells::UserShell;
+use lk::ui::{print_bad_function_name, print_bad_script_name, print_failures, print_scripts};
+use lk::{catalogue, doctor, executables, lint};
+use lk::{
+    history, provider, script, Aliases, Executables, Function, Job, LkDirs, Redactor, Runner,
+    Workspace,
+};
+
+use log::{debug, info, warn, LevelFilter};
+use log4rs::append::rolling_file::policy::compound::{
+    roll::fixed_window::FixedWindowRoller, trigger::size::SizeTrigger, CompoundPolicy,
+};
+use log4rs::append::rolling_file::RollingFileAppender;
 use log4rs::config::{Appender, Root};
 use log4rs::encode::pattern::PatternEncoder;
-use script::Function;
-use shells::UserShell;
+use pastel_colours::{ORANGE_FG, RESET_FG};
 use spinners::{Spinner, Spinners};
-use std::path::Path;
+use std::io::Write;
+use std::path::{Path, PathBuf};
 use structopt::StructOpt;
-use tempfile::tempdir;
-use ui::{print_bad_function_name, print_bad_script_name};
 
 mod tui;
 /// Use lk to explore and execute scripts in your current directory,
@@ -57,6 +59,66 @@ struct Cli {
     #[structopt(long, short)]
     excludes: Vec<String>,
 
+    /// Look in the places lk leaves out by default, like .git and node_modules, too.
+    #[structopt(long)]
+    no_default_excludes: bool,
+
+    /// Override a setting from lk.toml, e.g. `--set default_mode=fuzzy`. Separate lists with
+    /// commas. Use it more than once to set more than one setting.
+    #[structopt(long = "set", number_of_values = 1)]
+    sets: Vec<String>,
+
+    /// Capture the function's output in a log file, as well as showing it.
+    #[structopt(long)]
+    capture: bool,
+
+    /// Stop the function if it runs for longer than this, e.g. 30s or 10m. Overrides any
+    /// `# @timeout` annotation on the function.
+    #[structopt(long, parse(try_from_str = humantime::parse_duration))]
+    timeout: Option<std::time::Duration>,
+
+    /// Run several functions at once. Pass them as script:function, e.g.
+    /// `lk --parallel svc_a.sh:lint svc_b.sh:lint`.
+    #[structopt(long)]
+    parallel: bool,
+
+    /// How many functions to run at once with --parallel, or when several are picked in the TUI.
+    /// Defaults to the number of CPUs.
+    #[structopt(long, short = "j")]
+    jobs: Option<usize>,
+
+    /// Only show functions with this tag, from a `# @tags` annotation on the function or its
+    /// script. Use it more than once to only show functions with all of the tags.
+    #[structopt(long = "tag", number_of_values = 1)]
+    tags: Vec<String>,
+
+    /// Group functions by their owner, from `# @owner` annotations.
+    #[structopt(long)]
+    by_owner: bool,
+
+    /// Print the scripts and functions lk finds as JSON, rather than running anything.
+    #[structopt(long)]
+    json: bool,
+
+    /// Show hidden functions too, dimmed. Functions are hidden by the hide rules in lk.toml, or a
+    /// `# @private` or `# @hidden` annotation.
+    #[structopt(long, alias = "all")]
+    show_hidden: bool,
+
+    /// Run functions from scripts with commands outside of functions, even if `refuse_side_effects`
+    /// is set.
+    #[structopt(long)]
+    force: bool,
+
+    /// Don't ask for confirmation before running dangerous functions, or before `lk fix-perms`
+    /// changes anything.
+    #[structopt(long, short)]
+    yes: bool,
+
+    /// Say which files lk skipped because it couldn't use them, and why.
+    #[structopt(long, short)]
+    verbose: bool,
+
     /// Number of lines to show in fuzzy search.
     #[structopt(long, short = "n", default_value = "7")]
     number: i8,
@@ -68,197 +130,482 @@ struct Cli {
 }
 
 fn main() -> Result<()> {
-    // We will use the home directory to store lk configuration and log files.
-    let lk_dir = match dirs::home_dir() {
-        // Use a dir in ~/.config like a good human, but then store logs in it lol.
-        Some(home_dir) => format!("{}/.config/lk", home_dir.to_string_lossy()),
-        // If we don't have access to the home_dir for some reason then just use a temp dir.
-        None => {
-            println!("Unable to access your home directory. Using a temporary directory instead.");
-            tempdir().unwrap().into_path().to_string_lossy().to_string()
-        }
-    };
-
-    // Configure logging
-    let log_file_path = format!("{lk_dir}/lk.log");
-    let log_file = FileAppender::builder()
-        .encoder(Box::new(PatternEncoder::new("{l} - {m}\n")))
-        .build(log_file_path)?;
-
-    let config = log4rs::config::Config::builder()
-        .appender(Appender::builder().build("logfile", Box::new(log_file)))
-        .build(Root::builder().appender("logfile").build(LevelFilter::Info))?;
-    log4rs::init_config(config)?;
+    // Config, logs and history each go where the XDG spec says they should.
+    let lk_dirs = LkDirs::new();
+    lk_dirs.create()?;
+    let log_file_path = lk_dirs.log_file();
+    init_logging(&log_file_path)?;
+    lk_dirs.move_old_state();
 
     info!("\n\nStarting lk...");
 
     let args = Cli::from_args();
 
-    let mut sp = Spinner::new(Spinners::Line, "".to_string());
-
-    // Set configuration defaults, then load the user config followed by a workspace if they exist.
-    // Configurations in later files override earlier ones. However, command line configuration overrides these
-    let builder = Config::builder()
-        .set_default("default_mode", "tui")?
-        .set_default("includes", vec!["**/*".to_string(), "*".to_string()])?
-        .set_default(
-            "excludes",
-            vec![
-                // TODO: List these in help
-                // TODO: write these to global config if there's no exclude section already
-                "target".to_string(),
-                ".github".to_string(),
-                ".vscode".to_string(),
-                ".git".to_string(),
-                "node_modules".to_string(),
-                ".nvm".to_string(),
-                ".Trash".to_string(),
-                ".npm".to_string(),
-                ".cache".to_string(),
-                "Library".to_string(),
-                ".cargo".to_string(),
-                ".sock".to_string(),
-            ] as Vec<String>,
-        )?
-        .add_source(File::from(Path::new(&lk_dir).join("lk.toml")).required(false))
-        .add_source(File::from(Path::new(".").join("lk.toml")).required(false));
-
-    let config = builder.build()?;
-
-    // Extract the config file includes and excludes
-    let config_includes = config.get::<Vec<String>>("includes").unwrap();
-    let config_excludes = config.get::<Vec<String>>("excludes").unwrap();
-
-    // Merge the command line includes and excludes with the config file includes and excludes
-    let includes: Vec<String> = args
-        .includes
-        .clone()
-        .into_iter()
-        .chain(config_includes)
+    // lk works on the whole project, wherever in it you run lk.
+    let cwd = std::env::current_dir()?;
+    let workspace = Workspace::find(&cwd);
+    let config_files: Vec<PathBuf> = std::iter::once(lk_dirs.config.join("lk.toml"))
+        .chain(workspace.files.iter().cloned())
         .collect();
 
-    let excludes: Vec<String> = args
-        .excludes
-        .clone()
-        .into_iter()
-        .chain(config_excludes)
-        .collect();
+    // `lk config init` writes the defaults to the user config, so there's something to edit.
+    if args.script.as_deref() == Some("config") && args.function.as_deref() == Some("init") {
+        return Ok(settings::init(&config_files[0], &lk_dirs)?);
+    }
 
-    let default_mode = config.get::<String>("default_mode").unwrap();
+    // The user config comes first, followed by the workspace configs from the root of the project
+    // down to where we are, so the closest one wins.
+    let mut command_line = CommandLine::default();
+    command_line.sets = args.sets.clone();
+    command_line.includes = args.includes.clone();
+    command_line.excludes = args.excludes.clone();
+    command_line.no_default_excludes = args.no_default_excludes;
+    let (config, origins) =
+        settings::load(&lk_dirs, &config_files, std::env::vars(), &command_line)?;
+    for warning in settings::unknown_settings(&config_files)? {
+        warn!("{}", warning);
+        eprintln!("{ORANGE_FG}{warning}{RESET_FG}");
+    }
+
+    // `lk config show` shows the settings we ended up with, and where they're from.
+    if args.script.as_deref() == Some("config") {
+        return match args.function.as_deref() {
+            None | Some("show") => Ok(settings::show(config, &origins)?),
+            Some(other) => anyhow::bail!(
+                "Unknown config command: {}. Try lk config show or lk config init.",
+                other
+            ),
+        };
+    }
+    let settings = LkConfig::new(&config, &origins)?;
+
+    let (includes, excludes) = settings.globs(&origins, &workspace, &cwd);
+
+    let default_mode = settings.default_mode;
+    let confirm_rules = ConfirmRules::new(&settings.confirm, &settings.confirm_name)?;
+    let hide_rules = HideRules::new(
+        &settings.hide_prefixes,
+        &settings.hide_patterns,
+        args.show_hidden,
+    )?;
+
+    let follow_sources = settings.follow_sources;
+    let non_executable = settings.non_executable;
+    let capture_output = args.capture || settings.capture_output;
+    let mut runner = Runner::new(&lk_dirs.state);
+    runner.redactor = Redactor::new(&settings.redact_patterns)?;
+    runner.yes = args.yes;
+    runner.log_dir = capture_output.then_some(settings.log_dir);
+    runner.timeout = args.timeout;
+    runner.grace = settings.timeout_grace;
+    runner.refuse_side_effects = settings.refuse_side_effects && !args.force;
+    runner.symlink_cd = settings.symlink_cd;
+    runner.shell = UserShell::new();
+
+    // `lk logs` is a command of its own, rather than a script.
+    if args.script.as_deref() == Some("logs") {
+        return logs(&runner.history, args.function.as_deref());
+    }
 
     info!(
         "Using default_mode {:?}, includes {:?} and excludes {:?}",
         default_mode, includes, excludes
     );
 
-    //TODO: what should the root be and how does it overlap with the defaults or user specified includes?
+    // Paths are relative to the root of the project from here on.
+    std::env::set_current_dir(&workspace.root).with_context(|| {
+        format!(
+            "Couldn't change to the project root, {}",
+            workspace.root.to_string_lossy()
+        )
+    })?;
     // What executable scripts are available in the configuration directory?
-    let executables = Executables::new(&includes, &excludes)?;
+    // The spinner would end up in the JSON, so there's no spinner for --json.
+    let mut sp = (!args.json).then(|| Spinner::new(Spinners::Line, "".to_string()));
+    let mut executables = Executables::new(&includes, &excludes, non_executable)?;
 
-    sp.stop();
+    if let Some(sp) = sp.as_mut() {
+        sp.stop();
+    }
 
-    // What functions do these executables contain?
-    let scripts: Vec<script::Script> = executables
-        .executables
-        .iter()
-        .map(script::Script::new)
-        .filter_map(Result::ok)
-        .collect();
+    // `lk fix-perms` only needs the files, not what's in them.
+    if args.script.as_deref() == Some("fix-perms") {
+        return fix_perms(&executables, args.yes);
+    }
+
+    // What can we run? The providers say, e.g. the functions in the scripts we found.
+    let mut providers = provider::Providers::builtin();
+    providers.register(Box::new(Aliases::new(settings.aliases.clone())));
+    let context = provider::Context::new(&executables, &workspace, &config_files, follow_sources);
+    let found = providers.discover(&settings.providers, &context)?;
+    let mut scripts = found.scripts;
+    executables.failures.extend(found.failures);
+
+    // `lk doctor` explains what we found, so it wants to know about every file.
+    if args.script.as_deref() == Some("doctor") {
+        let setup = doctor::Setup {
+            root: &workspace.root,
+            config_files: &config_files,
+            includes: &includes,
+            excludes: &excludes,
+            dirs: &lk_dirs,
+            log_file: &log_file_path,
+        };
+        let code = doctor::doctor(&setup, &executables)?;
+        if code != 0 {
+            std::process::exit(code);
+        }
+        return Ok(());
+    }
+    if args.verbose {
+        print_failures(&executables.failures);
+    }
+    confirm_rules.apply(&mut scripts);
+
+    // `lk lint` checks every function, so it goes before we hide or filter any of them. It's about
+    // bash, so it only looks at scripts.
+    if args.script.as_deref() == Some("lint") {
+        scripts.retain(|script| script.provider == "bash");
+        let code = lint(
+            &executables,
+            scripts,
+            &hide_rules,
+            args.function.as_deref(),
+            args.json,
+        )?;
+        if code != 0 {
+            std::process::exit(code);
+        }
+        return Ok(());
+    }
+
+    // The user can run a hidden function if they name one, so we keep them all before any are
+    // hidden.
+    let everything = scripts.clone();
+
+    hide_rules.apply(&mut scripts);
+    catalogue::filter_by_tags(&mut scripts, &args.tags);
 
     debug!("Found the following scripts {:#?}", scripts);
 
+    if args.json {
+        println!("{}", catalogue::to_json(&scripts, args.by_owner)?);
+        return Ok(());
+    }
+    if args.by_owner {
+        // The modes show scripts in the order we give them, so put them in order of owner.
+        scripts = catalogue::group_by_owner(&scripts)
+            .into_iter()
+            .flat_map(|(_, scripts)| scripts)
+            .collect();
+    }
+
+    let concurrency = args.jobs.unwrap_or_else(|| {
+        std::thread::available_parallelism()
+            .map(usize::from)
+            .unwrap_or(1)
+    });
+
     // Command line rules ok?
-    if args.fuzzy {
-        fuzzy(&scripts, args.number + 1)
+    let code = if args.parallel {
+        parallel(&scripts, args, concurrency, &runner)
+    } else if args.fuzzy {
+        fuzzy(&scripts, args.number + 1, &runner)
     } else if args.list || args.script.is_some() {
         // If the user is specifying --list OR if there's some value for script.
         // Any value there is implicitly take as --list.
-        list(executables, args)
+        list(&everything, &scripts, args, &runner, &hide_rules)
     } else if args.tui {
-        tui(&scripts)
+        tui(&scripts, concurrency, &runner)
     } else {
         // Neither requested, so fall back on the configuration
-        match default_mode.as_str() {
-            "fuzzy" => fuzzy(&scripts, args.number + 1),
-            "list" => list(executables, args),
-            "tui" => tui(&scripts),
-            _ => panic!("No default mode set! Has there been a problem creating the config file?"),
+        match default_mode {
+            Mode::Fuzzy => fuzzy(&scripts, args.number + 1, &runner),
+            Mode::List => list(&everything, &scripts, args, &runner, &hide_rules),
+            Mode::Tui => tui(&scripts, concurrency, &runner),
         }
+    }?;
+
+    // lk exits with the same code as the function it ran, so it can be used in other scripts.
+    if code != 0 {
+        std::process::exit(code);
     }
+    Ok(())
+}
+
+/// Logs go to `lk.log`, which rolls over to `lk.1.log` and so on when it gets big. `LK_LOG` sets
+/// the level, e.g. `LK_LOG=debug`.
+fn init_logging(log_file: &Path) -> Result<()> {
+    const MAX_LOG_SIZE: u64 = 1024 * 1024;
+    const OLD_LOGS: u32 = 3;
+    let old_logs = log_file.with_file_name("lk.{}.log");
+    let roller = FixedWindowRoller::builder().build(&old_logs.to_string_lossy(), OLD_LOGS)?;
+    let policy = CompoundPolicy::new(Box::new(SizeTrigger::new(MAX_LOG_SIZE)), Box::new(roller));
+    let appender = RollingFileAppender::builder()
+        .encoder(Box::new(PatternEncoder::new("{l} - {m}\n")))
+        .build(log_file, Box::new(policy))?;
+
+    let level = match std::env::var("LK_LOG") {
+        Ok(level) => level.parse::<LevelFilter>().map_err(|_| {
+            anyhow::anyhow!(
+                "LK_LOG should be off, error, warn, info, debug or trace, not {}",
+                level
+            )
+        })?,
+        Err(_) => LevelFilter::Info,
+    };
+    let config = log4rs::config::Config::builder()
+        .appender(Appender::builder().build("logfile", Box::new(appender)))
+        .build(Root::builder().appender("logfile").build(level))?;
+    log4rs::init_config(config)?;
+    Ok(())
 }
 
-// Runs lk in 'tui' mode.
-fn tui(scripts: &[script::Script]) -> Result<()> {
+// Runs lk in 'tui' mode. Like the other modes it returns the exit code of the function it ran,
+// or 0 if it didn't run one.
+fn tui(scripts: &[script::Script], concurrency: usize, runner: &Runner) -> Result<i32> {
     println!("Running lk in tui mode");
-    let result = tui::list::find(scripts)?;
-    if let Some(function) = result {
-        // We're going to write the equivalent lk command to the shell's history
-        // file, so the user can easily re-run it.
-        let history = UserShell::new();
-        match history {
-            Some(history) => {
-                let lk_command = format!("lk {} {}", function.0.file_name(), function.1.name,);
-                history.add_command(lk_command)?;
-            }
-            None => {
-                log::warn!("Unable to write to history file because we couldn't figure out what shell you're using");
-            }
+    let mut chosen = tui::list::find(scripts)?;
+    match chosen.len() {
+        0 => Ok(0),
+        1 => {
+            let (script, function) = chosen.remove(0);
+            Ok(runner.run(&script, &function, [].to_vec(), true)?)
+        }
+        _ => {
+            let jobs = chosen
+                .into_iter()
+                .map(|(script, function)| Job::new(script, function, Vec::new()))
+                .collect();
+            Ok(runner.run_parallel(jobs, concurrency)?)
         }
-        // Finally we execute the function using a temporary bash file.
-        BashFile::run(function.0.to_owned(), function.1.to_owned(), [].to_vec())?;
     }
-    Ok(())
+}
+
+/// Runs several functions at once, e.g. `lk --parallel svc_a.sh:lint svc_b.sh:lint`.
+fn parallel(
+    scripts: &[script::Script],
+    args: Cli,
+    concurrency: usize,
+    runner: &Runner,
+) -> Result<i32> {
+    // Every positional argument is a script:function pair.
+    let specs: Vec<String> = args
+        .script
+        .into_iter()
+        .chain(args.function)
+        .chain(args.params)
+        .collect();
+    if specs.is_empty() {
+        anyhow::bail!("--parallel needs functions to run, e.g. lk --parallel a.sh:lint b.sh:lint");
+    }
+    let jobs = specs
+        .iter()
+        .map(|spec| {
+            let (script_name, function_name) = spec.split_once(':').ok_or_else(|| {
+                anyhow::anyhow!("Expected script:function, e.g. build.sh:lint, got {}", spec)
+            })?;
+            let script = scripts
+                .iter()
+                .find(|script| script.file_name() == script_name)
+                .ok_or_else(|| anyhow::anyhow!("Didn't find a script with name {}", script_name))?;
+            let function = script.get(function_name).ok_or_else(|| {
+                anyhow::anyhow!(
+                    "Didn't find a function with name {} in {}",
+                    function_name,
+                    script_name
+                )
+            })?;
+            Ok(Job::new(script.to_owned(), function.to_owned(), Vec::new()))
+        })
+        .collect::<Result<Vec<Job>>>()?;
+    Ok(runner.run_parallel(jobs, concurrency)?)
 }
 
 /// Runs lk in 'fuzzy' mode.
-fn fuzzy(scripts: &[script::Script], lines_to_show: i8) -> Result<()> {
-    let result = FuzzyFinder::find(scripts_to_item(scripts), lines_to_show).unwrap();
-    if let Some(function) = result {
-        // We're going to write the equivalent lk command to the shell's history
-        // file, so the user can easily re-run it.
-        let history = UserShell::new();
-        match history {
-            Some(history) => {
-                let lk_command = format!("lk {} {}", function.0.file_name(), function.1.name,);
-                history.add_command(lk_command)?;
-            }
-            None => {
-                log::warn!("Unable to write to history file because we couldn't figure out what shell you're using");
-            }
-        }
-        // Finally we execute the function using a temporary bash file.
-        BashFile::run(function.0.to_owned(), function.1.to_owned(), [].to_vec())?;
+fn fuzzy(scripts: &[script::Script], lines_to_show: i8, runner: &Runner) -> Result<i32> {
+    let result = FuzzyFinder::find(scripts_to_item(scripts), lines_to_show)?;
+    match result {
+        Some((script, function)) => Ok(runner.run(script, function, [].to_vec(), true)?),
+        None => Ok(0),
     }
-    Ok(())
 }
 
-/// Runs lk in 'list' mode.
-fn list(executables: Executables, args: Cli) -> Result<()> {
-    // Did the user request a script?
+/// Runs lk in 'list' mode. `everything` has the hidden functions too, because they can still be
+/// run by name, and `scripts` has what we list.
+fn list(
+    everything: &[script::Script],
+    scripts: &[script::Script],
+    args: Cli,
+    runner: &Runner,
+    hide_rules: &HideRules,
+) -> Result<i32> {
+    // Did the user request a script, or one of the other groups, like `lk Makefile`?
     if let Some(script) = args.script {
-        // Is it a script that exists on disk?
-        if let Some(executable) = executables.get(&script) {
-            // Yay, confirmed script
-            let script = script::Script::new(executable)?;
+        if let Some(found) = everything
+            .iter()
+            .find(|s| s.file_name() == script || s.path() == script)
+        {
+            let mut script = found.to_owned();
             // Did the user pass a function?
             if let Some(function) = args.function {
+                // Hidden functions aren't listed, but you can still run one if you name it.
+                script
+                    .functions
+                    .iter_mut()
+                    .for_each(|f| hide_rules.apply_to(f));
                 // Is it a function that exists in the script we found?
                 if let Some(function) = script.get(&function) {
-                    // Finally we execute the function using a temporary bash file.
-                    BashFile::run(script.to_owned(), function.to_owned(), args.params)?;
+                    // The user typed this command, so it's already in their shell's history.
+                    return Ok(runner.run(&script, function, args.params, false)?);
                 } else {
                     print_bad_function_name(&script, &function);
                 }
             } else {
                 // No function, display a list of what's available
+                hide_rules.apply(std::slice::from_mut(&mut script));
+                catalogue::retain_tagged(&mut script, &args.tags);
                 script.pretty_print();
             }
+        } else if let Some((group, function)) = by_name(everything, &script) {
+            // Commands and aliases from lk.toml can be run by their name too, e.g. `lk build`.
+            let params = args.function.into_iter().chain(args.params).collect();
+            // The user typed this command, so it's already in their shell's history.
+            return Ok(runner.run(group, function, params, false)?);
         } else {
-            print_bad_script_name(&script, executables);
+            print_bad_script_name(&script, scripts);
         }
+    } else if !args.tags.is_empty() || args.by_owner {
+        // Tags and owners are about functions, so we list those rather than the scripts.
+        catalogue::pretty_print(scripts, args.by_owner);
     } else {
-        // No executable, display a list of what's available
-        executables.pretty_print();
+        // No script, display a list of what's available
+        print_scripts(scripts);
+    }
+    Ok(0)
+}
+
+/// The function with this name from a group whose functions can be run by name alone, e.g. a
+/// command from lk.toml. Alias names aren't case sensitive, because we read them in lowercase.
+fn by_name<'a>(
+    scripts: &'a [script::Script],
+    name: &str,
+) -> Option<(&'a script::Script, &'a Function)> {
+    let groups = || scripts.iter().filter(|script| script.by_name);
+    groups()
+        .find_map(|script| Some(script).zip(script.get(name)))
+        .or_else(|| groups().find_map(|script| Some(script).zip(script.get(&name.to_lowercase()))))
+}
+
+/// Checks the scripts, or just the one named, and reports what it finds. Returns 1 if there were
+/// any errors, so it can fail a build.
+fn lint(
+    executables: &Executables,
+    mut scripts: Vec<script::Script>,
+    hide_rules: &HideRules,
+    which: Option<&str>,
+    json: bool,
+) -> Result<i32> {
+    if let Some(which) = which {
+        scripts.retain(|script| script.file_name() == which || script.path() == which);
+    }
+    scripts.iter_mut().for_each(|script| {
+        script
+            .functions
+            .iter_mut()
+            .for_each(|f| hide_rules.apply_to(f))
+    });
+
+    let mut diagnostics = lint::lint(executables, &scripts);
+    if let Some(which) = which {
+        diagnostics.retain(|d| d.check != "not-executable" || d.path.ends_with(which));
+        if scripts.is_empty() && diagnostics.is_empty() {
+            anyhow::bail!("Didn't find a script called {} to lint", which);
+        }
+    }
+    if json {
+        println!("{}", lint::to_json(&diagnostics)?);
+    } else {
+        lint::pretty_print(&diagnostics);
+    }
+    Ok(lint::exit_code(&diagnostics))
+}
+
+/// Sets the execute bit on the scripts that don't have it, after showing what it's going to
+/// change and asking first.
+fn fix_perms(executables: &Executables, yes: bool) -> Result<()> {
+    let paths = executables.without_execute_bit();
+    if paths.is_empty() {
+        println!("Every script is already executable.");
+        return Ok(());
+    }
+    println!("These scripts aren't executable:");
+    for path in &paths {
+        println!("  {}", path.to_string_lossy());
+    }
+    if !yes {
+        print!("Make them executable? [y/N] ");
+        std::io::stdout().flush()?;
+        let mut answer = String::new();
+        std::io::stdin().read_line(&mut answer)?;
+        if !matches!(answer.trim(), "y" | "Y" | "yes") {
+            println!("Didn't change anything.");
+            return Ok(());
+        }
+    }
+    for path in paths {
+        executables::make_executable(path)
+            .with_context(|| format!("Couldn't make {} executable", path.to_string_lossy()))?;
+        println!("Made {} executable", path.to_string_lossy());
+    }
+    Ok(())
+}
+
+/// Shows the output of recent runs. With no argument we list the runs that have logs, most
+/// recent first. With a number we page through the log of that run.
+fn logs(history: &history::RunHistory, which: Option<&str>) -> Result<()> {
+    let records = history.logged_records()?;
+    match which {
+        None => {
+            if records.is_empty() {
+                println!("No captured output yet. Run a function with --capture, or set capture_output = true in lk.toml.");
+            }
+            for (i, record) in records.iter().enumerate() {
+                println!(
+                    "{:>3}  {}  {} -> {} ({})",
+                    i + 1,
+                    record.time,
+                    record.script,
+                    record.function,
+                    record.params.join(" ")
+                );
+            }
+        }
+        Some(which) => {
+            let n: usize = which
+                .parse()
+                .map_err(|_| anyhow::anyhow!("Expected the number of a run, got {}", which))?;
+            let Some(log) = n
+                .checked_sub(1)
+                .and_then(|i| records.get(i))
+                .and_then(|r| r.log.as_ref())
+            else {
+                anyhow::bail!(
+                    "There's no run number {}. Run `lk logs` to see what's available.",
+                    n
+                );
+            };
+            let pager = std::env::var("PAGER").unwrap_or_else(|_| "less -R".to_string());
+            let mut pager = pager.split_whitespace();
+            let status = std::process::Command::new(pager.next().unwrap_or("less"))
+                .args(pager)
+                .arg(log)
+                .status();
+            if !matches!(status, Ok(status) if status.success()) {
+                // No pager, so just dump it.
+                print!("{}", std::fs::read_to_string(log)?);
+            }
+        }
     }
     Ok(())
 }
@@ -268,8 +615,19 @@ fn scripts_to_item(scripts: &[script::Script]) -> Vec<Item<(&script::Script, &Fu
     let mut fuzzy_functions: Vec<Item<(&script::Script, &Function)>> = Vec::new();
     scripts.iter().for_each(|script| {
         script.functions.iter().for_each(|function| {
+            let from = match &function.source {
+                Some(source) => format!(" (from {source})"),
+                None => String::new(),
+            };
+            // The fuzzy finder can't dim a line, so hidden functions say they're hidden.
+            let hidden = if function.hidden { " (hidden)" } else { "" };
+            let notes: String = script
+                .notes
+                .iter()
+                .map(|note| format!(" ({note})"))
+                .collect();
             fuzzy_functions.push(Item::new(
-                format!("{} - {}", script.path(), function.name),
+                format!("{} - {}{from}{hidden}{notes}", script.path(), function.name),
                 (script, function),
             ))
         })
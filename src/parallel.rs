@@ -0,0 +1,142 @@
+/// Runs several functions at once, docker-compose style, and sums up how they went.
+use crate::process::TIMED_OUT;
+use pad::{Alignment, PadStr};
+use pastel_colours::{
+    BLUE_FG, DARK_GREEN_FG, GREEN_FG, ORANGE_FG, PINK_FG, RED_FG, RESET_FG, TEAL_FG,
+};
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+
+/// How one of the functions we ran in parallel got on.
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[non_exhaustive]
+pub struct Outcome {
+    pub name: String,
+    pub code: i32,
+    pub duration: Duration,
+}
+
+/// Runs `run_job` for every name, at most `concurrency` at a time. `run_job` gets the index of the
+/// job and the prefix to put on each line of its output, and returns the job's exit code. The
+/// outcomes are in the same order as the names.
+pub fn run<F>(names: &[String], concurrency: usize, run_job: F) -> Vec<Outcome>
+where
+    F: Fn(usize, String) -> crate::Result<i32> + Sync,
+{
+    let colours = [
+        BLUE_FG,
+        GREEN_FG,
+        ORANGE_FG,
+        PINK_FG,
+        TEAL_FG,
+        DARK_GREEN_FG,
+    ];
+    let width = names.iter().map(|name| name.len()).max().unwrap_or(0);
+    let next = AtomicUsize::new(0);
+    let outcomes: Mutex<Vec<Option<Outcome>>> = Mutex::new(vec![None; names.len()]);
+
+    std::thread::scope(|scope| {
+        for _ in 0..concurrency.clamp(1, names.len().max(1)) {
+            scope.spawn(|| loop {
+                let i = next.fetch_add(1, Ordering::SeqCst);
+                let Some(name) = names.get(i) else {
+                    break;
+                };
+                let colour = colours[i % colours.len()];
+                let padded = name.pad_to_width_with_alignment(width, Alignment::Left);
+                let prefix = format!("{colour}{padded} |{RESET_FG} ");
+
+                let started = Instant::now();
+                let code = run_job(i, prefix).unwrap_or_else(|err| {
+                    println!("{RED_FG}{name}: {err}{RESET_FG}");
+                    1
+                });
+                outcomes.lock().unwrap()[i] = Some(Outcome {
+                    name: name.to_owned(),
+                    code,
+                    duration: started.elapsed(),
+                });
+            });
+        }
+    });
+
+    outcomes
+        .into_inner()
+        .unwrap()
+        .into_iter()
+        .flatten()
+        .collect()
+}
+
+/// The exit code for a parallel run: that of the first function that failed, or 0.
+pub fn exit_code(outcomes: &[Outcome]) -> i32 {
+    outcomes
+        .iter()
+        .map(|outcome| outcome.code)
+        .find(|&code| code != 0)
+        .unwrap_or(0)
+}
+
+pub fn print_summary(outcomes: &[Outcome]) {
+    let width = outcomes
+        .iter()
+        .map(|outcome| outcome.name.len())
+        .max()
+        .unwrap_or(0);
+    println!();
+    for outcome in outcomes {
+        let name = outcome
+            .name
+            .pad_to_width_with_alignment(width, Alignment::Left);
+        let status = match outcome.code {
+            0 => format!("{GREEN_FG}pass{RESET_FG}"),
+            TIMED_OUT => format!("{RED_FG}timed out{RESET_FG}"),
+            code => format!("{RED_FG}fail ({code}){RESET_FG}"),
+        };
+        println!(
+            "{name}  {:>8}  {status}",
+            humantime::format_duration(Duration::from_millis(outcome.duration.as_millis() as u64))
+                .to_string()
+        );
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn runs_everything_and_keeps_the_order() {
+        let names: Vec<String> = ["a", "b", "c", "d"].iter().map(|s| s.to_string()).collect();
+        let running = AtomicUsize::new(0);
+        let most_running = AtomicUsize::new(0);
+
+        let outcomes = run(&names, 2, |i, prefix| {
+            assert!(prefix.contains(&names[i]));
+            let now = running.fetch_add(1, Ordering::SeqCst) + 1;
+            most_running.fetch_max(now, Ordering::SeqCst);
+            std::thread::sleep(Duration::from_millis(50));
+            running.fetch_sub(1, Ordering::SeqCst);
+            Ok(i as i32)
+        });
+
+        assert_eq!(
+            outcomes.iter().map(|o| o.name.as_str()).collect::<Vec<_>>(),
+            vec!["a", "b", "c", "d"]
+        );
+        assert_eq!(
+            outcomes.iter().map(|o| o.code).collect::<Vec<_>>(),
+            vec![0, 1, 2, 3]
+        );
+        assert!(most_running.load(Ordering::SeqCst) <= 2);
+        assert_eq!(exit_code(&outcomes), 1);
+    }
+
+    #[test]
+    fn errors_count_as_failures() {
+        let names = vec!["a".to_string()];
+        let outcomes = run(&names, 4, |_, _| Err(std::io::Error::other("nope").into()));
+        assert_eq!(exit_code(&outcomes), 1);
+    }
+}
@@ -0,0 +1,1284 @@
+/// A small bash parser. It doesn't try to understand everything bash can do, just enough of the
+/// structure of a script to find the functions defined at its top level: quoting, heredocs,
+/// comments, substitutions and nesting.
+use std::mem;
+
+/// A function defined at the top level of a script. Lines start at 1.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct FunctionDef {
+    pub name: String,
+    pub start_line: usize,
+    pub end_line: usize,
+}
+
+/// A comment that's on a line of its own.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Comment {
+    pub line: usize,
+    pub text: String,
+}
+
+/// A `source` or `.` of a file whose path we know without running the script, e.g.
+/// `source ./lib/common.sh`. The path is unquoted but otherwise as written.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Source {
+    pub line: usize,
+    pub path: String,
+}
+
+/// A simple command that isn't in a function, e.g. `main "$@"`, so it may run when the script is
+/// sourced. The words are as written, quotes and all.
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[non_exhaustive]
+pub struct Command {
+    pub line: usize,
+    pub words: Vec<String>,
+    /// Whether it's guarded so it only runs when the script is run directly, not sourced, e.g. by
+    /// `if [[ "${BASH_SOURCE[0]}" == "$0" ]]`.
+    pub guarded: bool,
+}
+
+/// What we found in a script.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Parsed {
+    pub functions: Vec<FunctionDef>,
+    pub comments: Vec<Comment>,
+    /// Files sourced outside of any function, so they're loaded whenever the script is.
+    pub sources: Vec<Source>,
+    /// Commands outside of any function, apart from `source`s and plain variable assignments.
+    pub commands: Vec<Command>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+enum Kind {
+    /// A word, including any quotes or substitutions in it.
+    Word(String),
+    /// An operator, e.g. `;`, `&&`, `(` or `<<`.
+    Op(&'static str),
+    Newline,
+    /// The text is the whole comment, including the `#`.
+    Comment {
+        text: String,
+        own_line: bool,
+    },
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+struct Token {
+    kind: Kind,
+    line: usize,
+}
+
+/// Longest first, so we always match the longest operator we can.
+const OPERATORS: [&str; 21] = [
+    ";;&", "<<-", "<<<", "&&", "||", ";;", ";&", "|&", "<<", ">>", "<&", ">&", "&>", ">|", ";",
+    "&", "|", "(", ")", "<", ">",
+];
+
+struct Lexer {
+    chars: Vec<char>,
+    pos: usize,
+    line: usize,
+    /// Heredocs whose bodies start after the next newline: the delimiter, and whether tabs are
+    /// stripped (`<<-`).
+    pending_heredocs: Vec<(String, bool)>,
+    /// The line of the last token, so we know if a comment is on a line of its own.
+    last_token_line: usize,
+}
+
+impl Lexer {
+    fn new(source: &str) -> Self {
+        Self {
+            chars: source.chars().collect(),
+            pos: 0,
+            line: 1,
+            pending_heredocs: Vec::new(),
+            last_token_line: 0,
+        }
+    }
+
+    fn peek(&self) -> Option<char> {
+        self.chars.get(self.pos).copied()
+    }
+
+    fn peek_at(&self, offset: usize) -> Option<char> {
+        self.chars.get(self.pos + offset).copied()
+    }
+
+    fn starts_with(&self, s: &str) -> bool {
+        s.chars()
+            .enumerate()
+            .all(|(i, c)| self.peek_at(i) == Some(c))
+    }
+
+    /// Moves on one char, keeping count of lines.
+    fn bump(&mut self) -> Option<char> {
+        let c = self.peek()?;
+        self.pos += 1;
+        if c == '\n' {
+            self.line += 1;
+        }
+        Some(c)
+    }
+
+    fn tokens(mut self) -> Vec<Token> {
+        let mut tokens = Vec::new();
+        while let Some(token) = self.next_token() {
+            self.last_token_line = token.line;
+            tokens.push(token);
+        }
+        tokens
+    }
+
+    fn next_token(&mut self) -> Option<Token> {
+        // Skip blanks and line continuations.
+        loop {
+            match self.peek()? {
+                ' ' | '\t' | '\r' => {
+                    self.bump();
+                }
+                '\\' if self.peek_at(1) == Some('\n') => {
+                    self.bump();
+                    self.bump();
+                }
+                _ => break,
+            }
+        }
+
+        let line = self.line;
+        let c = self.peek()?;
+        let kind = if c == '\n' {
+            self.bump();
+            self.read_heredoc_bodies();
+            Kind::Newline
+        } else if c == '#' {
+            let mut text = String::new();
+            while let Some(c) = self.peek() {
+                if c == '\n' {
+                    break;
+                }
+                text.push(c);
+                self.bump();
+            }
+            Kind::Comment {
+                text,
+                own_line: self.last_token_line != line,
+            }
+        } else if (c == '<' || c == '>') && self.peek_at(1) == Some('(') {
+            // Process substitution is a word, e.g. `diff <(ls a) <(ls b)`.
+            let mut word = String::new();
+            word.push(self.bump()?);
+            self.read_balanced_parens(&mut word);
+            Kind::Word(word)
+        } else if self.starts_with("((") {
+            // Arithmetic, e.g. `(( i << 2 ))`, which we don't want to mistake for a heredoc.
+            let mut word = String::new();
+            self.read_arithmetic(&mut word);
+            Kind::Word(word)
+        } else if let Some(op) = OPERATORS.iter().find(|op| self.starts_with(op)) {
+            op.chars().for_each(|_| {
+                self.bump();
+            });
+            if *op == "<<" || *op == "<<-" {
+                self.read_heredoc_delimiter(*op == "<<-");
+            }
+            Kind::Op(op)
+        } else {
+            let word = self.read_word();
+            if word == "[[" {
+                // Conditional expressions can contain `<`, `(` and friends, so we take them whole.
+                Kind::Word(self.read_until_word("]]", word))
+            } else if !word.is_empty()
+                && word.chars().all(|c| c.is_ascii_digit())
+                && matches!(self.peek(), Some('<' | '>'))
+            {
+                // The file descriptor of a redirection, e.g. the 2 in `2>/dev/null`, isn't a word.
+                return self.next_token();
+            } else {
+                Kind::Word(word)
+            }
+        };
+        Some(Token { kind, line })
+    }
+
+    fn is_metachar(c: char) -> bool {
+        matches!(
+            c,
+            ' ' | '\t' | '\r' | '\n' | ';' | '&' | '|' | '(' | ')' | '<' | '>'
+        )
+    }
+
+    /// Reads a word, including everything quoted or substituted in it.
+    fn read_word(&mut self) -> String {
+        let mut word = String::new();
+        while let Some(c) = self.peek() {
+            match c {
+                // An array assignment, e.g. `SERVICES=(api web)`, is one word, parens and all,
+                // rather than an assignment followed by a subshell.
+                '(' if word.ends_with('=') && is_assignment(&word) => {
+                    self.read_balanced_parens(&mut word)
+                }
+                c if Self::is_metachar(c) => break,
+                '\\' => {
+                    self.bump();
+                    if let Some(escaped) = self.bump() {
+                        if escaped != '\n' {
+                            word.push('\\');
+                            word.push(escaped);
+                        }
+                    }
+                }
+                '\'' => self.read_single_quoted(&mut word),
+                '"' => self.read_double_quoted(&mut word),
+                '`' => self.read_backticks(&mut word),
+                '$' => self.read_dollar(&mut word),
+                _ => {
+                    word.push(c);
+                    self.bump();
+                }
+            }
+        }
+        word
+    }
+
+    /// Keeps reading words until we find `end`, and returns the lot as one word.
+    fn read_until_word(&mut self, end: &str, mut text: String) -> String {
+        loop {
+            match self.peek() {
+                None => return text,
+                Some(c) if Self::is_metachar(c) => {
+                    text.push(c);
+                    self.bump();
+                }
+                Some(_) => {
+                    let word = self.read_word();
+                    text.push_str(&word);
+                    if word == end {
+                        return text;
+                    }
+                }
+            }
+        }
+    }
+
+    fn read_single_quoted(&mut self, word: &mut String) {
+        word.push('\'');
+        self.bump();
+        while let Some(c) = self.bump() {
+            word.push(c);
+            if c == '\'' {
+                break;
+            }
+        }
+    }
+
+    fn read_double_quoted(&mut self, word: &mut String) {
+        word.push('"');
+        self.bump();
+        while let Some(c) = self.peek() {
+            match c {
+                '"' => {
+                    word.push(c);
+                    self.bump();
+                    break;
+                }
+                '\\' => {
+                    word.push(c);
+                    self.bump();
+                    if let Some(escaped) = self.bump() {
+                        word.push(escaped);
+                    }
+                }
+                '`' => self.read_backticks(word),
+                '$' => self.read_dollar(word),
+                _ => {
+                    word.push(c);
+                    self.bump();
+                }
+            }
+        }
+    }
+
+    fn read_backticks(&mut self, word: &mut String) {
+        word.push('`');
+        self.bump();
+        while let Some(c) = self.bump() {
+            word.push(c);
+            match c {
+                '\\' => {
+                    if let Some(escaped) = self.bump() {
+                        word.push(escaped);
+                    }
+                }
+                '`' => break,
+                _ => {}
+            }
+        }
+    }
+
+    /// Reads `$(...)`, `$((...))`, `${...}`, `$'...'` or just a `$`.
+    fn read_dollar(&mut self, word: &mut String) {
+        word.push('$');
+        self.bump();
+        match self.peek() {
+            Some('(') if self.starts_with("((") => self.read_arithmetic(word),
+            Some('(') => self.read_balanced_parens(word),
+            Some('{') => {
+                word.push('{');
+                self.bump();
+                let mut depth = 1;
+                while let Some(c) = self.peek() {
+                    match c {
+                        '\'' => self.read_single_quoted(word),
+                        '"' => self.read_double_quoted(word),
+                        '$' => self.read_dollar(word),
+                        '\\' => {
+                            word.push(c);
+                            self.bump();
+                            if let Some(escaped) = self.bump() {
+                                word.push(escaped);
+                            }
+                        }
+                        _ => {
+                            word.push(c);
+                            self.bump();
+                            if c == '{' {
+                                depth += 1;
+                            } else if c == '}' {
+                                depth -= 1;
+                                if depth == 0 {
+                                    break;
+                                }
+                            }
+                        }
+                    }
+                }
+            }
+            Some('\'') => {
+                // ANSI-C quoting, where `\'` doesn't end the string.
+                word.push('\'');
+                self.bump();
+                while let Some(c) = self.bump() {
+                    word.push(c);
+                    match c {
+                        '\\' => {
+                            if let Some(escaped) = self.bump() {
+                                word.push(escaped);
+                            }
+                        }
+                        '\'' => break,
+                        _ => {}
+                    }
+                }
+            }
+            _ => {}
+        }
+    }
+
+    /// Reads from an opening paren to its matching close. Command substitutions can contain
+    /// anything a script can, so we use the lexer itself to find the end of them. Inside a
+    /// `case` the patterns' closing parens don't have an opening one, so we don't count them.
+    fn read_balanced_parens(&mut self, word: &mut String) {
+        let start = self.pos;
+        self.bump();
+        let mut depth = 1;
+        let mut cases = 0;
+        while depth > 0 {
+            let Some(token) = self.next_token() else {
+                break;
+            };
+            match token.kind {
+                Kind::Op("(") => depth += 1,
+                Kind::Op(")") if cases == 0 => depth -= 1,
+                Kind::Word(w) if w == "case" => cases += 1,
+                Kind::Word(w) if w == "esac" => cases -= 1,
+                _ => {}
+            }
+        }
+        word.extend(&self.chars[start..self.pos]);
+    }
+
+    /// Reads `((...))`. There are no commands in arithmetic, so we only need to count parens.
+    fn read_arithmetic(&mut self, word: &mut String) {
+        let mut depth = 0;
+        while let Some(c) = self.bump() {
+            word.push(c);
+            match c {
+                '(' => depth += 1,
+                ')' => {
+                    depth -= 1;
+                    if depth == 0 {
+                        break;
+                    }
+                }
+                _ => {}
+            }
+        }
+    }
+
+    /// After `<<` comes the delimiter, which may be quoted. The body comes after the next newline.
+    fn read_heredoc_delimiter(&mut self, strip_tabs: bool) {
+        while matches!(self.peek(), Some(' ') | Some('\t')) {
+            self.bump();
+        }
+        let mut delimiter = String::new();
+        while let Some(c) = self.peek() {
+            if Self::is_metachar(c) {
+                break;
+            }
+            self.bump();
+            if c != '\'' && c != '"' && c != '\\' {
+                delimiter.push(c);
+            }
+        }
+        self.pending_heredocs.push((delimiter, strip_tabs));
+    }
+
+    /// Skips the bodies of any heredocs started on the line we just finished.
+    fn read_heredoc_bodies(&mut self) {
+        for (delimiter, strip_tabs) in mem::take(&mut self.pending_heredocs) {
+            while self.peek().is_some() {
+                let mut line = String::new();
+                while let Some(c) = self.bump() {
+                    if c == '\n' {
+                        break;
+                    }
+                    line.push(c);
+                }
+                let line = if strip_tabs {
+                    line.trim_start_matches('\t')
+                } else {
+                    line.as_str()
+                };
+                if line.trim_end_matches('\r') == delimiter {
+                    break;
+                }
+            }
+        }
+    }
+}
+
+#[derive(Debug, PartialEq, Eq)]
+enum FrameKind {
+    Brace,
+    Paren,
+    If,
+    Loop,
+    Case(CaseState),
+}
+
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+enum CaseState {
+    /// Before `in`.
+    Subject,
+    /// Waiting for a pattern's `)`.
+    Pattern,
+    /// Inside a branch.
+    Body,
+}
+
+/// When a command runs: whenever the script is sourced or executed, or only one of the two. This
+/// is how we recognise guards like `if [[ "${BASH_SOURCE[0]}" == "$0" ]]; then main "$@"; fi`.
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+enum Runs {
+    Always,
+    OnlyDirectly,
+    OnlySourced,
+}
+
+impl Runs {
+    /// When something runs if it needs both of these.
+    fn and(self, other: Runs) -> Runs {
+        match (self, other) {
+            (Runs::OnlyDirectly, _) | (_, Runs::OnlyDirectly) => Runs::OnlyDirectly,
+            (Runs::OnlySourced, _) | (_, Runs::OnlySourced) => Runs::OnlySourced,
+            _ => Runs::Always,
+        }
+    }
+
+    fn not(self) -> Runs {
+        match self {
+            Runs::Always => Runs::Always,
+            Runs::OnlyDirectly => Runs::OnlySourced,
+            Runs::OnlySourced => Runs::OnlyDirectly,
+        }
+    }
+}
+
+struct Frame {
+    kind: FrameKind,
+    /// The function whose body this is, if it's a top level function's body.
+    function: Option<usize>,
+    /// Whether it's the body of any function, top level or not, so nothing in it runs until the
+    /// function's called.
+    body: bool,
+    /// When the frame as a whole runs.
+    runs: Runs,
+    /// For an `if`, when its condition is true, if it's a test we understand.
+    condition: Option<Runs>,
+    /// Whether we're waiting for the first command, e.g. an `if`'s condition.
+    awaiting_first: bool,
+    /// Whether we're past an `if`'s `else` or `elif`.
+    in_else: bool,
+    /// Whether this is a `(return 0 2>/dev/null)`, which only succeeds when sourced.
+    returns: bool,
+    /// Whether it's negated with `!`.
+    negated: bool,
+}
+
+impl Frame {
+    fn new(kind: FrameKind, function: Option<usize>, body: bool, runs: Runs) -> Self {
+        Self {
+            kind,
+            function,
+            body,
+            runs,
+            condition: None,
+            awaiting_first: true,
+            in_else: false,
+            returns: false,
+            negated: false,
+        }
+    }
+
+    /// When the commands inside the frame run.
+    fn runs_inside(&self) -> Runs {
+        match (self.condition, self.in_else) {
+            (Some(condition), false) => self.runs.and(condition),
+            (Some(condition), true) => self.runs.and(condition.not()),
+            (None, _) => self.runs,
+        }
+    }
+}
+
+/// When a command at this point runs. `chain` is from any `&&` or `||` before it, and `stopped` is
+/// set if there's been a `return` that stops the script being sourced.
+fn runs_here(stack: &[Frame], chain: Runs, stopped: bool) -> Runs {
+    if stopped {
+        return Runs::OnlyDirectly;
+    }
+    stack
+        .iter()
+        .fold(chain, |runs, frame| runs.and(frame.runs_inside()))
+}
+
+/// Whether the command is a test of whether the script is being sourced, and if so when it's true.
+/// E.g. `[[ "${BASH_SOURCE[0]}" == "$0" ]]` is only true when the script is run directly.
+fn test_of_sourcing(words: &[String]) -> Option<Runs> {
+    let test = words.join(" ");
+    let is_test =
+        matches!(words.first().map(String::as_str), Some("[" | "test")) || test.starts_with("[[");
+    if !is_test || !test.contains("BASH_SOURCE") || !(test.contains("$0") || test.contains("${0}"))
+    {
+        return None;
+    }
+    if test.contains("!=") {
+        Some(Runs::OnlySourced)
+    } else if test.contains("==") || test.contains(" = ") || test.contains("-ef") {
+        Some(Runs::OnlyDirectly)
+    } else {
+        None
+    }
+}
+
+/// Parses a script, finding the functions defined at its top level, and its comments.
+pub fn parse(source: &str) -> Parsed {
+    let tokens = Lexer::new(source).tokens();
+    let mut parsed = Parsed::default();
+    let mut stack: Vec<Frame> = Vec::new();
+    let mut command_start = true;
+    // A function whose name we've seen, waiting for its body, and whether it's at the top level.
+    let mut pending: Option<(FunctionDef, bool)> = None;
+    // For spotting guards: when the `&&`s and `||`s so far mean the next command runs, when the
+    // last command was true if it was a test of sourcing, whether it's negated, and whether a
+    // `return` has stopped the script being sourced.
+    let mut chain = Runs::Always;
+    let mut last_test: Option<Runs> = None;
+    let mut negate = false;
+    let mut stopped = false;
+
+    let mut i = 0;
+    while i < tokens.len() {
+        let token = &tokens[i];
+        i += 1;
+
+        // Case patterns don't follow the usual rules, so we deal with them first.
+        if let Some(Frame {
+            kind: FrameKind::Case(state),
+            ..
+        }) = stack.last_mut()
+        {
+            match (&*state, &token.kind) {
+                (CaseState::Subject, Kind::Word(w)) if w == "in" => {
+                    *state = CaseState::Pattern;
+                    continue;
+                }
+                (CaseState::Subject, _) => continue,
+                (CaseState::Pattern, Kind::Word(w)) if w == "esac" => {
+                    let frame = stack.pop();
+                    end_function(frame, &mut parsed, token.line);
+                    command_start = false;
+                    continue;
+                }
+                (CaseState::Pattern, Kind::Op(")")) => {
+                    *state = CaseState::Body;
+                    command_start = true;
+                    continue;
+                }
+                (CaseState::Pattern, _) => continue,
+                (CaseState::Body, Kind::Op(";;") | Kind::Op(";&") | Kind::Op(";;&")) => {
+                    *state = CaseState::Pattern;
+                    continue;
+                }
+                (CaseState::Body, _) => {}
+            }
+        }
+
+        // Is this where a function we've seen the name of gets its body?
+        let opens_body = match &token.kind {
+            Kind::Word(w) => {
+                command_start
+                    && matches!(
+                        w.as_str(),
+                        "{" | "if" | "for" | "while" | "until" | "select" | "case"
+                    )
+            }
+            Kind::Op("(") => command_start,
+            _ => false,
+        };
+        let (function, body) = match pending.take() {
+            Some((def, true)) if opens_body => {
+                parsed.functions.push(def);
+                (Some(parsed.functions.len() - 1), true)
+            }
+            Some((_, false)) if opens_body => (None, true),
+            Some(def) if matches!(token.kind, Kind::Newline | Kind::Comment { .. }) => {
+                // The body can be on the next line.
+                pending = Some(def);
+                (None, false)
+            }
+            _ => (None, false),
+        };
+
+        match &token.kind {
+            Kind::Comment { text, own_line } => {
+                if *own_line {
+                    parsed.comments.push(Comment {
+                        line: token.line,
+                        text: text.to_owned(),
+                    });
+                }
+            }
+            Kind::Newline => {
+                chain = Runs::Always;
+                last_test = None;
+                command_start = true;
+            }
+            Kind::Op(op) => match *op {
+                "(" if command_start => {
+                    let mut frame = Frame::new(
+                        FrameKind::Paren,
+                        function,
+                        body,
+                        runs_here(&stack, chain, stopped),
+                    );
+                    frame.negated = std::mem::take(&mut negate);
+                    stack.push(frame);
+                    chain = Runs::Always;
+                }
+                ")" => {
+                    // `(return 0 2>/dev/null)` is how some scripts test whether they're sourced.
+                    if let Some(frame) = stack.last().filter(|f| f.kind == FrameKind::Paren) {
+                        last_test = frame.returns.then_some(if frame.negated {
+                            Runs::OnlyDirectly
+                        } else {
+                            Runs::OnlySourced
+                        });
+                    }
+                    close(&mut stack, &mut parsed, FrameKind::Paren, token.line);
+                    set_condition(&mut stack, last_test);
+                    command_start = false;
+                }
+                "&&" | "||" => {
+                    let test = last_test.take().unwrap_or(Runs::Always);
+                    chain = chain.and(if *op == "&&" { test } else { test.not() });
+                    command_start = true;
+                }
+                "|" | "|&" => command_start = true,
+                ";" | "&" | ";;" | ";&" | ";;&" => {
+                    chain = Runs::Always;
+                    last_test = None;
+                    command_start = true;
+                }
+                _ => command_start = false,
+            },
+            Kind::Word(word) if command_start => {
+                command_start = false;
+                match word.as_str() {
+                    "{" => {
+                        stack.push(Frame::new(
+                            FrameKind::Brace,
+                            function,
+                            body,
+                            runs_here(&stack, chain, stopped),
+                        ));
+                        chain = Runs::Always;
+                        command_start = true;
+                    }
+                    "}" => close(&mut stack, &mut parsed, FrameKind::Brace, token.line),
+                    "if" | "while" | "until" => {
+                        let kind = if word == "if" {
+                            FrameKind::If
+                        } else {
+                            FrameKind::Loop
+                        };
+                        stack.push(Frame::new(
+                            kind,
+                            function,
+                            body,
+                            runs_here(&stack, chain, stopped),
+                        ));
+                        chain = Runs::Always;
+                        command_start = true;
+                    }
+                    "for" | "select" | "case" => {
+                        let kind = if word == "case" {
+                            FrameKind::Case(CaseState::Subject)
+                        } else {
+                            FrameKind::Loop
+                        };
+                        stack.push(Frame::new(
+                            kind,
+                            function,
+                            body,
+                            runs_here(&stack, chain, stopped),
+                        ));
+                        chain = Runs::Always;
+                    }
+                    "fi" => close(&mut stack, &mut parsed, FrameKind::If, token.line),
+                    "done" => close(&mut stack, &mut parsed, FrameKind::Loop, token.line),
+                    "esac" => {
+                        if let Some(Frame {
+                            kind: FrameKind::Case(_),
+                            ..
+                        }) = stack.last()
+                        {
+                            let frame = stack.pop();
+                            end_function(frame, &mut parsed, token.line);
+                        }
+                    }
+                    "then" | "else" | "elif" => {
+                        if let Some(frame) = stack.last_mut().filter(|f| f.kind == FrameKind::If) {
+                            frame.awaiting_first = false;
+                            frame.in_else = word != "then";
+                        }
+                        chain = Runs::Always;
+                        command_start = true;
+                    }
+                    "!" => {
+                        negate = true;
+                        command_start = true;
+                    }
+                    "do" | "time" => command_start = true,
+                    "source" | "." => {
+                        let in_function = stack.iter().any(|frame| frame.body);
+                        if let Some(Token {
+                            kind: Kind::Word(path),
+                            ..
+                        }) = tokens.get(i)
+                        {
+                            match static_path(path) {
+                                Some(path) if !in_function => parsed.sources.push(Source {
+                                    line: token.line,
+                                    path,
+                                }),
+                                _ => {}
+                            }
+                        }
+                    }
+                    "function" => {
+                        // `function name`, `function name()` or `function name ()`.
+                        if let Some(Token {
+                            kind: Kind::Word(name),
+                            ..
+                        }) = tokens.get(i)
+                        {
+                            let name = name.trim_end_matches("()").to_string();
+                            i += 1;
+                            if tokens.get(i).map(|t| &t.kind) == Some(&Kind::Op("("))
+                                && tokens.get(i + 1).map(|t| &t.kind) == Some(&Kind::Op(")"))
+                            {
+                                i += 2;
+                            }
+                            let def = FunctionDef {
+                                name,
+                                start_line: token.line,
+                                end_line: token.line,
+                            };
+                            pending = Some((def, stack.is_empty()));
+                            command_start = true;
+                        }
+                    }
+                    _ => {
+                        // `name()` or `name ()`.
+                        if tokens.get(i).map(|t| &t.kind) == Some(&Kind::Op("("))
+                            && tokens.get(i + 1).map(|t| &t.kind) == Some(&Kind::Op(")"))
+                        {
+                            i += 2;
+                            if is_valid_name(word) {
+                                let def = FunctionDef {
+                                    name: word.to_owned(),
+                                    start_line: token.line,
+                                    end_line: token.line,
+                                };
+                                pending = Some((def, stack.is_empty()));
+                            }
+                            command_start = true;
+                        } else if is_assignment(word) {
+                            // `FOO=bar cmd` runs `cmd`, so we're still looking for the command.
+                            command_start = true;
+                        } else if !stack.iter().any(|frame| frame.body) {
+                            let words: Vec<String> = tokens[i - 1..]
+                                .iter()
+                                .map_while(|token| match &token.kind {
+                                    Kind::Word(word) => Some(word.to_owned()),
+                                    _ => None,
+                                })
+                                .collect();
+                            let runs = runs_here(&stack, chain, stopped);
+                            let in_subshell = stack.iter().any(|f| f.kind == FrameKind::Paren);
+                            if word == "return" {
+                                if let Some(frame) = stack
+                                    .last_mut()
+                                    .filter(|f| f.kind == FrameKind::Paren && f.awaiting_first)
+                                {
+                                    frame.returns = true;
+                                } else if !in_subshell && runs != Runs::OnlyDirectly {
+                                    // Sourcing stops here, so nothing after this runs under lk.
+                                    stopped = true;
+                                }
+                            }
+                            last_test = test_of_sourcing(&words).map(|test| {
+                                if std::mem::take(&mut negate) {
+                                    test.not()
+                                } else {
+                                    test
+                                }
+                            });
+                            set_condition(&mut stack, last_test);
+                            parsed.commands.push(Command {
+                                line: token.line,
+                                words,
+                                guarded: runs == Runs::OnlyDirectly,
+                            });
+                        }
+                    }
+                }
+            }
+            Kind::Word(_) => {}
+        }
+    }
+
+    // Anything left open runs to the end of the script, e.g. if it's missing a closing brace.
+    let last_line = tokens.last().map(|token| token.line).unwrap_or(1);
+    while let Some(frame) = stack.pop() {
+        end_function(Some(frame), &mut parsed, last_line);
+    }
+
+    parsed
+}
+
+/// If the innermost frame is waiting for its first command, it's had it. If that's an `if`, the
+/// command was its condition.
+fn set_condition(stack: &mut [Frame], test: Option<Runs>) {
+    if let Some(frame) = stack.last_mut().filter(|f| f.awaiting_first) {
+        frame.awaiting_first = false;
+        if frame.kind == FrameKind::If {
+            frame.condition = test;
+        }
+    }
+}
+
+/// Closes the innermost frame, if it's the kind we expect.
+fn close(stack: &mut Vec<Frame>, parsed: &mut Parsed, kind: FrameKind, line: usize) {
+    if stack.last().map(|frame| &frame.kind) == Some(&kind) {
+        let frame = stack.pop();
+        end_function(frame, parsed, line);
+    }
+}
+
+fn end_function(frame: Option<Frame>, parsed: &mut Parsed, line: usize) {
+    if let Some(index) = frame.and_then(|frame| frame.function) {
+        parsed.functions[index].end_line = line;
+    }
+}
+
+/// Unquotes a word if it's a path we can follow without running anything, i.e. it has no
+/// expansions or globs in it.
+fn static_path(word: &str) -> Option<String> {
+    let path = match (word.strip_prefix('\''), word.strip_prefix('"')) {
+        (Some(quoted), _) => quoted.strip_suffix('\'')?.to_string(),
+        (_, Some(quoted)) => quoted.strip_suffix('"')?.to_string(),
+        _ => word.to_string(),
+    };
+    let dynamic = path
+        .chars()
+        .any(|c| matches!(c, '$' | '`' | '\'' | '"' | '\\' | '*' | '?' | '['));
+    if path.is_empty() || dynamic {
+        None
+    } else {
+        Some(path)
+    }
+}
+
+/// Whether the word is a variable assignment, e.g. `FOO=bar`, `FOO+=bar` or `FOO[1]=bar`.
+fn is_assignment(word: &str) -> bool {
+    let Some(equals) = word.find('=') else {
+        return false;
+    };
+    let name = word[..equals].trim_end_matches('+');
+    let name = match name.split_once('[') {
+        Some((name, index)) if index.ends_with(']') => name,
+        Some(_) => return false,
+        None => name,
+    };
+    let mut chars = name.chars();
+    matches!(chars.next(), Some(c) if c.is_ascii_alphabetic() || c == '_')
+        && chars.all(|c| c.is_ascii_alphanumeric() || c == '_')
+}
+
+/// Function names can be almost anything, but not anything quoted or expanded.
+fn is_valid_name(name: &str) -> bool {
+    !name.is_empty()
+        && !name
+            .chars()
+            .any(|c| matches!(c, '$' | '\'' | '"' | '`' | '=' | '\\' | '{' | '}'))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn functions(source: &str) -> Vec<(String, usize, usize)> {
+        parse(source)
+            .functions
+            .into_iter()
+            .map(|f| (f.name, f.start_line, f.end_line))
+            .collect()
+    }
+
+    fn f(name: &str, start: usize, end: usize) -> (String, usize, usize) {
+        (name.to_string(), start, end)
+    }
+
+    #[test]
+    fn finds_the_usual_styles() {
+        let source = r#"some_function(){
+    echo one
+}
+   another_function   ()   {
+    echo two
+}
+third()
+{
+    echo three
+}
+function fourth {
+    echo four
+}
+function fifth() {
+    echo five
+}
+sixth() ( cd /tmp && ls )
+"#;
+        assert_eq!(
+            functions(source),
+            vec![
+                f("some_function", 1, 3),
+                f("another_function", 4, 6),
+                f("third", 7, 10),
+                f("fourth", 11, 13),
+                f("fifth", 14, 16),
+                f("sixth", 17, 17),
+            ]
+        );
+    }
+
+    #[test]
+    fn ignores_functions_in_strings_heredocs_and_comments() {
+        let source = r#"echo "foo() {"
+echo 'bar() {'
+# baz() {
+cat <<EOF
+qux() {
+}
+EOF
+cat <<-'END'
+	quux() {
+	}
+	END
+real() {
+    echo "}"
+}
+"#;
+        assert_eq!(functions(source), vec![f("real", 12, 14)]);
+    }
+
+    #[test]
+    fn only_reports_top_level_functions() {
+        let source = r#"outer() {
+    inner() {
+        echo inner
+    }
+    if true; then
+        echo "${x:-}"
+    fi
+}
+if [[ -n "$x" ]]; then
+    conditional() { echo no; }
+fi
+"#;
+        assert_eq!(functions(source), vec![f("outer", 1, 8)]);
+    }
+
+    #[test]
+    fn understands_case_and_substitutions() {
+        let source = r#"pick() {
+    case "$1" in
+        a) echo "a)" ;;
+        (b) echo $(case x in x) echo y;; esac) ;;
+        *) echo `echo }` ;;
+    esac
+    local count=$(( 1 << 2 ))
+    diff <(ls a) <(ls b)
+}
+after() {
+    :
+}
+"#;
+        assert_eq!(functions(source), vec![f("pick", 1, 9), f("after", 10, 12)]);
+    }
+
+    #[test]
+    fn collects_own_line_comments() {
+        let source = "#!/usr/bin/env bash\n# First\necho hi # not this\n  # Second\n";
+        assert_eq!(
+            parse(source).comments,
+            vec![
+                Comment {
+                    line: 1,
+                    text: "#!/usr/bin/env bash".to_string()
+                },
+                Comment {
+                    line: 2,
+                    text: "# First".to_string()
+                },
+                Comment {
+                    line: 4,
+                    text: "# Second".to_string()
+                },
+            ]
+        );
+    }
+
+    #[test]
+    fn finds_static_sources() {
+        let source = r#"source ./lib/common.sh
+. "lib/quoted file.sh"
+source "${BASH_SOURCE%/*}/dynamic.sh"
+if [[ -f ~/.lkrc ]]; then
+    source ~/.lkrc
+fi
+setup() {
+    source ./only_when_called.sh
+}
+echo source ./not_a_command.sh
+"#;
+        assert_eq!(
+            parse(source).sources,
+            vec![
+                Source {
+                    line: 1,
+                    path: "./lib/common.sh".to_string()
+                },
+                Source {
+                    line: 2,
+                    path: "lib/quoted file.sh".to_string()
+                },
+                Source {
+                    line: 5,
+                    path: "~/.lkrc".to_string()
+                },
+            ]
+        );
+    }
+
+    #[test]
+    fn finds_top_level_commands() {
+        let source = r#"set -euo pipefail
+readonly NAME="x"
+FOO=bar
+DEBUG=1 cat test.sh
+setup() {
+    echo "not at the top level"
+}
+if [[ -n "$CI" ]]; then
+    echo ci
+fi
+main "$@"
+"#;
+        let parsed = parse(source);
+        let commands: Vec<(usize, Vec<&str>)> = parsed
+            .commands
+            .iter()
+            .map(|c| (c.line, c.words.iter().map(String::as_str).collect()))
+            .collect();
+        assert_eq!(
+            commands,
+            vec![
+                (1, vec!["set", "-euo", "pipefail"]),
+                (2, vec!["readonly", "NAME=\"x\""]),
+                (4, vec!["cat", "test.sh"]),
+                (8, vec!["[[ -n \"$CI\" ]]"]),
+                (9, vec!["echo", "ci"]),
+                (11, vec!["main", "\"$@\""]),
+            ]
+        );
+    }
+
+    fn guarded(source: &str) -> Vec<(usize, bool)> {
+        parse(source)
+            .commands
+            .iter()
+            .map(|c| (c.line, c.guarded))
+            .collect()
+    }
+
+    #[test]
+    fn recognises_guards() {
+        let source = r#"echo always
+if [[ "${BASH_SOURCE[0]}" == "$0" ]]; then
+    main "$@"
+else
+    echo sourced
+fi
+[[ "$0" = "${BASH_SOURCE[0]}" ]] && main "$@"
+[ "${BASH_SOURCE[0]}" != "$0" ] || { main "$@"; }
+if ! (return 0 2>/dev/null); then
+    main "$@"
+fi
+(return 0 2>/dev/null) || main "$@"
+if [[ -n "$CI" ]]; then
+    echo not a guard
+fi
+"#;
+        assert_eq!(
+            guarded(source),
+            vec![
+                (1, false),
+                (2, false),
+                (3, true),
+                (5, false),
+                (7, false),
+                (7, true),
+                (8, false),
+                (8, true),
+                (9, false),
+                (10, true),
+                (12, false),
+                (12, true),
+                (13, false),
+                (14, false),
+            ]
+        );
+    }
+
+    #[test]
+    fn everything_after_an_early_return_is_guarded() {
+        let source = r#"helper() { :; }
+[[ "${BASH_SOURCE[0]}" != "$0" ]] && return
+main "$@"
+"#;
+        assert_eq!(guarded(source), vec![(2, false), (2, false), (3, true)]);
+    }
+
+    #[test]
+    fn redirected_file_descriptors_are_not_words() {
+        let parsed = parse("sleep 0.1 2>/dev/null || sleep 1\necho 2 >&2\n");
+        let words: Vec<Vec<&str>> = parsed
+            .commands
+            .iter()
+            .map(|c| c.words.iter().map(String::as_str).collect())
+            .collect();
+        assert_eq!(
+            words,
+            vec![vec!["sleep", "0.1"], vec!["sleep", "1"], vec!["echo", "2"]]
+        );
+    }
+
+    #[test]
+    fn array_assignments_are_not_commands() {
+        let source = r#"SERVICES=(api web)
+arr=( "a" "b" )
+TARGETS=(
+    staging # not prod
+    "eu west"
+)
+TARGETS+=(prod)
+declare -a PORTS=(80 443)
+echo "${SERVICES[@]}"
+setup() {
+    local hosts=(
+        a
+    )
+}
+"#;
+        let parsed = parse(source);
+        let commands: Vec<(usize, Vec<&str>)> = parsed
+            .commands
+            .iter()
+            .map(|c| (c.line, c.words.iter().map(String::as_str).collect()))
+            .collect();
+        assert_eq!(
+            commands,
+            vec![
+                (8, vec!["declare", "-a", "PORTS=(80 443)"]),
+                (9, vec!["echo", "\"${SERVICES[@]}\""]),
+            ]
+        );
+        assert_eq!(functions(source), vec![f("setup", 10, 14)]);
+    }
+
+    /// Every script in `tests/corpus` has a matching `.expected` file, listing what we should find
+    /// in it, one per line: `function name start end` for each function, and `command line words`
+    /// or `guarded line words` for each top level command.
+    #[test]
+    fn corpus() {
+        let mut checked = 0;
+        for entry in std::fs::read_dir("tests/corpus").unwrap() {
+            let path = entry.unwrap().path();
+            if path.extension().and_then(|e| e.to_str()) != Some("sh") {
+                continue;
+            }
+            let parsed = parse(&std::fs::read_to_string(&path).unwrap());
+            let found: Vec<String> = parsed
+                .functions
+                .iter()
+                .map(|f| format!("function {} {} {}", f.name, f.start_line, f.end_line))
+                .chain(parsed.commands.iter().map(|c| {
+                    let kind = if c.guarded { "guarded" } else { "command" };
+                    format!("{kind} {} {}", c.line, c.words.join(" "))
+                }))
+                .collect();
+            let expected: Vec<String> = std::fs::read_to_string(path.with_extension("expected"))
+                .unwrap()
+                .lines()
+                .filter(|line| !line.trim().is_empty())
+                .map(String::from)
+                .collect();
+            assert_eq!(found, expected, "in {:?}", path);
+            checked += 1;
+        }
+        assert!(checked > 0);
+    }
+}
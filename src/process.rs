@@ -0,0 +1,284 @@
+/// Runs a child process in its own process group, so lk can time it out and pass signals on to it.
+use signal_hook::consts::{SIGHUP, SIGINT, SIGTERM};
+use signal_hook::iterator::Signals;
+use std::io::IsTerminal;
+use std::io::Result;
+use std::os::unix::process::{CommandExt, ExitStatusExt};
+use std::process::{Child, Command};
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+
+/// The exit code we use when a function times out. It's the same as coreutils' `timeout`.
+pub const TIMED_OUT: i32 = 124;
+
+/// How often we check on the child while waiting for it.
+const POLL_INTERVAL: Duration = Duration::from_millis(50);
+
+/// A child in its own process group, which gets the signals lk gets until it's dropped.
+pub struct Running {
+    pub child: Child,
+    _forwarding: Forwarding,
+}
+
+/// Spawns the command as the leader of a new process group. If it's going to be in the
+/// `foreground` and we're attached to a terminal, then the child's group becomes the terminal's
+/// foreground group, so it gets the user's Ctrl-C and can read from the terminal as if lk wasn't
+/// there. Functions running in parallel stay in the background and rely on lk to forward signals.
+pub fn spawn(command: &mut Command, foreground: bool) -> Result<Running> {
+    // Forward anything that would otherwise kill lk and orphan the child, starting before the
+    // child exists, so there's no gap between it starting and us knowing its group.
+    forward_signals()?;
+    let forwarding = Forwarding::spawning();
+    let interactive = foreground && std::io::stdin().is_terminal();
+    unsafe {
+        command.pre_exec(move || {
+            if libc::setpgid(0, 0) != 0 {
+                return Err(std::io::Error::last_os_error());
+            }
+            if interactive {
+                // We're not the foreground group yet, so we have to ignore SIGTTOU to take over.
+                libc::signal(libc::SIGTTOU, libc::SIG_IGN);
+                libc::tcsetpgrp(libc::STDIN_FILENO, libc::getpid());
+                libc::signal(libc::SIGTTOU, libc::SIG_DFL);
+            }
+            Ok(())
+        });
+    }
+    let child = command.spawn()?;
+    Ok(Running {
+        _forwarding: forwarding.to(child.id() as libc::pid_t),
+        child,
+    })
+}
+
+/// Waits for the child to finish and returns its exit code. Signals sent to lk are forwarded to
+/// the child's process group. If the child runs for longer than `timeout` it gets a SIGTERM, and
+/// then a SIGKILL if it's still around after `grace`, and we return `TIMED_OUT`.
+pub fn wait(running: &mut Running, timeout: Option<Duration>, grace: Duration) -> Result<i32> {
+    let child = &mut running.child;
+    let group = child.id() as libc::pid_t;
+    let started = Instant::now();
+    let mut terminated_at: Option<Instant> = None;
+    let status = loop {
+        if let Some(status) = child.try_wait()? {
+            break status;
+        }
+        match (timeout, terminated_at) {
+            (Some(timeout), None) if started.elapsed() >= timeout => {
+                log::warn!("Process group {group} timed out after {timeout:?}, sending SIGTERM");
+                unsafe { libc::kill(-group, libc::SIGTERM) };
+                terminated_at = Some(Instant::now());
+            }
+            (_, Some(terminated_at)) if terminated_at.elapsed() >= grace => {
+                log::warn!("Process group {group} ignored SIGTERM, sending SIGKILL");
+                unsafe { libc::kill(-group, libc::SIGKILL) };
+            }
+            _ => {}
+        }
+        std::thread::sleep(POLL_INTERVAL);
+    };
+
+    reclaim_terminal();
+
+    if terminated_at.is_some() {
+        return Ok(TIMED_OUT);
+    }
+    Ok(match (status.code(), status.signal()) {
+        (Some(code), _) => code,
+        // Shells report death by signal as 128 + the signal number, so we will too.
+        (None, Some(signal)) => 128 + signal,
+        (None, None) => 1,
+    })
+}
+
+/// The process groups of the functions that are running, which get the signals lk gets.
+static RUNNING: Mutex<Groups> = Mutex::new(Groups {
+    groups: Vec::new(),
+    spawning: 0,
+    pending: Vec::new(),
+});
+
+struct Groups {
+    groups: Vec<libc::pid_t>,
+    /// How many functions are being spawned. Their groups don't exist yet, so signals that arrive
+    /// meanwhile wait in `pending` until they do.
+    spawning: usize,
+    pending: Vec<libc::c_int>,
+}
+
+fn groups() -> std::sync::MutexGuard<'static, Groups> {
+    RUNNING.lock().unwrap_or_else(|e| e.into_inner())
+}
+
+/// Catches the signals that would kill lk, for the rest of the process, and passes them on to the
+/// functions that are running. With none running, a signal does what it would have done, so
+/// Ctrl-C still stops lk between the steps of an alias or at a confirmation prompt.
+fn forward_signals() -> Result<()> {
+    static STARTED: Mutex<bool> = Mutex::new(false);
+    let mut started = STARTED.lock().unwrap_or_else(|e| e.into_inner());
+    if *started {
+        return Ok(());
+    }
+    let mut signals = Signals::new([SIGINT, SIGTERM, SIGHUP])?;
+    std::thread::spawn(move || {
+        for signal in signals.forever() {
+            let mut running = groups();
+            if running.spawning > 0 {
+                running.pending.push(signal);
+            } else if running.groups.is_empty() {
+                let _ = signal_hook::low_level::emulate_default_handler(signal);
+            }
+            for group in &running.groups {
+                log::info!("Forwarding signal {signal} to process group {group}");
+                unsafe { libc::kill(-group, signal) };
+            }
+        }
+    });
+    *started = true;
+    Ok(())
+}
+
+/// A process group in `RUNNING`, or one that's about to be, until this is dropped.
+struct Forwarding(Option<libc::pid_t>);
+
+impl Forwarding {
+    fn spawning() -> Self {
+        groups().spawning += 1;
+        Self(None)
+    }
+
+    /// The child's been spawned, so it gets the signals that came while it was, and any after.
+    fn to(mut self, group: libc::pid_t) -> Self {
+        let mut running = groups();
+        running.spawning -= 1;
+        running.groups.push(group);
+        for signal in running.pending.clone() {
+            log::info!("Forwarding signal {signal} to process group {group}");
+            unsafe { libc::kill(-group, signal) };
+        }
+        if running.spawning == 0 {
+            running.pending.clear();
+        }
+        self.0 = Some(group);
+        self
+    }
+}
+
+impl Drop for Forwarding {
+    fn drop(&mut self) {
+        let mut running = groups();
+        match self.0 {
+            Some(group) => running.groups.retain(|g| *g != group),
+            None => {
+                // The spawn failed, so there's no one to pass the signals on to but lk.
+                running.spawning -= 1;
+                if running.spawning == 0 && running.groups.is_empty() {
+                    for signal in std::mem::take(&mut running.pending) {
+                        let _ = signal_hook::low_level::emulate_default_handler(signal);
+                    }
+                }
+            }
+        }
+    }
+}
+
+/// Makes lk's process group the terminal's foreground group again, once the child is done with it.
+fn reclaim_terminal() {
+    if std::io::stdin().is_terminal() {
+        unsafe {
+            let previous = libc::signal(libc::SIGTTOU, libc::SIG_IGN);
+            libc::tcsetpgrp(libc::STDIN_FILENO, libc::getpgrp());
+            libc::signal(libc::SIGTTOU, previous);
+        }
+    }
+}
+
+/// Tests that spawn processes hold this, so the signal we send in one can't reach another's child.
+#[cfg(test)]
+pub(crate) static SERIAL: std::sync::Mutex<()> = std::sync::Mutex::new(());
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn bash(script: &str) -> Command {
+        let mut command = Command::new("bash");
+        command.arg("-c").arg(script);
+        command
+    }
+
+    #[test]
+    fn returns_the_exit_code() {
+        let _serial = SERIAL.lock();
+        let mut running = spawn(&mut bash("exit 3"), true).unwrap();
+        assert_eq!(wait(&mut running, None, Duration::ZERO).unwrap(), 3);
+    }
+
+    #[test]
+    fn times_out() {
+        let _serial = SERIAL.lock();
+        let started = Instant::now();
+        let mut running = spawn(&mut bash("sleep 30"), true).unwrap();
+        let code = wait(
+            &mut running,
+            Some(Duration::from_millis(200)),
+            Duration::from_secs(5),
+        )
+        .unwrap();
+        assert_eq!(code, TIMED_OUT);
+        assert!(started.elapsed() < Duration::from_secs(5));
+    }
+
+    #[test]
+    fn kills_children_that_ignore_sigterm() {
+        let _serial = SERIAL.lock();
+        let started = Instant::now();
+        let mut running =
+            spawn(&mut bash("trap '' TERM; sleep 30 & wait; sleep 30"), true).unwrap();
+        let code = wait(
+            &mut running,
+            Some(Duration::from_millis(200)),
+            Duration::from_millis(200),
+        )
+        .unwrap();
+        assert_eq!(code, TIMED_OUT);
+        assert!(started.elapsed() < Duration::from_secs(5));
+    }
+
+    #[test]
+    fn signals_are_forwarded_to_the_child() {
+        let _serial = SERIAL.lock();
+        let mut running = spawn(&mut bash("sleep 30"), true).unwrap();
+        let pid = running.child.id() as libc::pid_t;
+        // Pretend someone is killing lk, once we've had a chance to start waiting.
+        std::thread::spawn(move || {
+            std::thread::sleep(Duration::from_millis(200));
+            unsafe { libc::kill(libc::getpid(), libc::SIGTERM) };
+        });
+        let code = wait(&mut running, None, Duration::ZERO).unwrap();
+        assert_eq!(code, 128 + libc::SIGTERM);
+        // The whole group should have gone.
+        assert_ne!(unsafe { libc::kill(-pid, 0) }, 0);
+    }
+
+    #[test]
+    fn signals_that_come_while_spawning_reach_the_child() {
+        let _serial = SERIAL.lock();
+        let mut command = bash("sleep 30");
+        // Hold the child up between fork and exec, where we don't know its group yet.
+        unsafe {
+            command.pre_exec(|| {
+                std::thread::sleep(Duration::from_millis(500));
+                Ok(())
+            });
+        }
+        std::thread::spawn(move || {
+            std::thread::sleep(Duration::from_millis(100));
+            unsafe { libc::kill(libc::getpid(), libc::SIGTERM) };
+        });
+        let mut running = spawn(&mut command, true).unwrap();
+        // Without the signal this would sleep for 30s.
+        let code = wait(&mut running, Some(Duration::from_secs(10)), Duration::ZERO).unwrap();
+        assert_eq!(code, 128 + libc::SIGTERM);
+    }
+}
@@ -0,0 +1,257 @@
+/// Where the things lk runs come from. A provider finds groups of them, like a bash script and its
+/// functions or a Makefile and its targets, as the `Script`s and `Function`s every mode lists and
+/// runs. A group knows what it's called and what it's for, and each function knows its name,
+/// description, params and how to run it, so a new provider doesn't need changes to the modes.
+/// Providers are registered by name in `Providers`, and which ones a project uses is `providers`
+/// in lk.toml.
+use crate::executables::{Executables, Failure};
+use crate::imports::Tool;
+use crate::script::Script;
+use crate::workspace::Workspace;
+use crate::{Error, Result};
+use std::path::PathBuf;
+
+/// What a provider has to go on.
+#[derive(Clone, Copy)]
+#[non_exhaustive]
+pub struct Context<'a> {
+    /// The files under the project root, with the includes and excludes applied.
+    pub executables: &'a Executables,
+    pub workspace: &'a Workspace,
+    /// The config files, the user's first.
+    pub config_files: &'a [PathBuf],
+    pub follow_sources: bool,
+    /// What the providers before this one found, e.g. for aliases to run. `Providers::discover`
+    /// fills this in.
+    pub found: &'a [Script],
+}
+
+impl<'a> Context<'a> {
+    pub fn new(
+        executables: &'a Executables,
+        workspace: &'a Workspace,
+        config_files: &'a [PathBuf],
+        follow_sources: bool,
+    ) -> Self {
+        Self {
+            executables,
+            workspace,
+            config_files,
+            follow_sources,
+            found: &[],
+        }
+    }
+}
+
+/// What providers found, and the files they couldn't use.
+#[derive(Default)]
+#[non_exhaustive]
+pub struct Found {
+    pub scripts: Vec<Script>,
+    pub failures: Vec<Failure>,
+}
+
+pub trait Provider {
+    /// What it's called in `providers` in lk.toml, e.g. `make`.
+    fn name(&self) -> &str;
+
+    /// Finds the groups of functions. A file the provider can't use goes in the failures, so lk
+    /// carries on without it. An error stops lk, so it's for mistakes the user needs to fix, like
+    /// a command in lk.toml that's wrong.
+    fn discover(&self, context: &Context) -> Result<Found>;
+}
+
+/// The providers lk knows about, by name.
+pub struct Providers {
+    providers: Vec<Box<dyn Provider>>,
+}
+
+impl Providers {
+    /// lk's own providers: bash, commands, make, npm and just.
+    pub fn builtin() -> Self {
+        let mut providers = Self {
+            providers: Vec::new(),
+        };
+        providers.register(Box::new(Bash));
+        providers.register(Box::new(crate::commands::Commands));
+        providers.register(Box::new(Tool::Make));
+        providers.register(Box::new(Tool::Npm));
+        providers.register(Box::new(Tool::Just));
+        providers
+    }
+
+    /// Adds a provider, in place of any with the same name.
+    pub fn register(&mut self, provider: Box<dyn Provider>) {
+        self.providers.retain(|p| p.name() != provider.name());
+        self.providers.push(provider);
+    }
+
+    pub fn names(&self) -> Vec<&str> {
+        self.providers.iter().map(|p| p.name()).collect()
+    }
+
+    /// Everything the named providers find, in the order they're named, each group marked with the
+    /// provider that found it. It's an error to name one we haven't got.
+    pub fn discover(&self, names: &[String], context: &Context) -> Result<Found> {
+        let mut all = Found::default();
+        for name in names {
+            let provider = self
+                .providers
+                .iter()
+                .find(|p| p.name() == name)
+                .ok_or_else(|| {
+                    Error::Config(format!(
+                        "providers in lk.toml is wrong: there's no provider called {name}, expected one of {}",
+                        self.names().join(", ")
+                    ))
+                })?;
+            let found = provider.discover(&Context {
+                found: &all.scripts,
+                ..*context
+            })?;
+            all.scripts
+                .extend(found.scripts.into_iter().map(|script| Script {
+                    provider: name.to_owned(),
+                    ..script
+                }));
+            all.failures.extend(found.failures);
+        }
+        Ok(all)
+    }
+}
+
+/// Functions in the bash scripts lk found. One bad script doesn't stop us finding the rest.
+pub struct Bash;
+
+impl Provider for Bash {
+    fn name(&self) -> &str {
+        "bash"
+    }
+
+    fn discover(&self, context: &Context) -> Result<Found> {
+        let mut found = Found::default();
+        for executable in &context.executables.executables {
+            match Script::new(executable, context.follow_sources) {
+                Ok(script) => found.scripts.push(script),
+                Err(err) => found
+                    .failures
+                    .push(Failure::new(&executable.path, err.report())),
+            }
+        }
+        Ok(found)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::executables::NonExecutable;
+    use std::fs::Permissions;
+    use std::os::unix::fs::PermissionsExt;
+
+    #[test]
+    fn each_provider_finds_its_own() {
+        let dir = tempfile::tempdir().unwrap();
+        let root = dir.path().canonicalize().unwrap();
+        let script = root.join("build.sh");
+        std::fs::write(&script, "#!/usr/bin/env bash\n\nall() {\n    make\n}\n").unwrap();
+        std::fs::set_permissions(&script, Permissions::from_mode(0o755)).unwrap();
+        std::fs::write(root.join("Makefile"), "test:\n\tcargo test\n").unwrap();
+        std::fs::write(root.join("lk.toml"), "[commands.hi]\nrun = \"echo hi\"\n").unwrap();
+        let executables =
+            Executables::new(&[format!("{}/*", root.display())], &[], NonExecutable::Skip).unwrap();
+        let workspace = Workspace {
+            root: root.clone(),
+            files: vec![root.join("lk.toml")],
+        };
+        let context = Context {
+            executables: &executables,
+            workspace: &workspace,
+            config_files: &workspace.files,
+            follow_sources: true,
+            found: &[],
+        };
+        let providers = Providers::builtin();
+        let names = |names: &[&str]| names.iter().map(|n| n.to_string()).collect::<Vec<_>>();
+
+        let found = providers
+            .discover(&names(&["bash", "make", "commands"]), &context)
+            .unwrap();
+
+        let groups: Vec<(&str, String)> = found
+            .scripts
+            .iter()
+            .map(|script| (script.provider.as_str(), script.file_name()))
+            .collect();
+        assert_eq!(
+            groups,
+            vec![
+                ("bash", "build.sh".to_string()),
+                ("make", "Makefile".to_string()),
+                ("commands", "commands".to_string()),
+            ]
+        );
+        assert!(found.failures.is_empty());
+        // Just the ones asked for.
+        let found = providers.discover(&names(&["just"]), &context).unwrap();
+        assert!(found.scripts.is_empty());
+        let error = providers
+            .discover(&names(&["bash", "rake"]), &context)
+            .err()
+            .unwrap();
+        assert_eq!(
+            error.to_string(),
+            "providers in lk.toml is wrong: there's no provider called rake, expected one of bash, commands, make, npm, just"
+        );
+    }
+
+    /// Finds a group named after the functions the providers before it found.
+    struct Count;
+
+    impl Provider for Count {
+        fn name(&self) -> &str {
+            "count"
+        }
+
+        fn discover(&self, context: &Context) -> Result<Found> {
+            let count: usize = context.found.iter().map(|s| s.functions.len()).sum();
+            Ok(Found {
+                scripts: vec![Script {
+                    path: PathBuf::from(format!("{count} functions")),
+                    ..Script::default()
+                }],
+                failures: Vec::new(),
+            })
+        }
+    }
+
+    #[test]
+    fn registered_providers_see_what_came_before() {
+        let dir = tempfile::tempdir().unwrap();
+        let root = dir.path().canonicalize().unwrap();
+        std::fs::write(root.join("Makefile"), "build:\n\tcc\ntest:\n\t./test\n").unwrap();
+        let executables =
+            Executables::new(&[format!("{}/*", root.display())], &[], NonExecutable::Skip).unwrap();
+        let workspace = Workspace {
+            root: root.clone(),
+            files: Vec::new(),
+        };
+        let context = Context {
+            executables: &executables,
+            workspace: &workspace,
+            config_files: &[],
+            follow_sources: false,
+            found: &[],
+        };
+        let mut providers = Providers::builtin();
+        providers.register(Box::new(Count));
+
+        let found = providers
+            .discover(&["make".to_string(), "count".to_string()], &context)
+            .unwrap();
+
+        assert_eq!(found.scripts[1].file_name(), "2 functions");
+        assert_eq!(found.scripts[1].provider, "count");
+        assert!(providers.names().contains(&"count"));
+    }
+}
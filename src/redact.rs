@@ -0,0 +1,204 @@
+/// Masks secrets before lk echoes them to the terminal, the log file or any history.
+use crate::script::Function;
+use crate::{Error, Result};
+use regex::Regex;
+
+/// What we show instead of a secret.
+pub const MASK: &str = "*****";
+
+#[derive(Debug, Clone, Default)]
+pub struct Redactor {
+    patterns: Vec<Regex>,
+}
+
+impl Redactor {
+    /// Builds a redactor from the `redact_patterns` in the config. Any text matching one of
+    /// these regexes will be masked, regardless of which function it was passed to.
+    pub fn new(patterns: &[String]) -> Result<Self> {
+        let patterns = patterns
+            .iter()
+            .map(|pattern| {
+                Regex::new(pattern).map_err(|source| Error::BadRedactPattern {
+                    pattern: pattern.to_string(),
+                    source,
+                })
+            })
+            .collect::<Result<Vec<Regex>>>()?;
+        Ok(Self { patterns })
+    }
+
+    /// Masks anything in `text` that matches one of the configured patterns.
+    pub fn redact(&self, text: &str) -> String {
+        self.patterns
+            .iter()
+            .fold(text.to_string(), |text, pattern| {
+                pattern.replace_all(&text, MASK).to_string()
+            })
+    }
+
+    /// Masks the params passed to a function. A param is secret if its position (1-based) or its
+    /// name is listed in one of the function's `# @secret` annotations. Named params are
+    /// recognised as `name=value`, `--name=value` or `--name value`. A bare `# @secret` makes
+    /// every param secret. Whatever is left is then checked against the configured patterns.
+    pub fn redact_params(&self, function: &Function, params: &[String]) -> Vec<String> {
+        classify(function, params)
+            .into_iter()
+            .map(|param| match param {
+                Param::Secret(_) => MASK.to_string(),
+                Param::SecretValue { name, .. } => format!("{name}={MASK}"),
+                Param::Plain(param) => self.redact(param),
+            })
+            .collect()
+    }
+
+    /// A redactor for one run of the function, which masks the values of its secret params as
+    /// well as the patterns, so they're masked when the function echoes them too.
+    pub fn for_run(&self, function: &Function, params: &[String]) -> Redactor {
+        let mut secrets: Vec<&str> = classify(function, params)
+            .into_iter()
+            .filter_map(|param| match param {
+                Param::Secret(value) | Param::SecretValue { value, .. } => Some(value),
+                Param::Plain(_) => None,
+            })
+            .filter(|value| !value.is_empty())
+            .collect();
+        // The longest first, so a secret that contains another is masked whole.
+        secrets.sort_by_key(|value| std::cmp::Reverse(value.len()));
+        let mut patterns: Vec<Regex> = secrets
+            .into_iter()
+            .filter_map(|value| Regex::new(&regex::escape(value)).ok())
+            .collect();
+        patterns.extend(self.patterns.iter().cloned());
+        Redactor { patterns }
+    }
+}
+
+/// A param, and which part of it is secret.
+enum Param<'a> {
+    Secret(&'a str),
+    /// e.g. `--token=abc`, where the name is `--token`.
+    SecretValue {
+        name: &'a str,
+        value: &'a str,
+    },
+    Plain(&'a str),
+}
+
+fn classify<'a>(function: &Function, params: &'a [String]) -> Vec<Param<'a>> {
+    let all_secret = function.secrets().iter().any(|secret| secret == "*");
+    let is_secret = |name: &str| function.secrets().iter().any(|secret| secret == name);
+    let mut classified = Vec::with_capacity(params.len());
+    let mut next_is_secret = false;
+    for (i, param) in params.iter().enumerate() {
+        if all_secret || next_is_secret || is_secret(&(i + 1).to_string()) {
+            classified.push(Param::Secret(param));
+            next_is_secret = false;
+            continue;
+        }
+        let flag = param.trim_start_matches('-');
+        match flag.split_once('=') {
+            Some((name, value)) if is_secret(name) => {
+                let name = &param[..param.len() - value.len() - 1];
+                classified.push(Param::SecretValue { name, value });
+            }
+            None if param.starts_with("--") && is_secret(flag) => {
+                classified.push(Param::Plain(param));
+                next_is_secret = true;
+            }
+            _ => classified.push(Param::Plain(param)),
+        }
+    }
+    classified
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::script::{BashFunction, Run};
+
+    fn function_with_secrets(secrets: &[&str]) -> Function {
+        Function {
+            name: "deploy".to_string(),
+            run: Run::Bash(BashFunction {
+                secrets: secrets.iter().map(|s| s.to_string()).collect(),
+                ..Default::default()
+            }),
+            ..Default::default()
+        }
+    }
+
+    fn params(params: &[&str]) -> Vec<String> {
+        params.iter().map(|s| s.to_string()).collect()
+    }
+
+    #[test]
+    fn redacts_by_position() {
+        let redactor = Redactor::default();
+        let function = function_with_secrets(&["2"]);
+        let redacted = redactor.redact_params(&function, &params(&["prod", "hunter2"]));
+        assert_eq!(redacted, vec!["prod", MASK]);
+    }
+
+    #[test]
+    fn redacts_by_name() {
+        let redactor = Redactor::default();
+        let function = function_with_secrets(&["token"]);
+        let redacted = redactor.redact_params(
+            &function,
+            &params(&["token=abc", "--token=def", "--token", "ghi", "other=jkl"]),
+        );
+        assert_eq!(
+            redacted,
+            vec![
+                format!("token={MASK}"),
+                format!("--token={MASK}"),
+                "--token".to_string(),
+                MASK.to_string(),
+                "other=jkl".to_string()
+            ]
+        );
+    }
+
+    #[test]
+    fn redacts_everything_with_wildcard() {
+        let redactor = Redactor::default();
+        let function = function_with_secrets(&["*"]);
+        let redacted = redactor.redact_params(&function, &params(&["a", "b"]));
+        assert_eq!(redacted, vec![MASK, MASK]);
+    }
+
+    #[test]
+    fn redacts_by_pattern() {
+        let redactor = Redactor::new(&["ghp_[A-Za-z0-9]+".to_string()]).unwrap();
+        let function = function_with_secrets(&[]);
+        let redacted = redactor.redact_params(&function, &params(&["x", "key=ghp_abc123"]));
+        assert_eq!(redacted, vec!["x".to_string(), format!("key={MASK}")]);
+        assert_eq!(
+            redactor.redact("lk deploy.sh run ghp_abc123"),
+            format!("lk deploy.sh run {MASK}")
+        );
+    }
+
+    #[test]
+    fn masks_secret_values_for_a_run() {
+        let redactor = Redactor::new(&["ghp_[A-Za-z0-9]+".to_string()]).unwrap();
+        let function = function_with_secrets(&["token", "3"]);
+
+        let for_run = redactor.for_run(
+            &function,
+            &params(&["prod", "--token=hunter2", "s3cr3t", "ghp_abc123"]),
+        );
+
+        assert_eq!(
+            for_run.redact("got --token=hunter2, s3cr3t and ghp_abc123 for prod"),
+            format!("got --token={MASK}, {MASK} and {MASK} for prod")
+        );
+        // The redactor we started with is unchanged.
+        assert_eq!(redactor.redact("hunter2"), "hunter2");
+    }
+
+    #[test]
+    fn rejects_bad_patterns() {
+        assert!(Redactor::new(&["(".to_string()]).is_err());
+    }
+}
@@ -0,0 +1,531 @@
+/// Everything that happens around running a function: history, redaction and the bash file itself.
+use crate::bash_file::{BashFile, ExecuteOptions};
+use crate::confirm::confirm;
+use crate::history::{RunHistory, RunRecord};
+use crate::parallel;
+use crate::redact::Redactor;
+use crate::script::{Function, Run, Script, SymlinkCd};
+use crate::shells::UserShell;
+use crate::{Error, Result};
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+
+/// Asks whether to run a function that needs confirming, and says whether to go ahead.
+pub type Ask = Box<dyn Fn(&Function) -> Result<bool> + Send + Sync>;
+
+#[non_exhaustive]
+pub struct Runner {
+    pub redactor: Redactor,
+    pub history: RunHistory,
+    /// Skip confirmation prompts, e.g. when lk is used in automation.
+    pub yes: bool,
+    /// This asks on the terminal unless it's replaced, e.g. by a tool with its own UI.
+    pub confirm: Ask,
+    /// If this is set then the output of every run is captured in a log file in this directory.
+    pub log_dir: Option<PathBuf>,
+    /// Overrides any `# @timeout` annotations.
+    pub timeout: Option<Duration>,
+    /// How long a timed-out function has to clean up before it's killed.
+    pub grace: Duration,
+    /// Refuse to run functions from scripts with unguarded side effects. This is the
+    /// `refuse_side_effects` config, unless lk's run with `--force`.
+    pub refuse_side_effects: bool,
+    /// Where to run functions from when we found their script through a symlink.
+    pub symlink_cd: SymlinkCd,
+    /// The shell whose history gets the lk command for functions the user picked, so they can
+    /// run it again. None leaves the shell history alone.
+    pub shell: Option<UserShell>,
+}
+
+/// A function to run, and what to pass it.
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[non_exhaustive]
+pub struct Job {
+    pub script: Script,
+    pub function: Function,
+    pub params: Vec<String>,
+}
+
+impl Job {
+    pub fn new(script: Script, function: Function, params: Vec<String>) -> Self {
+        Self {
+            script,
+            function,
+            params,
+        }
+    }
+
+    /// How we refer to the job when it's running with others, e.g. `deploy.sh:release`.
+    pub fn name(&self) -> String {
+        format!("{}:{}", self.script.file_name(), self.function.name)
+    }
+}
+
+impl Runner {
+    /// A runner with lk's defaults, which keeps its history in `state_dir`. It doesn't redact
+    /// anything, capture output, time functions out or touch the shell's history, and it asks
+    /// before running anything that needs confirming.
+    ///
+    /// ```
+    /// use lk::Runner;
+    /// use std::time::Duration;
+    ///
+    /// # fn main() -> lk::Result<()> {
+    /// let state = tempfile::tempdir()?;
+    /// let mut runner = Runner::new(state.path());
+    /// runner.yes = true;
+    /// runner.timeout = Some(Duration::from_secs(60));
+    /// assert!(runner.history.records()?.is_empty());
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn new(state_dir: &Path) -> Self {
+        Self {
+            redactor: Redactor::default(),
+            history: RunHistory::new(state_dir),
+            yes: false,
+            confirm: Box::new(|function| {
+                confirm(function, std::io::stdin().lock(), std::io::stdout())
+            }),
+            log_dir: None,
+            timeout: None,
+            grace: Duration::from_secs(5),
+            refuse_side_effects: false,
+            symlink_cd: SymlinkCd::default(),
+            shell: None,
+        }
+    }
+
+    /// Runs the function and returns its exit code. If `add_to_shell_history` is set we'll also
+    /// write the equivalent lk command to the shell's history file, so the user can easily re-run it.
+    pub fn run(
+        &self,
+        script: &Script,
+        function: &Function,
+        params: Vec<String>,
+        add_to_shell_history: bool,
+    ) -> Result<i32> {
+        if let Run::Steps(steps) = &function.run {
+            return self.run_steps(function, steps, params, add_to_shell_history);
+        }
+        self.check_side_effects(script, function)?;
+        self.confirmed(function)?;
+
+        if add_to_shell_history {
+            let redacted_params = self.redactor.redact_params(function, &params);
+            let lk_command = format!(
+                "lk {} {} {}",
+                script.file_name(),
+                function.name,
+                redacted_params.join(" ")
+            );
+            self.add_to_history(lk_command.trim_end().to_string())?;
+        }
+
+        let log_file = self.record(script, function, &params)?;
+
+        // Finally we execute the function using a temporary bash file.
+        BashFile::run(
+            script.to_owned(),
+            function.to_owned(),
+            params,
+            &self.options(log_file.as_deref(), None),
+        )
+    }
+
+    /// Runs the steps of an alias one after the other, stopping at the first one that fails.
+    /// Returns the exit code of the one that failed, or 0. The params go to the last step, and the
+    /// history gets `lk <alias>`.
+    fn run_steps(
+        &self,
+        alias: &Function,
+        steps: &[std::result::Result<Job, String>],
+        params: Vec<String>,
+        add_to_shell_history: bool,
+    ) -> Result<i32> {
+        let mut steps = resolve(alias, steps)?;
+        let Some(last) = steps.last_mut() else {
+            return Ok(0);
+        };
+        let given = last.params.len();
+        last.params.extend(params);
+        if add_to_shell_history {
+            // The params are the last step's, so they're secret if its are.
+            let redacted = self
+                .redactor
+                .redact_params(&last.function, &last.params)
+                .split_off(given);
+            let lk_command = format!("lk {} {}", alias.name, redacted.join(" "));
+            self.add_to_history(lk_command.trim_end().to_string())?;
+        }
+        for step in &steps {
+            let code = self.run(&step.script, &step.function, step.params.clone(), false)?;
+            if code != 0 {
+                return Ok(code);
+            }
+        }
+        Ok(0)
+    }
+
+    /// Runs the jobs at the same time, at most `concurrency` at once, with their output prefixed
+    /// with their names. Returns the exit code of the first job that failed, or 0.
+    pub fn run_parallel(&self, jobs: Vec<Job>, concurrency: usize) -> Result<i32> {
+        // An alias with one step runs that. One with several runs them in order, which it can't do
+        // alongside other functions.
+        let jobs = jobs
+            .into_iter()
+            .map(|job| {
+                let Run::Steps(steps) = &job.function.run else {
+                    return Ok(job);
+                };
+                let mut steps = resolve(&job.function, steps)?;
+                if steps.len() != 1 {
+                    return Err(Error::NotParallel {
+                        function: job.function.name.clone(),
+                        steps: steps.len(),
+                    });
+                }
+                let mut step = steps.remove(0);
+                step.params.extend(job.params);
+                Ok(step)
+            })
+            .collect::<Result<Vec<Job>>>()?;
+        // Get all the checks and confirmations out of the way before anything starts.
+        for job in &jobs {
+            self.check_side_effects(&job.script, &job.function)?;
+            self.confirmed(&job.function)?;
+        }
+        let log_files = jobs
+            .iter()
+            .map(|job| self.record(&job.script, &job.function, &job.params))
+            .collect::<Result<Vec<_>>>()?;
+        let names: Vec<String> = jobs.iter().map(Job::name).collect();
+
+        println!(
+            "lk: running {} functions, {} at a time",
+            jobs.len(),
+            concurrency.min(jobs.len())
+        );
+        let outcomes = parallel::run(&names, concurrency, |i, prefix| {
+            let job = &jobs[i];
+            BashFile::run(
+                job.script.to_owned(),
+                job.function.to_owned(),
+                job.params.to_owned(),
+                &self.options(log_files[i].as_deref(), Some(prefix)),
+            )
+        });
+        parallel::print_summary(&outcomes);
+        Ok(parallel::exit_code(&outcomes))
+    }
+
+    /// Sourcing a script to run one of its functions runs all its top level commands too. We tell
+    /// the user about them, and refuse to go on if we've been told to.
+    fn check_side_effects(&self, script: &Script, function: &Function) -> Result<()> {
+        if function.side_effects().is_empty() {
+            return Ok(());
+        }
+        let side_effects = function
+            .side_effects()
+            .iter()
+            .map(|command| format!("`{}` (line {})", command.words.join(" "), command.line))
+            .collect::<Vec<_>>()
+            .join(", ");
+        if self.refuse_side_effects {
+            return Err(Error::SideEffects {
+                function: function.name.clone(),
+                script: script.path(),
+                side_effects,
+            });
+        }
+        log::warn!(
+            "{} runs commands whenever it's sourced: {}",
+            script.path(),
+            side_effects
+        );
+        Ok(())
+    }
+
+    fn confirmed(&self, function: &Function) -> Result<()> {
+        if self.yes || function.confirm.is_none() {
+            return Ok(());
+        }
+        if !(self.confirm)(function)? {
+            return Err(Error::NotConfirmed {
+                function: function.name.clone(),
+            });
+        }
+        Ok(())
+    }
+
+    /// Records the run in the history, and returns the log file to capture its output in, if
+    /// we're capturing output.
+    fn record(
+        &self,
+        script: &Script,
+        function: &Function,
+        params: &[String],
+    ) -> Result<Option<PathBuf>> {
+        let now = chrono::Local::now();
+        let log_file = match &self.log_dir {
+            Some(log_dir) => {
+                std::fs::create_dir_all(log_dir)?;
+                let name = format!(
+                    "{}_{}_{}",
+                    now.format("%Y%m%d-%H%M%S"),
+                    script.file_name(),
+                    function.name
+                );
+                Some(reserve_log_file(log_dir, &name)?)
+            }
+            None => None,
+        };
+
+        let record = RunRecord {
+            time: now.to_rfc3339(),
+            script: script.path(),
+            function: function.name.clone(),
+            params: self.redactor.redact_params(function, params),
+            log: log_file
+                .as_ref()
+                .map(|log_file| log_file.to_string_lossy().to_string()),
+        };
+        if let Err(err) = self.history.record(&record) {
+            log::error!("Unable to record this run in the history: {err}");
+        }
+        log::info!(
+            "Running {} {} with params {:?}",
+            record.script,
+            record.function,
+            record.params
+        );
+        Ok(log_file)
+    }
+
+    /// Adds the command to the user's shell history, so they can easily run it again.
+    fn add_to_history(&self, lk_command: String) -> Result<()> {
+        match &self.shell {
+            Some(shell) => shell.add_command(lk_command)?,
+            None => {
+                log::warn!("Unable to write to history file because we couldn't figure out what shell you're using");
+            }
+        }
+        Ok(())
+    }
+
+    fn options<'a>(
+        &'a self,
+        log_file: Option<&'a Path>,
+        prefix: Option<String>,
+    ) -> ExecuteOptions<'a> {
+        ExecuteOptions {
+            redactor: &self.redactor,
+            log_file,
+            timeout: self.timeout,
+            grace: self.grace,
+            prefix,
+            symlink_cd: self.symlink_cd,
+        }
+    }
+}
+
+/// The jobs an alias's steps run, or what's wrong with the first one lk couldn't find.
+fn resolve(alias: &Function, steps: &[std::result::Result<Job, String>]) -> Result<Vec<Job>> {
+    steps
+        .iter()
+        .map(|step| {
+            step.clone().map_err(|problem| Error::BadStep {
+                alias: alias.name.clone(),
+                problem,
+            })
+        })
+        .collect()
+}
+
+/// Creates `<name>.log` in the log dir, or `<name>-2.log` and so on if another run that started
+/// in the same second already has it, so no two runs ever share a log.
+fn reserve_log_file(log_dir: &Path, name: &str) -> Result<PathBuf> {
+    let mut attempt = 1;
+    loop {
+        let log_file = match attempt {
+            1 => log_dir.join(format!("{name}.log")),
+            n => log_dir.join(format!("{name}-{n}.log")),
+        };
+        match std::fs::OpenOptions::new()
+            .write(true)
+            .create_new(true)
+            .open(&log_file)
+        {
+            Ok(_) => return Ok(log_file),
+            Err(err) if err.kind() == std::io::ErrorKind::AlreadyExists => attempt += 1,
+            Err(err) => return Err(err.into()),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::executables::Executable;
+    use crate::process;
+    use crate::redact::MASK;
+
+    /// A runner with its history, captured output and shell history in a tempdir, and a script
+    /// whose function takes a secret and echoes it.
+    fn runner(dir: &Path) -> (Runner, Script) {
+        let path = dir.join("deploy.sh");
+        std::fs::write(
+            &path,
+            "#!/usr/bin/env bash\n\n# @secret token\ndeploy() {\n    echo \"deploying $1 with $2\"\n}\n",
+        )
+        .unwrap();
+        let executable = Executable {
+            short_name: "deploy.sh".to_string(),
+            absolute_path: path.clone(),
+            path,
+            non_executable: false,
+            via_symlink: false,
+        };
+        let shell_history = dir.join(".bash_history");
+        std::fs::write(&shell_history, "").unwrap();
+        let runner = Runner {
+            log_dir: Some(dir.join("logs")),
+            shell: Some(UserShell::with_history_file(
+                shell_history.to_string_lossy().to_string(),
+            )),
+            ..Runner::new(dir)
+        };
+        (runner, Script::new(&executable, false).unwrap())
+    }
+
+    /// Everything lk wrote: the history, the shell history and the captured output.
+    fn on_disk(dir: &Path) -> String {
+        let mut written = std::fs::read_to_string(dir.join("history.jsonl")).unwrap();
+        written.push_str(&std::fs::read_to_string(dir.join(".bash_history")).unwrap());
+        for log in std::fs::read_dir(dir.join("logs")).unwrap() {
+            written.push_str(&std::fs::read_to_string(log.unwrap().path()).unwrap());
+        }
+        written
+    }
+
+    #[test]
+    fn secrets_never_reach_the_disk() {
+        let _serial = process::SERIAL.lock();
+        let dir = tempfile::tempdir().unwrap();
+        let (runner, script) = runner(dir.path());
+        let deploy = script.get("deploy").unwrap();
+
+        let code = runner
+            .run(
+                &script,
+                deploy,
+                vec!["prod".to_string(), "token=hunter2".to_string()],
+                true,
+            )
+            .unwrap();
+
+        assert_eq!(code, 0);
+        let shell_history = std::fs::read_to_string(dir.path().join(".bash_history")).unwrap();
+        assert_eq!(
+            shell_history,
+            format!("lk deploy.sh deploy prod token={MASK}\n")
+        );
+        let records = runner.history.records().unwrap();
+        assert_eq!(
+            records[0].params,
+            vec!["prod".to_string(), format!("token={MASK}")]
+        );
+        let written = on_disk(dir.path());
+        assert!(written.contains(&format!("deploying prod with token={MASK}")));
+        assert!(!written.contains("hunter2"), "{written}");
+    }
+
+    #[test]
+    fn alias_steps_add_the_alias_to_the_shell_history() {
+        let _serial = process::SERIAL.lock();
+        let dir = tempfile::tempdir().unwrap();
+        let (runner, script) = runner(dir.path());
+        let step = Job {
+            function: script.get("deploy").unwrap().clone(),
+            script: script.clone(),
+            params: vec!["prod".to_string()],
+        };
+        let ship = Function {
+            name: "ship".to_string(),
+            run: Run::Steps(vec![Ok(step)]),
+            ..Function::default()
+        };
+
+        let code = runner
+            .run(&script, &ship, vec!["token=hunter2".to_string()], true)
+            .unwrap();
+
+        assert_eq!(code, 0);
+        let shell_history = std::fs::read_to_string(dir.path().join(".bash_history")).unwrap();
+        assert_eq!(shell_history, format!("lk ship token={MASK}\n"));
+        assert!(!on_disk(dir.path()).contains("hunter2"));
+
+        let broken = Function {
+            run: Run::Steps(vec![Err("Didn't find a script with name a.sh".to_string())]),
+            ..ship
+        };
+        let error = runner.run(&script, &broken, Vec::new(), true).unwrap_err();
+        assert_eq!(
+            error.to_string(),
+            "Alias ship: Didn't find a script with name a.sh"
+        );
+    }
+
+    #[test]
+    fn parallel_runs_of_a_function_get_their_own_logs() {
+        let _serial = process::SERIAL.lock();
+        let dir = tempfile::tempdir().unwrap();
+        let (runner, script) = runner(dir.path());
+        let runner = Runner {
+            yes: true,
+            ..runner
+        };
+        let job = |target: &str| Job {
+            function: script.get("deploy").unwrap().clone(),
+            script: script.clone(),
+            params: vec![target.to_string()],
+        };
+
+        let code = runner
+            .run_parallel(vec![job("staging"), job("prod")], 2)
+            .unwrap();
+
+        assert_eq!(code, 0);
+        let records = runner.history.records().unwrap();
+        assert_eq!(records.len(), 2);
+        assert_ne!(records[0].log, records[1].log);
+        for record in records {
+            let log = std::fs::read_to_string(record.log.unwrap()).unwrap();
+            assert!(
+                log.contains(&format!("deploying {} with", record.params[0])),
+                "{log}"
+            );
+        }
+    }
+
+    #[test]
+    fn asks_the_confirm_hook() {
+        let _serial = process::SERIAL.lock();
+        let dir = tempfile::tempdir().unwrap();
+        let (_, script) = runner(dir.path());
+        let mut deploy = script.get("deploy").unwrap().clone();
+        deploy.confirm = Some(crate::Confirm::TypeName(None));
+        let runner = |answer: &'static str| Runner {
+            confirm: Box::new(move |function| confirm(function, answer.as_bytes(), Vec::new())),
+            ..Runner::new(dir.path())
+        };
+
+        let refused = runner("no\n").run(&script, &deploy, Vec::new(), false);
+        let code = runner("deploy\n")
+            .run(&script, &deploy, Vec::new(), false)
+            .unwrap();
+
+        assert!(matches!(refused, Err(Error::NotConfirmed { function }) if function == "deploy"));
+        assert_eq!(code, 0);
+    }
+}
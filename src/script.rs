@@ -1,105 +1,279 @@
 /// Parses a script file and extracts comments and functions.
 use crate::executables::Executable;
+use crate::parser;
+use crate::runner::Job;
 use crate::ui::{print_no_functions_in_script_help, print_script_header};
-use anyhow::Result;
+use crate::{Error, Result};
 use pad::{Alignment, PadStr};
-use pastel_colours::{GREEN_FG, RESET_FG};
-use regex::bytes::Regex;
-use std::io::BufRead;
-use std::{fs::File, path::Path};
+use pastel_colours::{GREEN_FG, GREY_FG, RESET_FG};
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, HashSet};
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+use termion::style::{Faint, NoFaint};
+
+/// A command outside of any function, so it runs whenever the script is sourced.
+pub use crate::parser::Command as SideEffect;
 
 /// Everything we need to know about a function in a script
-#[derive(PartialEq, Eq, Debug, Clone)]
+#[derive(PartialEq, Eq, Debug, Clone, Default)]
+#[non_exhaustive]
 pub struct Function {
     pub name: String,
     pub comment: Vec<String>,
+    /// Whether the user has to confirm before we run this function, from `# @confirm` annotations
+    /// or the `confirm` globs in the config.
+    pub confirm: Option<Confirm>,
+    /// How long the function may run for, from a `# @timeout 10m` annotation.
+    pub timeout: Option<Duration>,
+    /// Where the function is in its script. Lines start at 1.
+    pub start_line: usize,
+    pub end_line: usize,
+    /// The library this function comes from, as the script sources it, if the script doesn't
+    /// define it itself.
+    pub source: Option<String>,
+    /// From `# @tags infra, db`. A function also has its script's tags.
+    pub tags: Vec<String>,
+    /// From `# @owner team-platform`. If it's not set then the script's owner owns the function.
+    pub owner: Option<String>,
+    /// Hidden functions are left out of the lists, unless lk's run with `--show-hidden`. They're
+    /// hidden by a `# @private` or `# @hidden` annotation, or by the hide rules in the config.
+    pub hidden: bool,
+    /// The params it takes, if where it's from says, e.g. a just recipe's `target="debug"`.
+    pub params: Vec<String>,
+    /// How lk runs it.
+    pub run: Run,
+}
+
+impl Function {
+    /// The params whose values are secret. Only bash functions have any.
+    pub fn secrets(&self) -> &[String] {
+        match &self.run {
+            Run::Bash(bash) => &bash.secrets,
+            _ => &[],
+        }
+    }
+
+    /// What runs before the function does, because we source its script to run it.
+    pub fn side_effects(&self) -> &[SideEffect] {
+        match &self.run {
+            Run::Bash(bash) => &bash.side_effects,
+            _ => &[],
+        }
+    }
+}
+
+/// How lk runs a function, which depends on the provider that found it.
+#[derive(PartialEq, Eq, Debug, Clone)]
+#[non_exhaustive]
+pub enum Run {
+    /// A function in a bash script. We source the script and call the function.
+    Bash(BashFunction),
+    /// Bash to run as it is, e.g. a command from lk.toml or a make target.
+    Inline(Inline),
+    /// Other functions, one after the other, e.g. for an alias. A step lk couldn't find says why.
+    Steps(Vec<std::result::Result<Job, String>>),
+}
+
+impl Default for Run {
+    fn default() -> Self {
+        Run::Bash(BashFunction::default())
+    }
+}
+
+/// What only a function in a bash script has.
+#[derive(PartialEq, Eq, Debug, Clone, Default)]
+#[non_exhaustive]
+pub struct BashFunction {
+    /// Parameters named by `# @secret` annotations. Their values are masked wherever lk echoes them.
+    pub secrets: Vec<String>,
+    /// Commands outside of functions in its script that do more than set things up. We source the
+    /// whole script to run the function, so these run every time.
+    pub side_effects: Vec<SideEffect>,
 }
 
+/// What a command from lk.toml, or an imported target, runs, and how.
 #[derive(PartialEq, Eq, Debug, Clone)]
+#[non_exhaustive]
+pub struct Inline {
+    /// The bash to run. Params are in `$@`, like a function's.
+    pub run: String,
+    /// Where to run it from.
+    pub dir: PathBuf,
+    /// Environment variables to set first.
+    pub env: BTreeMap<String, String>,
+}
+
+/// How a user confirms they really want to run a dangerous function, with an optional warning.
+#[derive(PartialEq, Eq, Debug, Clone)]
+pub enum Confirm {
+    /// `# @confirm "message"` asks a y/N question.
+    YesNo(Option<String>),
+    /// `# @confirm-name "message"` makes the user type the function's name.
+    TypeName(Option<String>),
+}
+
+#[derive(PartialEq, Eq, Debug, Clone, Default)]
+#[non_exhaustive]
 pub struct Script {
     pub path: std::path::PathBuf,
     pub absolute_path: std::path::PathBuf,
     pub comment: Vec<String>,
+    /// From `# @description ...` in the header.
+    pub description: Option<String>,
+    /// From `# @tags infra, db` in the header.
+    pub tags: Vec<String>,
+    /// From `# @owner team-platform` in the header.
+    pub owner: Option<String>,
     pub functions: Vec<Function>,
+    /// We found the file through a symlink, so it's really at `absolute_path`.
+    pub via_symlink: bool,
+    /// Shown next to its name, e.g. that a script isn't executable.
+    pub notes: Vec<String>,
+    /// Its functions can be run by their name alone, e.g. `lk build` for a command from lk.toml.
+    pub by_name: bool,
+    /// The name of the provider that found it, e.g. bash or make.
+    pub provider: String,
+}
+
+/// Where to run a function from when we found its script through a symlink. This is
+/// `symlink_cd` in lk.toml.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Deserialize, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum SymlinkCd {
+    /// The directory the script is really in, so it finds the files next to it. The default.
+    #[default]
+    Target,
+    /// The directory the link is in, e.g. for shared tooling that works on whatever project
+    /// links to it.
+    Link,
 }
 
 impl Script {
-    pub fn new(executable: &Executable) -> Result<Self> {
-        let lines = match read_lines(&executable.path) {
-            Ok(lines) => lines,
+    /// Reads the script's comments and functions. If `follow_sources` is set then we also pick up
+    /// the functions from any libraries it sources, because they can be run through the script too.
+    pub fn new(executable: &Executable, follow_sources: bool) -> Result<Self> {
+        let source = match std::fs::read(&executable.path) {
+            Ok(bytes) => String::from_utf8_lossy(&bytes).to_string(),
             Err(err) => {
                 log::error!(
                     "Unable to read executable: {}. Error was: {err}",
                     &executable.path.to_string_lossy()
                 );
-                anyhow::bail!("Permissiond denied in opening script");
+                return Err(Error::Read {
+                    path: executable.path.to_owned(),
+                    source: err,
+                });
             }
         };
+        let parsed = parser::parse(&source);
+        let (header_lines, included_comments) = get_header(&parsed);
+        let mut included_functions = get_functions(&parsed, &header_lines, None);
 
-        // `comments` accumulates comments until we find a function header line, and then they're cleared.
-        let mut comments: Vec<String> = Vec::new();
-        let mut included_comments: Vec<String> = Vec::new();
-        let mut included_functions: Vec<Function> = Vec::new();
-        let mut in_header_comments: bool = false;
-        for line in lines.map_while(Result::ok) {
-            // Find lines that are part of the same comment block
-            if line.starts_with('#') {
-                // Are we dealing with a hashbang line? If so, then we expect
-                // the next line(s) until an empty line to be script comments.
-                if line.contains("#!/") {
-                    in_header_comments = true;
-                } else if in_header_comments {
-                    let comment = clean_comment_line(&line);
-                    if included_comments.is_empty() && comment.is_empty() {
-                        // If we don't yet have any comments, and this comment has 0 length
-                        // then we're probably dealing with a spacing line between the hashbang
-                        // and the actual file header. So we'll ignore this line.
-                    } else {
-                        included_comments.push(comment);
-                    }
-                } else {
-                    comments.push(clean_comment_line(&line));
-                }
-            } else if !line.starts_with('#') {
-                // Find lines that start a function
-                if is_function_header_line(&line) {
-                    let function = get_function(line, &comments);
-                    included_functions.push(function);
-                }
-                comments.clear();
-                in_header_comments = false;
+        if follow_sources {
+            // Functions are run from the script's directory, so relative paths start there.
+            let dir = executable
+                .absolute_path
+                .parent()
+                .unwrap_or_else(|| Path::new("/"));
+            let mut visited = HashSet::from([executable.absolute_path.to_owned()]);
+            add_sourced_functions(
+                dir,
+                &executable.path,
+                &parsed.sources,
+                &mut visited,
+                &mut included_functions,
+            );
+        }
+
+        // Every function runs the script's side effects, because we source the whole script.
+        let side_effects = get_side_effects(&parsed);
+        for function in &mut included_functions {
+            if let Run::Bash(bash) = &mut function.run {
+                bash.side_effects = side_effects.clone();
             }
         }
 
-        Ok(Self {
-            comment: included_comments,
+        // Annotations in the header describe the script, everything else is shown to the user.
+        let mut script = Self {
             functions: included_functions,
             path: executable.path.to_owned(),
             absolute_path: executable.absolute_path.to_owned(),
+            via_symlink: executable.via_symlink,
+            ..Default::default()
+        };
+        if executable.non_executable {
+            // We can still run its functions, because we source it.
+            script.notes.push("not executable".to_string());
+        }
+        for comment in included_comments {
+            match get_annotation(&comment) {
+                Some(("description", value)) => script.description = unquote(value),
+                Some(("tags", value)) => script.tags.extend(split_list(value)),
+                Some(("owner", value)) => script.owner = unquote(value),
+                _ => script.comment.push(comment),
+            }
+        }
+        Ok(script)
+    }
+
+    /// Whether the function has every one of the tags, either itself or from its script. Tags
+    /// aren't case sensitive.
+    pub fn has_tags(&self, function: &Function, tags: &[String]) -> bool {
+        tags.iter().all(|tag| {
+            function
+                .tags
+                .iter()
+                .chain(&self.tags)
+                .any(|t| t.eq_ignore_ascii_case(tag))
         })
     }
 
+    /// Who owns the function: its own owner, or else the script's.
+    pub fn owner_of<'a>(&'a self, function: &'a Function) -> Option<&'a str> {
+        function.owner.as_deref().or(self.owner.as_deref())
+    }
+
     pub fn get(&self, function_name: &str) -> Option<&Function> {
         self.functions.iter().find(|&n| n.name == function_name)
     }
 
+    /// The script's file name, or its whole path if it somehow doesn't have one.
     pub fn file_name(&self) -> String {
-        if self.path.file_name().is_some() {
-            self.path.file_name().unwrap().to_string_lossy().to_string()
-        } else {
-            panic!("File has no name!")
+        match self.path.file_name() {
+            Some(name) => name.to_string_lossy().to_string(),
+            None => self.path(),
         }
     }
 
     pub fn path(&self) -> String {
         let path = self.path.clone();
-        return path.as_os_str().to_string_lossy().to_string();
+        path.as_os_str().to_string_lossy().to_string()
     }
 
-    pub fn working_dir_absolute(&self) -> String {
-        let mut path = self.absolute_path.clone();
+    /// The path we source the script by when we run a function, which is the link or its target.
+    fn path_to_run(&self, cd: SymlinkCd) -> PathBuf {
+        match cd {
+            SymlinkCd::Link if self.via_symlink => {
+                std::path::absolute(&self.path).unwrap_or_else(|_| self.absolute_path.clone())
+            }
+            _ => self.absolute_path.clone(),
+        }
+    }
+
+    pub fn working_dir_absolute(&self, cd: SymlinkCd) -> String {
+        let mut path = self.path_to_run(cd);
         path.pop();
-        return path.as_os_str().to_string_lossy().to_string();
+        path.as_os_str().to_string_lossy().to_string()
+    }
+
+    /// The name of the file to source from `working_dir_absolute`. That's the target's name
+    /// unless we're running from the link's directory.
+    pub fn file_name_to_run(&self, cd: SymlinkCd) -> String {
+        self.path_to_run(cd)
+            .file_name()
+            .map(|name| name.to_string_lossy().to_string())
+            .unwrap_or_else(|| self.file_name())
     }
 
     pub fn pretty_print(&self) {
@@ -107,6 +281,9 @@ impl Script {
         if self.functions.is_empty() {
             print_no_functions_in_script_help();
         } else {
+            if let Some(description) = &self.description {
+                println!("  {description}");
+            }
             self.comment.iter().for_each(|comment_line| {
                 println!("  {}", comment_line);
             });
@@ -127,15 +304,31 @@ impl Script {
                 let to_print = function
                     .name
                     .pad_to_width_with_alignment(padding, Alignment::Right);
-                let coloured_to_print = format!("{GREEN_FG}{to_print}{RESET_FG}");
-                if !function.comment.is_empty() {
+                // Hidden functions are only here because of --show-hidden, so they're dimmed.
+                let coloured_to_print = if function.hidden {
+                    format!("{Faint}{GREEN_FG}{to_print}{RESET_FG}{NoFaint}")
+                } else {
+                    format!("{GREEN_FG}{to_print}{RESET_FG}")
+                };
+                // Functions from sourced libraries say where they're from, after their comments.
+                let mut lines = function.comment.clone();
+                if !function.params.is_empty() {
+                    lines.push(format!(
+                        "{GREY_FG}(takes {}){RESET_FG}",
+                        function.params.join(" ")
+                    ));
+                }
+                if let Some(source) = &function.source {
+                    lines.push(format!("{GREY_FG}(from {source}){RESET_FG}"));
+                }
+                if !lines.is_empty() {
                     print!("{coloured_to_print}");
                 } else {
                     println!("{coloured_to_print}");
                 }
 
                 // Then follow up with the comment lines
-                function.comment.iter().enumerate().for_each(|(i, line)| {
+                lines.iter().enumerate().for_each(|(i, line)| {
                     if i == 0 {
                         println!(" {line}");
                     } else {
@@ -150,41 +343,219 @@ impl Script {
     }
 }
 
-/// Gets a `Function` from a line that contains a function name. Uses accumulated comments.
-fn get_function(line: String, comments_found_so_far: &[String]) -> Function {
-    let name = line.split("()").next();
-    match name {
-        Some(actual_name) => Function {
-            name: String::from(actual_name.trim()),
-            comment: comments_found_so_far
+/// Gets the block of comments straight after the hashbang line, and the lines they're on.
+fn get_header(parsed: &parser::Parsed) -> (Vec<usize>, Vec<String>) {
+    let mut header_lines: Vec<usize> = Vec::new();
+    let mut included_comments: Vec<String> = Vec::new();
+    if let Some(hashbang) = parsed.comments.iter().find(|c| c.text.contains("#!/")) {
+        header_lines.push(hashbang.line);
+        for comment in parsed.comments.iter().filter(|c| c.line > hashbang.line) {
+            if comment.line != header_lines[header_lines.len() - 1] + 1 {
+                break;
+            }
+            header_lines.push(comment.line);
+            let comment = clean_comment_line(&comment.text);
+            if included_comments.is_empty() && comment.is_empty() {
+                // If we don't yet have any comments, and this comment has 0 length
+                // then we're probably dealing with a spacing line between the hashbang
+                // and the actual file header. So we'll ignore this line.
+            } else {
+                included_comments.push(comment);
+            }
+        }
+    }
+    (header_lines, included_comments)
+}
+
+/// Commands that only set things up for the functions, so they're fine to run every time.
+/// `return` stops the script being sourced, so it's not a problem either.
+const SETUP_COMMANDS: [&str; 15] = [
+    "set", "shopt", "trap", "readonly", "export", "declare", "typeset", "local", "unset", "alias",
+    "umask", ":", "true", "false", "return",
+];
+
+/// Gets the top level commands that do more than set things up, e.g. `cat test.sh` or `main "$@"`.
+/// Tests like `[[ -f ~/.env ]]` and `(( DEBUG ))` don't count, but what they guard does, unless
+/// it's guarded so it only runs when the script isn't sourced.
+fn get_side_effects(parsed: &parser::Parsed) -> Vec<SideEffect> {
+    parsed
+        .commands
+        .iter()
+        .filter(|command| {
+            let name = command.words[0].as_str();
+            !(command.guarded
+                || SETUP_COMMANDS.contains(&name)
+                || matches!(name, "[" | "test")
+                || name.starts_with("[[")
+                || name.starts_with("(("))
+        })
+        .cloned()
+        .collect()
+}
+
+/// The side effects of the script at `path`. Its functions have them too, but a script without
+/// functions still has side effects, e.g. for `lk lint`.
+pub fn side_effects(path: &Path) -> Result<Vec<SideEffect>> {
+    let source = std::fs::read(path).map_err(|source| Error::Read {
+        path: path.to_owned(),
+        source,
+    })?;
+    Ok(get_side_effects(&parser::parse(&String::from_utf8_lossy(
+        &source,
+    ))))
+}
+
+/// Gets the functions we should list from a parsed file, with the comments directly above them.
+/// `source` is set if the file is a library that the script sources.
+fn get_functions(
+    parsed: &parser::Parsed,
+    header_lines: &[usize],
+    source: Option<&str>,
+) -> Vec<Function> {
+    parsed
+        .functions
+        .iter()
+        .map(|def| {
+            let mut comments: Vec<String> = Vec::new();
+            let mut line = def.start_line;
+            while let Some(comment) = parsed
+                .comments
                 .iter()
-                .map(|comment| comment.to_owned())
-                .collect(),
-        },
-        None => {
-            panic!("There is some kind of formatting error with the name of this function:");
+                .find(|c| c.line + 1 == line && !header_lines.contains(&c.line))
+            {
+                comments.insert(0, clean_comment_line(&comment.text));
+                line = comment.line;
+            }
+            let mut function = get_function(&def.name, &comments);
+            function.start_line = def.start_line;
+            function.end_line = def.end_line;
+            function.source = source.map(String::from);
+            function
+        })
+        .collect()
+}
+
+/// Adds the functions from the files in `sources`, and from the files they source, and so on.
+/// Relative paths are relative to `dir`, the script's directory, however deep we go, because
+/// that's where bash will be when it sources them. A function the script already has wins over
+/// one with the same name from a library. `visited` stops us going round in circles.
+fn add_sourced_functions(
+    dir: &Path,
+    from: &Path,
+    sources: &[parser::Source],
+    visited: &mut HashSet<PathBuf>,
+    functions: &mut Vec<Function>,
+) {
+    for source in sources {
+        let path = match source.path.strip_prefix("~/") {
+            Some(in_home) => match dirs::home_dir() {
+                Some(home) => home.join(in_home),
+                None => continue,
+            },
+            None => dir.join(&source.path),
+        };
+        let Ok(absolute_path) = path.canonicalize() else {
+            log::warn!(
+                "{} sources {} on line {}, but it doesn't exist",
+                from.to_string_lossy(),
+                source.path,
+                source.line
+            );
+            continue;
+        };
+        if !visited.insert(absolute_path.clone()) {
+            log::debug!(
+                "Already looked at {:?}, not following it again",
+                absolute_path
+            );
+            continue;
+        }
+        let parsed = match std::fs::read(&absolute_path) {
+            Ok(bytes) => parser::parse(&String::from_utf8_lossy(&bytes)),
+            Err(err) => {
+                log::warn!("Unable to read {:?}. Error was: {err}", absolute_path);
+                continue;
+            }
+        };
+        let (header_lines, _) = get_header(&parsed);
+        let name = source.path.trim_start_matches("./");
+        for function in get_functions(&parsed, &header_lines, Some(name)) {
+            if !functions.iter().any(|f| f.name == function.name) {
+                functions.push(function);
+            }
         }
+        add_sourced_functions(
+            dir,
+            Path::new(&source.path),
+            &parsed.sources,
+            visited,
+            functions,
+        );
+    }
+}
+
+/// Gets a `Function` from its name and the comments above it.
+fn get_function(name: &str, comments_found_so_far: &[String]) -> Function {
+    let mut function = Function {
+        name: String::from(name),
+        ..Default::default()
+    };
+    let mut bash = BashFunction::default();
+    // Annotations are pulled out of the comment, everything else is shown to the user.
+    for comment in comments_found_so_far {
+        match get_annotation(comment) {
+            // A bare `@secret` means every param is secret.
+            Some(("secret", "")) => bash.secrets.push("*".to_string()),
+            Some(("secret", value)) => bash.secrets.extend(split_list(value)),
+            Some(("tags", value)) => function.tags.extend(split_list(value)),
+            Some(("owner", value)) => function.owner = unquote(value),
+            Some(("confirm", value)) => function.confirm = Some(Confirm::YesNo(unquote(value))),
+            Some(("confirm-name", value)) => {
+                function.confirm = Some(Confirm::TypeName(unquote(value)))
+            }
+            Some(("private" | "hidden", _)) => function.hidden = true,
+            Some(("timeout", value)) => match humantime::parse_duration(value) {
+                Ok(timeout) => function.timeout = Some(timeout),
+                Err(err) => log::warn!(
+                    "Ignoring the timeout for {}, because '{value}' isn't a duration: {err}",
+                    function.name
+                ),
+            },
+            _ => function.comment.push(comment.to_owned()),
+        }
+    }
+    function.run = Run::Bash(bash);
+    function
+}
+
+/// Splits a cleaned comment line like `@secret token` into its annotation name and value.
+fn get_annotation(comment: &str) -> Option<(&str, &str)> {
+    let annotation = comment.strip_prefix('@')?;
+    match annotation.split_once(char::is_whitespace) {
+        Some((name, value)) => Some((name, value.trim())),
+        None => Some((annotation, "")),
     }
 }
 
-// The output is wrapped in a Result to allow matching on errors
-// Returns an Iterator to the Reader of the lines of the file.
-// https://doc.rust-lang.org/rust-by-example/std_misc/file/read_lines.html
-fn read_lines<P>(filename: P) -> std::io::Result<std::io::Lines<std::io::BufReader<File>>>
-where
-    P: AsRef<Path>,
-{
-    let file = File::open(filename)?;
-    Ok(std::io::BufReader::new(file).lines())
+/// Splits an annotation value like `infra, db` into its parts.
+fn split_list(value: &str) -> impl Iterator<Item = String> + '_ {
+    value
+        .split(|c: char| c == ',' || c.is_whitespace())
+        .filter(|part| !part.is_empty())
+        .map(String::from)
 }
 
-fn is_function_header_line(line: &str) -> bool {
-    if line.trim().starts_with('_') {
-        false
+/// Strips surrounding quotes from an annotation value. Empty values become `None`.
+fn unquote(value: &str) -> Option<String> {
+    let value = value.trim();
+    let value = value
+        .strip_prefix('"')
+        .and_then(|v| v.strip_suffix('"'))
+        .unwrap_or(value);
+    if value.is_empty() {
+        None
     } else {
-        Regex::new(r"^.*\(\).*\{$")
-            .unwrap()
-            .is_match(line.as_bytes())
+        Some(value.to_string())
     }
 }
 
@@ -215,11 +586,10 @@ mod tests {
     #[test]
     fn test_get_function() {
         // Given
-        let line = String::from("some_function(){");
         let comments = vec![String::from("First line"), String::from("Second line")];
 
         // When
-        let function = get_function(line, &comments);
+        let function = get_function("some_function", &comments);
 
         // Then
         assert_eq!(function.name, "some_function");
@@ -227,30 +597,271 @@ mod tests {
     }
 
     #[test]
-    fn test_get_function_edge() {
+    fn test_get_function_secrets() {
         // Given
-        let line = String::from("   some_function   ()   {");
-        let comments = vec![String::from("First line"), String::from("Second # line")];
+        let comments = vec![
+            String::from("Deploys the thing"),
+            String::from("@secret token, password"),
+            String::from("@secret 3"),
+        ];
 
         // When
-        let function = get_function(line, &comments);
+        let function = get_function("deploy", &comments);
 
         // Then
-        assert_eq!(function.name, "some_function");
-        assert_eq!(function.comment, vec!["First line", "Second # line"]);
+        assert_eq!(function.comment, vec!["Deploys the thing"]);
+        assert_eq!(function.secrets(), vec!["token", "password", "3"]);
+    }
+
+    #[test]
+    fn test_get_function_confirm() {
+        let with_message = vec![String::from("@confirm \"This will wipe prod\"")];
+        let function = get_function("drop_db", &with_message);
+        assert_eq!(
+            function.confirm,
+            Some(Confirm::YesNo(Some("This will wipe prod".to_string())))
+        );
+        assert!(function.comment.is_empty());
+
+        let typed = vec![String::from("@confirm-name")];
+        let function = get_function("drop_db", &typed);
+        assert_eq!(function.confirm, Some(Confirm::TypeName(None)));
+    }
+
+    #[test]
+    fn test_get_function_hidden() {
+        let comments = vec![
+            String::from("Only used by deploy"),
+            String::from("@private"),
+        ];
+        let function = get_function("render_templates", &comments);
+        assert!(function.hidden);
+        assert_eq!(function.comment, vec!["Only used by deploy"]);
+
+        assert!(get_function("helper", &[String::from("@hidden")]).hidden);
+        assert!(!get_function("deploy", &[]).hidden);
+    }
+
+    #[test]
+    fn test_new() {
+        // Given
+        let path = std::path::PathBuf::from("tests/script.sh");
+        let executable = Executable {
+            short_name: "script.sh".to_string(),
+            absolute_path: path.canonicalize().unwrap(),
+            path,
+            non_executable: false,
+            via_symlink: false,
+        };
+
+        // When
+        let script = Script::new(&executable, true).unwrap();
+
+        // Then
+        assert_eq!(
+            script.comment,
+            vec![
+                "First line of file header comment",
+                "Second line of file header comment"
+            ]
+        );
+        let names: Vec<&str> = script.functions.iter().map(|f| f.name.as_str()).collect();
+        assert_eq!(
+            names,
+            vec![
+                "some_function",
+                "another_function",
+                "yet_more_functions",
+                "printing_function",
+                "_hidden_function"
+            ]
+        );
+        let some_function = script.get("some_function").unwrap();
+        assert_eq!(
+            some_function.comment,
+            vec![
+                "This function is very clever and awesome and does a",
+                "lot of neat stuff."
+            ]
+        );
+        assert_eq!((some_function.start_line, some_function.end_line), (10, 16));
+        assert!(script.get("another_function").unwrap().comment.is_empty());
+        assert_eq!(
+            script.get("yet_more_functions").unwrap().comment,
+            vec!["More functions"]
+        );
     }
 
     #[test]
-    fn test_is_function_header_line() {
-        assert!(is_function_header_line(&String::from("some_function(){")));
-        assert!(is_function_header_line(&String::from(
-            "some_function    () {"
-        )));
-        assert!(is_function_header_line(&String::from(
-            "some_function    ()     {"
-        )));
-        assert!(is_function_header_line(&String::from(
-            "    some_function    ()     {"
-        )));
+    fn test_new_metadata() {
+        // Given
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("backup.sh");
+        std::fs::write(
+            &path,
+            r#"#!/usr/bin/env bash
+#
+# Backs up the databases.
+# @description "Nightly database backups"
+# @tags infra, db
+# @owner team-platform
+
+# Restores a backup
+# @tags restore
+# @owner team-data
+restore() { :; }
+
+backup() { :; }
+"#,
+        )
+        .unwrap();
+        let executable = Executable {
+            short_name: "backup.sh".to_string(),
+            absolute_path: path.canonicalize().unwrap(),
+            path,
+            non_executable: false,
+            via_symlink: false,
+        };
+
+        // When
+        let script = Script::new(&executable, true).unwrap();
+
+        // Then
+        assert_eq!(script.comment, vec!["Backs up the databases."]);
+        assert_eq!(
+            script.description.as_deref(),
+            Some("Nightly database backups")
+        );
+        assert_eq!(script.tags, vec!["infra", "db"]);
+        assert!(script.functions.iter().all(|f| f.side_effects().is_empty()));
+        let restore = script.get("restore").unwrap();
+        assert_eq!(restore.comment, vec!["Restores a backup"]);
+        assert_eq!(script.owner_of(restore), Some("team-data"));
+        assert_eq!(
+            script.owner_of(script.get("backup").unwrap()),
+            Some("team-platform")
+        );
+        let tags = |tags: &[&str]| tags.iter().map(|t| t.to_string()).collect::<Vec<_>>();
+        assert!(script.has_tags(restore, &tags(&["DB", "restore"])));
+        assert!(!script.has_tags(script.get("backup").unwrap(), &tags(&["restore"])));
+    }
+
+    #[test]
+    fn test_new_side_effects() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("tool.sh");
+        std::fs::write(
+            &path,
+            "#!/usr/bin/env bash\nset -e\ncat notes.txt\nmain() { :; }\nif [[ \"${BASH_SOURCE[0]}\" == \"$0\" ]]; then\n    main \"$@\"\nfi\n",
+        )
+        .unwrap();
+        let executable = Executable {
+            short_name: "tool.sh".to_string(),
+            absolute_path: path.canonicalize().unwrap(),
+            path,
+            non_executable: false,
+            via_symlink: false,
+        };
+
+        let script = Script::new(&executable, true).unwrap();
+
+        let found: Vec<(usize, String)> = script
+            .get("main")
+            .unwrap()
+            .side_effects()
+            .iter()
+            .map(|c| (c.line, c.words.join(" ")))
+            .collect();
+        assert_eq!(found, vec![(3, "cat notes.txt".to_string())]);
+        assert_eq!(side_effects(&executable.path).unwrap().len(), 1);
+    }
+
+    #[test]
+    fn test_symlink_cd() {
+        let dir = tempfile::tempdir().unwrap();
+        let shared = dir.path().join("shared");
+        let project = dir.path().join("project");
+        std::fs::create_dir(&shared).unwrap();
+        std::fs::create_dir(&project).unwrap();
+        let target = shared.join("tool.sh");
+        std::fs::write(&target, "#!/usr/bin/env bash\n").unwrap();
+        let path = project.join("run.sh");
+        std::os::unix::fs::symlink(&target, &path).unwrap();
+        let executable = Executable {
+            short_name: "run.sh".to_string(),
+            absolute_path: path.canonicalize().unwrap(),
+            path,
+            non_executable: false,
+            via_symlink: true,
+        };
+        let script = Script::new(&executable, true).unwrap();
+
+        let shared = shared.canonicalize().unwrap();
+        assert_eq!(
+            script.working_dir_absolute(SymlinkCd::Target),
+            shared.to_string_lossy()
+        );
+        assert_eq!(script.file_name_to_run(SymlinkCd::Target), "tool.sh");
+        assert_eq!(
+            script.working_dir_absolute(SymlinkCd::Link),
+            project.to_string_lossy()
+        );
+        assert_eq!(script.file_name_to_run(SymlinkCd::Link), "run.sh");
+    }
+
+    #[test]
+    fn test_new_follows_sources() {
+        // Given a script that sources a library, which sources another library and the script
+        // again, and a library that doesn't exist.
+        let dir = tempfile::tempdir().unwrap();
+        std::fs::create_dir(dir.path().join("lib")).unwrap();
+        let files = [
+            (
+                "main.sh",
+                "#!/usr/bin/env bash\nsource ./lib/common.sh\n. missing.sh\nshared() { :; }\nmine() { :; }\n",
+            ),
+            (
+                "lib/common.sh",
+                "source lib/more.sh\nsource ./main.sh\n# Says hello\ngreet() { :; }\n_private() { :; }\n",
+            ),
+            ("lib/more.sh", "shared() { :; }\nmore() { :; }\n"),
+        ];
+        for (name, contents) in files {
+            std::fs::write(dir.path().join(name), contents).unwrap();
+        }
+        let path = dir.path().join("main.sh");
+        let executable = Executable {
+            short_name: "main.sh".to_string(),
+            absolute_path: path.canonicalize().unwrap(),
+            path,
+            non_executable: false,
+            via_symlink: false,
+        };
+
+        // When
+        let script = Script::new(&executable, true).unwrap();
+
+        // Then
+        let functions: Vec<(&str, Option<&str>)> = script
+            .functions
+            .iter()
+            .map(|f| (f.name.as_str(), f.source.as_deref()))
+            .collect();
+        assert_eq!(
+            functions,
+            vec![
+                ("shared", None),
+                ("mine", None),
+                ("greet", Some("lib/common.sh")),
+                ("_private", Some("lib/common.sh")),
+                ("more", Some("lib/more.sh")),
+            ]
+        );
+        assert_eq!(script.get("greet").unwrap().comment, vec!["Says hello"]);
+
+        let script = Script::new(&executable, false).unwrap();
+        assert_eq!(script.functions.len(), 2);
+        // `source` isn't a side effect, it's how the functions get loaded.
+        assert!(side_effects(&executable.path).unwrap().is_empty());
     }
 }
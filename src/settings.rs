@@ -0,0 +1,712 @@
+/// lk's settings, with their defaults, checked as they're read so mistakes in `lk.toml` get a
+/// helpful error rather than being ignored.
+use crate::executables::NonExecutable;
+use crate::script::SymlinkCd;
+use crate::workspace::Workspace;
+use crate::xdg::LkDirs;
+use crate::{Error, Result};
+use config::builder::DefaultState;
+use config::{Config, ConfigBuilder, Environment, File};
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
+use std::collections::BTreeMap;
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+
+/// How lk starts if you don't pass `--tui`, `--fuzzy` or `--list`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Mode {
+    Tui,
+    Fuzzy,
+    List,
+}
+
+/// Every setting there is. Anything missing from the config gets its default, and anything that
+/// isn't here is a setting lk doesn't know.
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize, Serialize)]
+#[serde(default)]
+#[non_exhaustive]
+pub struct LkConfig {
+    pub default_mode: Mode,
+    pub includes: Vec<String>,
+    pub excludes: Vec<String>,
+    /// Whether to leave out `DEFAULT_EXCLUDES` as well as `excludes`.
+    pub default_excludes: bool,
+    pub redact_patterns: Vec<String>,
+    pub confirm: Vec<String>,
+    pub confirm_name: Vec<String>,
+    pub capture_output: bool,
+    pub log_dir: PathBuf,
+    #[serde(with = "duration")]
+    pub timeout_grace: Duration,
+    pub follow_sources: bool,
+    pub refuse_side_effects: bool,
+    pub hide_prefixes: Vec<String>,
+    pub hide_patterns: Vec<String>,
+    pub non_executable: NonExecutable,
+    pub symlink_cd: SymlinkCd,
+    /// Where to find things to run, e.g. bash scripts and Makefiles, by the providers' names.
+    pub providers: Vec<String>,
+    /// What each alias runs, e.g. `up = "docker.sh compose_up --build"`. The names are lowercase.
+    #[serde(deserialize_with = "steps")]
+    pub aliases: BTreeMap<String, Vec<String>>,
+}
+
+/// Settings that aren't in `LkConfig`. The commands module reads `[commands]` itself, because
+/// config-rs would lowercase the environment variables in them.
+const READ_ELSEWHERE: &[&str] = &["commands"];
+
+/// Things nobody wants lk to look through for scripts. These are left out as well as `excludes`,
+/// unless `default_excludes` is false.
+pub const DEFAULT_EXCLUDES: &[&str] = &[
+    // TODO: List these in help
+    "target",
+    ".github",
+    ".vscode",
+    ".git",
+    "node_modules",
+    ".nvm",
+    ".Trash",
+    ".npm",
+    ".cache",
+    "Library",
+    ".cargo",
+    ".sock",
+];
+
+impl LkConfig {
+    /// lk's defaults, with captured output kept in the state directory.
+    pub fn defaults(dirs: &LkDirs) -> Self {
+        let strings = |values: &[&str]| values.iter().map(|v| v.to_string()).collect();
+        Self {
+            default_mode: Mode::Tui,
+            includes: strings(&["**/*", "*"]),
+            excludes: Vec::new(),
+            default_excludes: true,
+            redact_patterns: Vec::new(),
+            confirm: Vec::new(),
+            confirm_name: Vec::new(),
+            capture_output: false,
+            log_dir: dirs.state.join("logs"),
+            timeout_grace: Duration::from_secs(5),
+            follow_sources: true,
+            refuse_side_effects: false,
+            hide_prefixes: strings(&["_"]),
+            hide_patterns: Vec::new(),
+            non_executable: NonExecutable::Skip,
+            symlink_cd: SymlinkCd::Target,
+            providers: ["bash", "commands", "aliases"].map(String::from).to_vec(),
+            aliases: BTreeMap::new(),
+        }
+    }
+
+    /// Reads the settings. If one's wrong, the error says which, and which file it's in.
+    pub fn new(config: &Config, origins: &BTreeMap<String, Origin>) -> Result<Self> {
+        let settings: serde_json::Value = config.clone().try_deserialize()?;
+        LkConfig::deserialize(&settings).map_err(|error| {
+            // Find the setting that's wrong by reading them one at a time.
+            for (key, value) in settings.as_object().into_iter().flatten() {
+                let alone = serde_json::json!({ key: value });
+                if let Err(error) = LkConfig::deserialize(&alone) {
+                    return match origins.get(key) {
+                        Some(origin) => {
+                            Error::Config(format!("{key} in {origin} is wrong: {error}"))
+                        }
+                        None => Error::Config(format!("{key} is wrong: {error}")),
+                    };
+                }
+            }
+            error.into()
+        })
+    }
+
+    /// The includes and excludes, relative to the root of the project, which is where lk looks
+    /// for scripts. Globs in a workspace config file are relative to that file, and globs from
+    /// the environment or the command line are relative to `cwd`. Globs in the user config
+    /// already are where they need to be.
+    pub fn globs(
+        &self,
+        origins: &BTreeMap<String, Origin>,
+        workspace: &Workspace,
+        cwd: &Path,
+    ) -> (Vec<String>, Vec<String>) {
+        let anchored = |key: &str, globs: &[String]| match origins.get(key) {
+            Some(Origin::File(file)) if !workspace.files.contains(file) => globs.to_vec(),
+            Some(origin) => workspace.anchor(origin.dir(cwd), globs),
+            None => globs.to_vec(),
+        };
+        let includes = anchored("includes", &self.includes);
+        let mut excludes = anchored("excludes", &self.excludes);
+        if self.default_excludes {
+            excludes.extend(DEFAULT_EXCLUDES.iter().map(|e| e.to_string()));
+        }
+        (includes, excludes)
+    }
+}
+
+impl Default for LkConfig {
+    fn default() -> Self {
+        Self::defaults(&LkDirs::new())
+    }
+}
+
+/// The settings lk knows, and whether each is a list. In an environment variable or `--set`, a
+/// list's values are separated by commas.
+fn known_settings() -> BTreeMap<String, bool> {
+    let defaults = serde_json::to_value(LkConfig::default()).unwrap_or_default();
+    let settings = defaults.as_object().cloned().unwrap_or_default();
+    settings
+        .into_iter()
+        .map(|(key, value)| (key, value.is_array()))
+        .chain(READ_ELSEWHERE.iter().map(|key| (key.to_string(), false)))
+        .collect()
+}
+
+/// Durations are written like `"5s"` or `"1m"`.
+mod duration {
+    use super::*;
+
+    pub fn serialize<S: Serializer>(duration: &Duration, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.serialize_str(&humantime::format_duration(*duration).to_string())
+    }
+
+    pub fn deserialize<'de, D: Deserializer<'de>>(deserializer: D) -> Result<Duration, D::Error> {
+        let value = String::deserialize(deserializer)?;
+        humantime::parse_duration(&value).map_err(|e| {
+            serde::de::Error::custom(format!(
+                "'{value}' isn't a duration like 5s or 1m, because it's {e}"
+            ))
+        })
+    }
+}
+
+/// An alias runs one step, or a list of them.
+fn steps<'de, D: Deserializer<'de>>(
+    deserializer: D,
+) -> Result<BTreeMap<String, Vec<String>>, D::Error> {
+    #[derive(Deserialize)]
+    #[serde(untagged)]
+    enum Steps {
+        One(String),
+        Many(Vec<String>),
+    }
+    let aliases = BTreeMap::<String, Steps>::deserialize(deserializer)?;
+    Ok(aliases
+        .into_iter()
+        .map(|(name, steps)| match steps {
+            Steps::One(step) => (name, vec![step]),
+            Steps::Many(steps) => (name, steps),
+        })
+        .collect())
+}
+
+/// A config builder with every setting's default, ready for the config files.
+pub fn defaults(dirs: &LkDirs) -> Result<ConfigBuilder<DefaultState>> {
+    Ok(Config::builder().add_source(Config::try_from(&LkConfig::defaults(dirs))?))
+}
+
+/// Where a setting comes from, if it isn't lk's default.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Origin {
+    File(PathBuf),
+    /// An `LK_*` environment variable, e.g. `LK_DEFAULT_MODE`.
+    Env(String),
+    /// A command line option, e.g. `--set` or `--exclude`.
+    CommandLine(String),
+}
+
+impl Origin {
+    /// Where globs from here are relative to: the directory of the config file, or where lk was
+    /// run for everything else.
+    pub fn dir<'a>(&'a self, cwd: &'a Path) -> &'a Path {
+        match self {
+            Origin::File(file) => file.parent().unwrap_or(cwd),
+            Origin::Env(_) | Origin::CommandLine(_) => cwd,
+        }
+    }
+}
+
+impl std::fmt::Display for Origin {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        match self {
+            Origin::File(file) => write!(f, "{}", file.to_string_lossy()),
+            Origin::Env(name) | Origin::CommandLine(name) => write!(f, "{name}"),
+        }
+    }
+}
+
+/// Which of the config files each setting comes from, i.e. the last one that sets it. Settings
+/// that aren't here are lk's defaults.
+pub fn origins(files: &[PathBuf]) -> Result<BTreeMap<String, Origin>> {
+    let mut origins = BTreeMap::new();
+    for file in files {
+        for key in settings(file)?.into_keys() {
+            origins.insert(key, Origin::File(file.to_owned()));
+        }
+    }
+    Ok(origins)
+}
+
+pub fn settings(file: &Path) -> Result<BTreeMap<String, serde_json::Value>> {
+    Config::builder()
+        .add_source(File::from(file).required(false))
+        .build()
+        .and_then(Config::try_deserialize)
+        .map_err(|e| Error::Config(format!("Couldn't read {}: {e}", file.to_string_lossy())))
+}
+
+/// Prints every setting, and where it comes from. This is `lk config show`.
+pub fn show(config: Config, origins: &BTreeMap<String, Origin>) -> Result<()> {
+    let settings: BTreeMap<String, serde_json::Value> = config.try_deserialize()?;
+    for (key, value) in settings {
+        let origin = match origins.get(&key) {
+            Some(origin) => origin.to_string(),
+            None => "default".to_string(),
+        };
+        println!("{key} = {value}  # {origin}");
+    }
+    Ok(())
+}
+
+/// Warnings about settings in the files that lk doesn't know, e.g. a typo like `exclude`, which
+/// would otherwise be ignored without a word.
+pub fn unknown_settings(files: &[PathBuf]) -> Result<Vec<String>> {
+    let known = known_settings();
+    let mut warnings = Vec::new();
+    for file in files {
+        for key in settings(file)?.into_keys() {
+            if known.contains_key(&key) {
+                continue;
+            }
+            warnings.push(format!(
+                "lk doesn't know the setting {key} in {}, so it's ignoring it.{}",
+                file.display(),
+                suggestion(&key)
+            ));
+        }
+    }
+    Ok(warnings)
+}
+
+/// Suggests a setting with a name like `key`, if there is one.
+fn suggestion(key: &str) -> String {
+    known_settings()
+        .into_keys()
+        .find(|setting| distance(setting, key) <= 2)
+        .map(|setting| format!(" Did you mean {setting}?"))
+        .unwrap_or_default()
+}
+
+/// Adds `LK_*` environment variables for settings, e.g. `LK_DEFAULT_MODE=fuzzy`, on top of the
+/// config files. Other `LK_*` variables, like `LK_LOG`, aren't settings so they're left alone.
+pub fn environment(
+    builder: ConfigBuilder<DefaultState>,
+    vars: impl IntoIterator<Item = (String, String)>,
+    origins: &mut BTreeMap<String, Origin>,
+) -> ConfigBuilder<DefaultState> {
+    let known = known_settings();
+    let vars: config::Map<String, String> = vars
+        .into_iter()
+        .filter(|(name, _)| {
+            let key = name.strip_prefix("LK_").unwrap_or_default().to_lowercase();
+            known.contains_key(&key)
+        })
+        .collect();
+    for name in vars.keys() {
+        origins.insert(name[3..].to_lowercase(), Origin::Env(name.to_owned()));
+    }
+    let mut environment = Environment::with_prefix("LK")
+        .source(Some(vars))
+        .try_parsing(true)
+        .list_separator(",");
+    for (key, _) in known.iter().filter(|(_, list)| **list) {
+        environment = environment.with_list_parse_key(key);
+    }
+    builder.add_source(environment)
+}
+
+/// Sets a setting from `--set key=value`, overriding the config files and environment. Lists are
+/// separated by commas, e.g. `--set hide_prefixes=_,internal_`.
+pub fn set(
+    builder: ConfigBuilder<DefaultState>,
+    setting: &str,
+    origins: &mut BTreeMap<String, Origin>,
+) -> Result<ConfigBuilder<DefaultState>> {
+    let Some((key, value)) = setting.split_once('=') else {
+        return Err(Error::Config(format!(
+            "--set needs a key=value, e.g. default_mode=fuzzy, not {setting}"
+        )));
+    };
+    let key = key.trim();
+    let Some(&list) = known_settings().get(key) else {
+        return Err(Error::Config(format!(
+            "--set {setting}: lk doesn't know the setting {key}.{}",
+            suggestion(key)
+        )));
+    };
+    let origin = Origin::CommandLine("--set".to_string());
+    if list {
+        let values: Vec<String> = value
+            .split(',')
+            .map(str::trim)
+            .filter(|value| !value.is_empty())
+            .map(String::from)
+            .collect();
+        set_override(builder, key, values, origin, origins)
+    } else if let Ok(flag) = value.parse::<bool>() {
+        // Like the environment variables, `true` and `false` are booleans rather than strings.
+        set_override(builder, key, flag, origin, origins)
+    } else {
+        set_override(builder, key, value, origin, origins)
+    }
+}
+
+/// Overrides a setting from the command line, and notes where it came from.
+pub fn set_override(
+    builder: ConfigBuilder<DefaultState>,
+    key: &str,
+    value: impl Into<config::Value>,
+    origin: Origin,
+    origins: &mut BTreeMap<String, Origin>,
+) -> Result<ConfigBuilder<DefaultState>> {
+    origins.insert(key.to_string(), origin);
+    Ok(builder.set_override(key, value)?)
+}
+
+/// The settings given on the command line: `--set`, and the options that have a setting of their
+/// own, e.g. `--excludes`.
+#[derive(Debug, Clone, Default)]
+#[non_exhaustive]
+pub struct CommandLine {
+    pub sets: Vec<String>,
+    pub includes: Vec<String>,
+    pub excludes: Vec<String>,
+    pub no_default_excludes: bool,
+}
+
+/// Puts the settings together, and says where each comes from. lk's defaults come first, then
+/// the config files in order, so later files override earlier ones. `LK_*` environment variables
+/// override those, `--set` overrides them, and options of their own, like `--excludes`, override
+/// everything.
+pub fn load(
+    dirs: &LkDirs,
+    files: &[PathBuf],
+    env: impl IntoIterator<Item = (String, String)>,
+    command_line: &CommandLine,
+) -> Result<(Config, BTreeMap<String, Origin>)> {
+    let mut builder = defaults(dirs)?;
+    for file in files {
+        builder = builder.add_source(File::from(file.as_path()).required(false));
+    }
+    let mut origins = origins(files)?;
+    builder = environment(builder, env, &mut origins);
+    for setting in &command_line.sets {
+        builder = set(builder, setting, &mut origins)?;
+    }
+    let options = [
+        ("includes", "--includes", &command_line.includes),
+        ("excludes", "--excludes", &command_line.excludes),
+    ];
+    for (key, option, globs) in options {
+        if !globs.is_empty() {
+            let origin = Origin::CommandLine(option.to_string());
+            builder = set_override(builder, key, globs.clone(), origin, &mut origins)?;
+        }
+    }
+    if command_line.no_default_excludes {
+        let origin = Origin::CommandLine("--no-default-excludes".to_string());
+        builder = set_override(builder, "default_excludes", false, origin, &mut origins)?;
+    }
+    Ok((builder.build()?, origins))
+}
+
+/// How many single character edits it takes to get from one word to the other.
+fn distance(a: &str, b: &str) -> usize {
+    let b: Vec<char> = b.chars().collect();
+    let mut previous: Vec<usize> = (0..=b.len()).collect();
+    for (i, a) in a.chars().enumerate() {
+        let mut current = vec![i + 1];
+        for (j, b) in b.iter().enumerate() {
+            let substitution = previous[j] + usize::from(a != *b);
+            current.push(substitution.min(previous[j + 1] + 1).min(current[j] + 1));
+        }
+        previous = current;
+    }
+    previous[b.len()]
+}
+
+/// An `lk.toml` with every setting at its default, and what it does.
+fn default_file(dirs: &LkDirs) -> String {
+    let default_excludes: String = DEFAULT_EXCLUDES
+        .iter()
+        .map(|exclude| format!("#   {exclude}\n"))
+        .collect();
+    format!(
+        r#"# lk's settings. A project's lk.toml overrides these.
+
+# How lk starts if you don't pass --tui, --fuzzy or --list: "tui", "fuzzy" or "list".
+default_mode = "tui"
+
+# Where to look for scripts, as globs relative to the project root.
+includes = ["**/*", "*"]
+
+# Where not to look, as well as the default excludes.
+excludes = []
+
+# Set this to false to look in the places lk leaves out by default:
+{default_excludes}default_excludes = true
+
+# Regexes for things to mask in lk's output, logs and history, e.g. "ghp_[A-Za-z0-9]+".
+redact_patterns = []
+
+# Globs for functions that need a y/N before they run, and ones where you have to type the name.
+confirm = []
+confirm_name = []
+
+# Write the output of every run to a log file in log_dir, like --capture.
+capture_output = false
+log_dir = "{log_dir}"
+
+# How long a function that's timed out gets to clean up before it's killed.
+timeout_grace = "5s"
+
+# List the functions from files a script sources.
+follow_sources = true
+
+# Don't run functions from scripts that run commands whenever they're sourced, unless --force.
+refuse_side_effects = false
+
+# Functions to hide, by the start of their name or a regex.
+hide_prefixes = ["_"]
+hide_patterns = []
+
+# Scripts without the execute bit: "skip" them, include them by "extension", or by "shebang".
+non_executable = "skip"
+
+# Where to run functions from when the script is a symlink: the "target"'s directory or the "link"'s.
+symlink_cd = "target"
+
+# Where to find things to run: functions in "bash" scripts, "commands" from lk.toml, the targets in
+# Makefiles ("make"), package.json scripts ("npm") and justfiles ("just"), and the "aliases" below.
+# Aliases run what the providers before them found.
+providers = ["bash", "commands", "aliases"]
+
+# Short names for functions, e.g. up = "docker.sh compose_up --build", or for several to run one
+# after the other, e.g. ship = ["build.sh all", "deploy.sh release prod"].
+[aliases]
+
+# Commands that don't need a script of their own. They're listed under "commands", e.g.
+#
+# [commands.build]
+# run = "cargo build --release"
+# description = "Builds the release binary"
+# cwd = "app"                      # Relative to this file's directory.
+# env = {{ RUST_LOG = "debug" }}
+[commands]
+"#,
+        log_dir = dirs.state.join("logs").display()
+    )
+}
+
+/// Writes `lk.toml` with the defaults, so there's something to edit. This is `lk config init`.
+pub fn init(path: &Path, dirs: &LkDirs) -> Result<()> {
+    if path.exists() {
+        return Err(Error::Config(format!(
+            "{} already exists, so lk config init has left it alone.",
+            path.display()
+        )));
+    }
+    std::fs::write(path, default_file(dirs)).map_err(|source| Error::Write {
+        path: path.to_owned(),
+        source,
+    })?;
+    println!("Wrote lk's default settings to {}", path.display());
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use config::File;
+
+    fn dirs() -> LkDirs {
+        LkDirs {
+            config: PathBuf::from("/config/lk"),
+            state: PathBuf::from("/state/lk"),
+            cache: PathBuf::from("/cache/lk"),
+        }
+    }
+
+    fn load(toml: &str) -> Result<LkConfig> {
+        let config = defaults(&dirs())?
+            .add_source(File::from_str(toml, config::FileFormat::Toml))
+            .build()?;
+        let file = PathBuf::from("lk.toml");
+        let origins = toml::from_str::<toml::Table>(toml)
+            .unwrap()
+            .into_iter()
+            .map(|(key, _)| (key, Origin::File(file.clone())))
+            .collect();
+        LkConfig::new(&config, &origins)
+    }
+
+    #[test]
+    fn the_default_file_has_the_defaults() {
+        let defaults = load("").unwrap();
+        assert_eq!(defaults.default_mode, Mode::Tui);
+        assert_eq!(defaults.timeout_grace, Duration::from_secs(5));
+        assert_eq!(load(&default_file(&dirs())).unwrap(), defaults);
+        // And it sets everything there is.
+        let file: toml::Table = toml::from_str(&default_file(&dirs())).unwrap();
+        let keys: Vec<&String> = file.keys().collect();
+        let settings = known_settings();
+        assert_eq!(keys, settings.keys().collect::<Vec<_>>());
+    }
+
+    #[test]
+    fn reads_aliases() {
+        let settings = load(
+            "[aliases]\nup = \"docker.sh compose_up --build\"\nShip = [\"build.sh all\", \"deploy.sh release\"]",
+        )
+        .unwrap();
+        assert_eq!(
+            settings.aliases,
+            BTreeMap::from([
+                // Keys aren't case sensitive, so config-rs lowercases them.
+                (
+                    "ship".to_string(),
+                    vec!["build.sh all".to_string(), "deploy.sh release".to_string()]
+                ),
+                (
+                    "up".to_string(),
+                    vec!["docker.sh compose_up --build".to_string()]
+                ),
+            ])
+        );
+    }
+
+    #[test]
+    fn bad_settings_say_what_is_wrong() {
+        let error = load("default_mode = \"fuzy\"").unwrap_err().to_string();
+        assert_eq!(
+            error,
+            "default_mode in lk.toml is wrong: unknown variant `fuzy`, expected one of `tui`, `fuzzy`, `list`"
+        );
+
+        let error = load("timeout_grace = \"soon\"").unwrap_err().to_string();
+        assert!(
+            error.starts_with("timeout_grace in lk.toml is wrong: 'soon' isn't a duration"),
+            "{error}"
+        );
+
+        let error = load("capture_output = [1]").unwrap_err().to_string();
+        assert!(
+            error.starts_with("capture_output in lk.toml is wrong"),
+            "{error}"
+        );
+    }
+
+    #[test]
+    fn warns_about_unknown_settings() {
+        let dir = tempfile::tempdir().unwrap();
+        let file = dir.path().join("lk.toml");
+        std::fs::write(
+            &file,
+            "exclude = [\"x\"]\nincludes = []\nfrobnicate = true\n",
+        )
+        .unwrap();
+
+        let warnings = unknown_settings(&[file]).unwrap();
+
+        assert_eq!(warnings.len(), 2);
+        assert!(warnings[0].contains("exclude in"));
+        assert!(warnings[0].ends_with("Did you mean excludes?"));
+        assert!(warnings[1].contains("frobnicate"));
+        assert!(!warnings[1].contains("Did you mean"));
+    }
+
+    #[test]
+    fn overrides_the_files_with_the_environment_then_set() {
+        let mut origins = BTreeMap::new();
+        let builder = defaults(&dirs()).unwrap().add_source(File::from_str(
+            "default_mode = \"list\"\ncapture_output = true",
+            config::FileFormat::Toml,
+        ));
+        let vars = [
+            ("LK_DEFAULT_MODE", "fuzzy"),
+            ("LK_HIDE_PREFIXES", "_,internal_"),
+            ("LK_CAPTURE_OUTPUT", "false"),
+            ("LK_LOG", "debug"),
+            ("HOME", "/home/me"),
+        ]
+        .map(|(name, value)| (name.to_string(), value.to_string()));
+        let builder = environment(builder, vars, &mut origins);
+        let builder = set(builder, "default_mode=tui", &mut origins).unwrap();
+        let builder = set(builder, "excludes=a, b", &mut origins).unwrap();
+        let settings = LkConfig::new(&builder.build().unwrap(), &origins).unwrap();
+
+        assert_eq!(settings.default_mode, Mode::Tui);
+        assert_eq!(settings.hide_prefixes, vec!["_", "internal_"]);
+        assert!(!settings.capture_output);
+        assert_eq!(settings.excludes, vec!["a", "b"]);
+        assert_eq!(
+            origins,
+            BTreeMap::from([
+                (
+                    "capture_output".to_string(),
+                    Origin::Env("LK_CAPTURE_OUTPUT".to_string())
+                ),
+                (
+                    "default_mode".to_string(),
+                    Origin::CommandLine("--set".to_string())
+                ),
+                (
+                    "excludes".to_string(),
+                    Origin::CommandLine("--set".to_string())
+                ),
+                (
+                    "hide_prefixes".to_string(),
+                    Origin::Env("LK_HIDE_PREFIXES".to_string())
+                ),
+            ])
+        );
+    }
+
+    #[test]
+    fn set_needs_a_setting_lk_knows() {
+        let mut origins = BTreeMap::new();
+        let error = set(defaults(&dirs()).unwrap(), "exclude=x", &mut origins).unwrap_err();
+        assert_eq!(
+            error.to_string(),
+            "--set exclude=x: lk doesn't know the setting exclude. Did you mean excludes?"
+        );
+        assert!(set(defaults(&dirs()).unwrap(), "excludes", &mut origins).is_err());
+    }
+
+    #[test]
+    fn measures_distance() {
+        assert_eq!(distance("exclude", "excludes"), 1);
+        assert_eq!(distance("confrim", "confirm"), 2);
+        assert_eq!(distance("", "abc"), 3);
+    }
+
+    #[test]
+    fn knows_where_settings_come_from() {
+        let dir = tempfile::tempdir().unwrap();
+        let root = dir.path().join("root.toml");
+        let leaf = dir.path().join("leaf.toml");
+        std::fs::write(&root, "default_mode = \"list\"\nexcludes = [\"target\"]\n").unwrap();
+        std::fs::write(&leaf, "default_mode = \"fuzzy\"\n").unwrap();
+
+        let origins =
+            origins(&[root.clone(), leaf.clone(), dir.path().join("missing.toml")]).unwrap();
+
+        assert_eq!(
+            origins,
+            BTreeMap::from([
+                ("default_mode".to_string(), Origin::File(leaf)),
+                ("excludes".to_string(), Origin::File(root))
+            ])
+        );
+    }
+}
@@ -1,6 +1,6 @@
 use std::{fs::OpenOptions, io::Write, path::Path};
 
-use anyhow::Result;
+use std::io::Result;
 
 #[derive(Clone)]
 pub struct Shell {
@@ -70,15 +70,41 @@ impl UserShell {
         }
     }
 
+    pub fn history_file(&self) -> &str {
+        &self.history_file
+    }
+
+    /// A shell whose history is in the given file, rather than the user's.
+    pub fn with_history_file(history_file: String) -> Self {
+        Self { history_file }
+    }
+
+    /// Appends a command to the shell's history. Callers must redact the command first.
     pub fn add_command(&self, command: String) -> Result<()> {
         log::info!("Adding command to history: {}", command);
         log::info!("History file: {}", &self.history_file);
         // TODO Don't fail if the file doesn't exist
-        let mut file = OpenOptions::new()
-            .append(true)
-            .open(&self.history_file)
-            .unwrap();
+        let mut file = OpenOptions::new().append(true).open(&self.history_file)?;
         writeln!(file, "{}", command)?;
         Ok(())
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn appends_to_the_history_file() {
+        let file = tempfile::NamedTempFile::new().unwrap();
+        std::fs::write(file.path(), "ls\n").unwrap();
+        let shell = UserShell::with_history_file(file.path().to_string_lossy().to_string());
+
+        shell
+            .add_command("lk deploy.sh deploy".to_string())
+            .unwrap();
+
+        let on_disk = std::fs::read_to_string(file.path()).unwrap();
+        assert_eq!(on_disk, "ls\nlk deploy.sh deploy\n");
+    }
+}
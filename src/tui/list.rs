@@ -11,9 +11,11 @@ use std::{
 };
 
 use super::state::App;
-use crate::script::{self, Function, Script};
+use lk::script::{self, Function, Script};
 
-pub fn find(scripts: &[script::Script]) -> Result<Option<(Script, Function)>> {
+/// Shows the TUI and returns the functions the user chose. The user can pick several with Tab,
+/// otherwise it's the one they pressed Enter on. Nothing if they pressed Esc.
+pub fn find(scripts: &[script::Script]) -> Result<Vec<(Script, Function)>> {
     // setup terminal
     enable_raw_mode()?;
     let mut stdout = io::stdout();
@@ -42,7 +44,7 @@ fn find_loop<B: Backend>(
     terminal: &mut Terminal<B>,
     mut app: App,
     tick_rate: Duration,
-) -> Result<Option<(Script, Function)>> {
+) -> Result<Vec<(Script, Function)>> {
     let last_tick = Instant::now();
     app.filtered_items.next();
     loop {
@@ -60,7 +62,11 @@ fn find_loop<B: Backend>(
                         KeyCode::Left => app.filtered_items.unselect(),
                         KeyCode::Down => app.filtered_items.next(),
                         KeyCode::Up => app.filtered_items.previous(),
-                        KeyCode::Esc => return Ok(None),
+                        KeyCode::Esc => return Ok(Vec::new()),
+                        KeyCode::Tab => {
+                            app.toggle_selected();
+                            app.filtered_items.next();
+                        }
                         KeyCode::Char(c) => {
                             app.update_search_term(c.to_string().as_str());
                             app.filtered_items.next();
@@ -73,13 +79,7 @@ fn find_loop<B: Backend>(
                             app.delete_search_term_char();
                             app.filtered_items.next();
                         }
-                        KeyCode::Enter => {
-                            let selected = app.get_selected();
-                            match selected {
-                                Some(selected) => return Ok(Some(selected.source.clone())),
-                                None => return Ok(None),
-                            }
-                        }
+                        KeyCode::Enter => return Ok(app.get_chosen()),
                         _ => {}
                     }
                 }
@@ -127,7 +127,7 @@ impl App {
         para.block(block)
     }
 
-    fn item_list(&self) -> List {
+    fn item_list(&self) -> List<'_> {
         let list_items: Vec<ListItem> = self
             .filtered_items
             .get_as_coloured()
@@ -174,14 +174,54 @@ impl App {
                 let mut text = vec![
                     Line::from("Location".black().on_blue()),
                     Line::from(relative_path),
+                ];
+                if selected.source.0.via_symlink {
+                    text.push(Line::from(format!(
+                        "Links to {}",
+                        selected.source.0.absolute_path.to_string_lossy()
+                    )));
+                }
+                if let Some(source) = &selected.source.1.source {
+                    text.push(Line::from(format!("Function from {source}")));
+                }
+                let (script, function) = &selected.source;
+                if let Some(owner) = script.owner_of(function) {
+                    text.push(Line::from(format!("Owned by {owner}")));
+                }
+                let tags: Vec<&str> = function
+                    .tags
+                    .iter()
+                    .chain(&script.tags)
+                    .map(String::as_str)
+                    .collect();
+                if !tags.is_empty() {
+                    text.push(Line::from(format!("Tags: {}", tags.join(", "))));
+                }
+                text.extend([
                     Line::from(""),
                     Line::from("File comments".black().on_blue()),
-                ];
+                ]);
+                if let Some(description) = &script.description {
+                    text.push(Line::from(description.clone()));
+                }
                 text.append(&mut file_comments);
                 text.push(Line::from(""));
                 text.push(Line::from("Function comments".black().on_blue()));
                 text.append(&mut function_comments);
 
+                // lk sources the whole script, so these run before the function does.
+                if !function.side_effects().is_empty() {
+                    text.push(Line::from(""));
+                    text.push(Line::from(
+                        "Runs every time the script is sourced".black().on_yellow(),
+                    ));
+                    text.extend(function.side_effects().iter().map(|command| {
+                        Line::from(
+                            format!("{}: {}", command.line, command.words.join(" ")).yellow(),
+                        )
+                    }));
+                }
+
                 // Finally we can create the paragraph and render it
                 let para = Paragraph::new(text)
                     .style(Style::new().white())
@@ -3,7 +3,7 @@ use ratatui::style::Stylize as _;
 use ratatui::widgets::*;
 use ratatui::{style::Color, text::Span};
 
-use crate::script::{self, Function, Script};
+use lk::script::{self, Function, Script};
 
 /// This struct holds the state of a list widget.
 pub(crate) struct StatefulList {
@@ -52,11 +52,7 @@ impl StatefulList {
     }
 
     pub fn get_as_coloured(&self) -> Vec<Vec<Span<'_>>> {
-        self.items
-            .iter()
-            .enumerate()
-            .map(|(_, item)| item.coloured())
-            .collect()
+        self.items.iter().map(|item| item.coloured()).collect()
     }
 }
 
@@ -67,12 +63,17 @@ pub(crate) struct Item {
     pub(crate) name: String,
     pub(crate) source: (Script, Function),
     pub(crate) score: Option<(i64, Vec<usize>)>,
+    /// Whether the user has picked this item to run alongside others.
+    pub(crate) selected: bool,
 }
 
 impl Item {
     /// Highlights the line for this function. Will highlight matching characters in the search.
     pub fn coloured(&self) -> Vec<Span<'_>> {
         let mut spans: Vec<Span> = vec![];
+        if self.selected {
+            spans.push(Span::from("● ").fg(Color::Green));
+        }
         let text = self.name.clone();
         let text_vec = text.chars().collect::<Vec<_>>();
         let mut start = 0;
@@ -90,6 +91,24 @@ impl Item {
             .collect::<String>();
 
         spans.push(Span::from(remaining_chars));
+
+        if let Some(source) = &self.source.1.source {
+            spans.push(Span::from(format!(" (from {source})")).fg(Color::DarkGray));
+        }
+
+        for note in &self.source.0.notes {
+            spans.push(Span::from(format!(" ({note})")).fg(Color::DarkGray));
+        }
+
+        // Functions that need confirming get a warning sign, so nobody is surprised by the prompt.
+        if self.source.1.confirm.is_some() {
+            spans.push(Span::from(" ⚠").fg(Color::Yellow));
+        }
+
+        // Hidden functions are only here because of --show-hidden, so they're dimmed.
+        if self.source.1.hidden {
+            spans = spans.into_iter().map(|span| span.dim()).collect();
+        }
         spans
     }
 }
@@ -116,6 +135,7 @@ impl App {
                     name,
                     score: None,
                     source: (script.clone(), function.clone()),
+                    selected: false,
                 })
             })
         });
@@ -167,6 +187,38 @@ impl App {
         // to a long list we might lose out selection somewhere below in a non-visible area.
     }
 
+    /// Picks or unpicks the highlighted item, to run alongside others.
+    pub fn toggle_selected(&mut self) {
+        let Some(name) = self.get_selected().map(|item| item.name.clone()) else {
+            return;
+        };
+        self.items
+            .items
+            .iter_mut()
+            .chain(self.filtered_items.items.iter_mut())
+            .filter(|item| item.name == name)
+            .for_each(|item| item.selected = !item.selected);
+    }
+
+    /// Everything the user picked. If they didn't pick anything then it's the highlighted item.
+    pub fn get_chosen(&self) -> Vec<(Script, Function)> {
+        let picked: Vec<(Script, Function)> = self
+            .items
+            .items
+            .iter()
+            .filter(|item| item.selected)
+            .map(|item| item.source.clone())
+            .collect();
+        if picked.is_empty() {
+            self.get_selected()
+                .map(|item| item.source.clone())
+                .into_iter()
+                .collect()
+        } else {
+            picked
+        }
+    }
+
     pub fn get_selected(&self) -> Option<&Item> {
         match self.filtered_items.state.selected() {
             Some(i) => {
@@ -1,21 +1,45 @@
 use crate::{
-    executables::Executables,
+    executables::Failure,
     script::{Function, Script},
 };
-use pastel_colours::{BLUE_FG, DARK_BLUE_BG, GREEN_FG, RED_FG, RESET_BG, RESET_FG};
+use pad::{Alignment, PadStr};
+use pastel_colours::{
+    BLUE_FG, DARK_BLUE_BG, DARK_GREEN_FG, GREEN_FG, GREY_FG, ORANGE_FG, RED_FG, RESET_BG, RESET_FG,
+};
 
 pub fn print_root_header() {
     println!("{DARK_BLUE_BG}lk: ./{RESET_BG}");
 }
 
+pub fn print_owner_header(owner: Option<&str>) {
+    println!(
+        "{DARK_BLUE_BG}lk: {}{RESET_BG}",
+        owner.unwrap_or("no owner")
+    );
+}
+
+/// Lists the files lk couldn't use, and why, e.g. for `--verbose`.
+pub fn print_failures(failures: &[Failure]) {
+    if failures.is_empty() {
+        return;
+    }
+    eprintln!("lk skipped {} file(s) it couldn't use:", failures.len());
+    for failure in failures {
+        eprintln!(
+            "  {ORANGE_FG}{}{RESET_FG}: {}",
+            failure.path.to_string_lossy(),
+            failure.reason
+        );
+    }
+}
+
 pub fn print_script_header(script: &Script) {
-    let script_path = script
-        .path
-        .to_owned()
-        .into_os_string()
-        .into_string()
-        .unwrap();
-    println!("{DARK_BLUE_BG}lk: {script_path}{RESET_BG}");
+    let notes: String = script
+        .notes
+        .iter()
+        .map(|note| format!(" ({note})"))
+        .collect();
+    println!("{DARK_BLUE_BG}lk: {}{notes}{RESET_BG}", script.path());
 }
 
 pub fn print_complete_header(script: &Script, function: &Function, params: &[String]) {
@@ -27,6 +51,14 @@ pub fn print_complete_header(script: &Script, function: &Function, params: &[Str
     );
 }
 
+pub fn print_timed_out(function: &Function, timeout: std::time::Duration) {
+    println!(
+        "{RED_FG}lk: {} timed out after {}{RESET_FG}",
+        function.name,
+        humantime::format_duration(timeout)
+    );
+}
+
 pub fn print_no_functions_in_script_help() {
     println!("Could not find any functions! Why not add some. They look like this:");
     let example_function = r#"# Some great comment
@@ -37,9 +69,45 @@ blow_mind() {
     println!("{GREEN_FG}{example_function}{RESET_FG}");
 }
 
-pub fn print_bad_script_name(script: &str, executables: Executables) {
+/// Lists the scripts, and the other groups of functions, so the user can pick one to look at.
+pub fn print_scripts(scripts: &[Script]) {
+    print_root_header();
+    // We'll pad right so everything aligns nicely.
+    const INDENT: usize = 2;
+    let padding = scripts
+        .iter()
+        .map(|script| script.file_name().len())
+        .max()
+        .unwrap_or(0)
+        + INDENT;
+    for script in scripts {
+        let name = script
+            .file_name()
+            .pad_to_width_with_alignment(padding, Alignment::Right);
+        let target = if script.via_symlink {
+            format!(" -> {}", script.absolute_path.to_string_lossy())
+        } else {
+            String::new()
+        };
+        let notes: String = script
+            .notes
+            .iter()
+            .map(|note| format!(" {GREY_FG}({note}){RESET_FG}"))
+            .collect();
+        let description = match &script.description {
+            Some(description) => format!(" {GREY_FG}{description}{RESET_FG}"),
+            None => String::new(),
+        };
+        println!(
+            "{DARK_GREEN_FG}{name}{RESET_FG} - {}{target}{notes}{description}",
+            script.path()
+        );
+    }
+}
+
+pub fn print_bad_script_name(script: &str, scripts: &[Script]) {
     println!("{RED_FG}Didn't find a script with name {BLUE_FG}{script}!{RESET_FG}\n");
-    executables.pretty_print();
+    print_scripts(scripts);
 }
 
 pub fn print_bad_function_name(script: &Script, function: &str) {
@@ -0,0 +1,117 @@
+/// Finds the project lk is running in, and the config files that apply to it, so lk works the same
+/// from anywhere in the project.
+use glob::Pattern;
+use std::path::{Path, PathBuf};
+
+#[non_exhaustive]
+pub struct Workspace {
+    /// Where we look for scripts. That's the top of the git repo, or else the directory of the
+    /// nearest `lk.toml`, or else where lk was run.
+    pub root: PathBuf,
+    /// The workspace `lk.toml` files, from the root down to where lk was run. Later ones override
+    /// earlier ones.
+    pub files: Vec<PathBuf>,
+}
+
+impl Workspace {
+    /// Looks up from `cwd` for the project. In a git repo every `lk.toml` between the top of the
+    /// repo and `cwd` applies, otherwise just the nearest one.
+    pub fn find(cwd: &Path) -> Self {
+        let has_config = |dir: &Path| dir.join("lk.toml").is_file();
+        match cwd.ancestors().find(|dir| dir.join(".git").exists()) {
+            Some(repo) => {
+                let mut files: Vec<PathBuf> = cwd
+                    .ancestors()
+                    .take_while(|dir| dir.starts_with(repo))
+                    .filter(|dir| has_config(dir))
+                    .map(|dir| dir.join("lk.toml"))
+                    .collect();
+                files.reverse();
+                Self {
+                    root: repo.to_owned(),
+                    files,
+                }
+            }
+            None => match cwd.ancestors().find(|dir| has_config(dir)) {
+                Some(dir) => Self {
+                    root: dir.to_owned(),
+                    files: vec![dir.join("lk.toml")],
+                },
+                None => Self {
+                    root: cwd.to_owned(),
+                    files: Vec::new(),
+                },
+            },
+        }
+    }
+
+    /// Makes globs that are relative to `dir` relative to the root instead, e.g. `*.sh` in
+    /// `tools/lk.toml` becomes `tools/*.sh`. Globs from outside the project, e.g. the user's
+    /// config, are already relative to the root.
+    pub fn anchor(&self, dir: &Path, globs: &[String]) -> Vec<String> {
+        let prefix = match dir.strip_prefix(&self.root) {
+            Ok(prefix) if !prefix.as_os_str().is_empty() => prefix,
+            _ => return globs.to_vec(),
+        };
+        let prefix = Pattern::escape(&prefix.to_string_lossy());
+        globs
+            .iter()
+            .map(|glob| {
+                if Path::new(glob).is_absolute() {
+                    glob.to_owned()
+                } else {
+                    format!("{prefix}/{glob}")
+                }
+            })
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn finds_the_config_up_to_the_repo() {
+        let dir = tempfile::tempdir().unwrap();
+        let repo = dir.path().join("repo");
+        let sub = repo.join("tools").join("db");
+        std::fs::create_dir_all(&sub).unwrap();
+        std::fs::create_dir(repo.join(".git")).unwrap();
+        for config in [dir.path(), &repo, &sub] {
+            std::fs::write(config.join("lk.toml"), "").unwrap();
+        }
+
+        let workspace = Workspace::find(&sub);
+        assert_eq!(workspace.root, repo);
+        assert_eq!(
+            workspace.files,
+            vec![repo.join("lk.toml"), sub.join("lk.toml")]
+        );
+
+        // Outside a repo it's the nearest lk.toml.
+        std::fs::remove_dir(repo.join(".git")).unwrap();
+        std::fs::remove_file(sub.join("lk.toml")).unwrap();
+        let workspace = Workspace::find(&sub);
+        assert_eq!(workspace.root, repo);
+        assert_eq!(workspace.files, vec![repo.join("lk.toml")]);
+    }
+
+    #[test]
+    fn anchors_globs_to_the_root() {
+        let workspace = Workspace {
+            root: PathBuf::from("/repo"),
+            files: Vec::new(),
+        };
+        let globs = vec!["*.sh".to_string(), "/opt/lk/*".to_string()];
+        assert_eq!(
+            workspace.anchor(Path::new("/repo/tools [old]"), &globs),
+            vec!["tools [[]old[]]/*.sh", "/opt/lk/*"]
+        );
+        assert_eq!(workspace.anchor(Path::new("/repo"), &globs), globs);
+        assert_eq!(
+            workspace.anchor(Path::new("/home/me/.config/lk"), &globs),
+            globs
+        );
+    }
+}
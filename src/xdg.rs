@@ -0,0 +1,122 @@
+/// Where lk keeps its files, following the XDG base directory spec: config in `$XDG_CONFIG_HOME`,
+/// logs and history in `$XDG_STATE_HOME`, and anything it can rebuild in `$XDG_CACHE_HOME`.
+use crate::{Error, Result};
+use std::path::{Path, PathBuf};
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[non_exhaustive]
+pub struct LkDirs {
+    /// `lk.toml`.
+    pub config: PathBuf,
+    /// `lk.log`, the run history, and captured output.
+    pub state: PathBuf,
+    pub cache: PathBuf,
+}
+
+impl Default for LkDirs {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl LkDirs {
+    /// Works out the directories from the environment. Without a home directory, and without the
+    /// XDG variables to say otherwise, everything goes in the temp dir.
+    pub fn new() -> Self {
+        Self::from_env(
+            |name| std::env::var_os(name).map(PathBuf::from),
+            dirs::home_dir(),
+        )
+    }
+
+    fn from_env(var: impl Fn(&str) -> Option<PathBuf>, home: Option<PathBuf>) -> Self {
+        let home = home.unwrap_or_else(std::env::temp_dir);
+        let dir = |name: &str, default: &str| {
+            // The spec says relative paths are invalid and should be ignored.
+            let base = var(name)
+                .filter(|path| path.is_absolute())
+                .unwrap_or_else(|| home.join(default));
+            base.join("lk")
+        };
+        Self {
+            config: dir("XDG_CONFIG_HOME", ".config"),
+            state: dir("XDG_STATE_HOME", ".local/state"),
+            cache: dir("XDG_CACHE_HOME", ".cache"),
+        }
+    }
+
+    /// Makes any of the directories that don't exist yet, e.g. the first time lk runs.
+    pub fn create(&self) -> Result<()> {
+        for dir in [&self.config, &self.state, &self.cache] {
+            create_dir(dir)?;
+        }
+        Ok(())
+    }
+
+    /// lk used to keep its history and captured output in `~/.config/lk`, with its config. This
+    /// moves them to the state directory, if they're not there already.
+    pub fn move_old_state(&self) {
+        let Some(home) = dirs::home_dir() else {
+            return;
+        };
+        let old = home.join(".config/lk");
+        for name in ["history.jsonl", "logs"] {
+            let (from, to) = (old.join(name), self.state.join(name));
+            if from.exists() && !to.exists() {
+                match std::fs::rename(&from, &to) {
+                    Ok(()) => log::info!("Moved {} to {}", from.display(), to.display()),
+                    Err(e) => {
+                        log::warn!("Couldn't move {} to {}: {e}", from.display(), to.display())
+                    }
+                }
+            }
+        }
+    }
+
+    pub fn log_file(&self) -> PathBuf {
+        self.state.join("lk.log")
+    }
+}
+
+fn create_dir(dir: &Path) -> Result<()> {
+    std::fs::create_dir_all(dir).map_err(|source| Error::CreateDir {
+        path: dir.to_owned(),
+        source,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn uses_the_xdg_variables_or_the_defaults() {
+        let dirs = LkDirs::from_env(
+            |name| match name {
+                "XDG_CONFIG_HOME" => Some(PathBuf::from("/xdg/config")),
+                // Relative paths don't count.
+                "XDG_STATE_HOME" => Some(PathBuf::from("state")),
+                _ => None,
+            },
+            Some(PathBuf::from("/home/me")),
+        );
+        assert_eq!(
+            dirs,
+            LkDirs {
+                config: PathBuf::from("/xdg/config/lk"),
+                state: PathBuf::from("/home/me/.local/state/lk"),
+                cache: PathBuf::from("/home/me/.cache/lk"),
+            }
+        );
+    }
+
+    #[test]
+    fn creates_missing_directories() {
+        let dir = tempfile::tempdir().unwrap();
+        let dirs = LkDirs::from_env(|_| None, Some(dir.path().to_owned()));
+        dirs.create().unwrap();
+        assert!(dirs.config.is_dir());
+        assert!(dirs.state.is_dir());
+        assert!(dirs.cache.is_dir());
+    }
+}
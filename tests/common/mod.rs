@@ -0,0 +1,275 @@
+//! A harness for running the lk binary in tests. Each `Lk` is a project in a tempdir, with a home
+//! and XDG directories of its own, so nothing reads or writes the user's config, history or shell
+//! history, and nothing depends on the directory the tests run from.
+
+// Each test file uses some of this, so the rest looks dead to it.
+#![allow(dead_code)]
+
+use regex::Regex;
+use std::fs::File;
+use std::io::{Read, Write};
+use std::os::fd::{FromRawFd, OwnedFd};
+use std::os::unix::process::CommandExt;
+use std::path::{Path, PathBuf};
+use std::process::{Command, Stdio};
+use std::sync::mpsc::{Receiver, RecvTimeoutError};
+use std::time::{Duration, Instant};
+use tempfile::TempDir;
+
+pub struct Lk {
+    _dir: TempDir,
+    /// The root of the project. It's a git repo, so lk doesn't look above it for lk.toml files.
+    pub project: PathBuf,
+    pub home: PathBuf,
+    pub config: PathBuf,
+    pub state: PathBuf,
+}
+
+/// What a run of lk did. The output's had its colours taken out.
+#[derive(Debug)]
+pub struct Run {
+    pub code: Option<i32>,
+    pub stdout: String,
+    pub stderr: String,
+}
+
+impl Run {
+    pub fn success(&self) -> bool {
+        self.code == Some(0)
+    }
+}
+
+impl Lk {
+    pub fn new() -> Self {
+        let dir = tempfile::tempdir().unwrap();
+        let root = dir.path().canonicalize().unwrap();
+        let lk = Self {
+            project: root.join("project"),
+            home: root.join("home"),
+            config: root.join("config"),
+            state: root.join("state"),
+            _dir: dir,
+        };
+        std::fs::create_dir_all(lk.project.join(".git")).unwrap();
+        std::fs::create_dir_all(&lk.home).unwrap();
+        std::fs::write(lk.shell_history_file(), "").unwrap();
+        lk
+    }
+
+    /// Copies a file or directory from `tests/` into the project, keeping its permissions.
+    pub fn fixture(&self, name: &str) -> &Self {
+        let from = Path::new(env!("CARGO_MANIFEST_DIR"))
+            .join("tests")
+            .join(name);
+        copy(&from, &self.project.join(name));
+        self
+    }
+
+    /// Writes a file in the project, making any directories it's in.
+    pub fn file(&self, path: &str, text: &str) -> &Self {
+        let path = self.project.join(path);
+        std::fs::create_dir_all(path.parent().unwrap()).unwrap();
+        std::fs::write(path, text).unwrap();
+        self
+    }
+
+    /// Writes an executable script in the project.
+    pub fn script(&self, path: &str, text: &str) -> &Self {
+        use std::os::unix::fs::PermissionsExt;
+        self.file(path, text);
+        let permissions = std::fs::Permissions::from_mode(0o755);
+        std::fs::set_permissions(self.project.join(path), permissions).unwrap();
+        self
+    }
+
+    /// Writes the user's lk.toml.
+    pub fn user_config(&self, text: &str) -> &Self {
+        std::fs::create_dir_all(self.config.join("lk")).unwrap();
+        std::fs::write(self.config.join("lk/lk.toml"), text).unwrap();
+        self
+    }
+
+    /// lk, ready to run in the project with nothing from our own environment but PATH. SHELL is
+    /// bash, so lk would write to the history file in our home.
+    pub fn command(&self, args: &[&str]) -> Command {
+        let mut command = Command::new(env!("CARGO_BIN_EXE_lk"));
+        command
+            .args(args)
+            .current_dir(&self.project)
+            .env_clear()
+            .env("PATH", std::env::var_os("PATH").unwrap_or_default())
+            .env("HOME", &self.home)
+            .env("SHELL", "/bin/bash")
+            .env("XDG_CONFIG_HOME", &self.config)
+            .env("XDG_STATE_HOME", &self.state)
+            .env("XDG_CACHE_HOME", self.home.join("cache"))
+            .stdin(Stdio::null());
+        command
+    }
+
+    pub fn run(&self, args: &[&str]) -> Run {
+        run(self.command(args))
+    }
+
+    /// Runs lk in a terminal of its own, for the modes that need one, like fuzzy. Once `ready`
+    /// is on the screen we type `keys`. lk's stdout and stderr both go to the terminal, so
+    /// they're in `stdout`.
+    pub fn run_in_terminal(&self, args: &[&str], ready: &str, keys: &str) -> Run {
+        let terminal = Terminal::open();
+        let mut command = self.command(args);
+        command
+            .stdin(terminal.lk_side())
+            .stdout(terminal.lk_side())
+            .stderr(terminal.lk_side());
+        // lk reads keys from /dev/tty, so the terminal has to be the one it's controlling.
+        unsafe {
+            command.pre_exec(|| {
+                if libc::setsid() == -1 || libc::ioctl(0, libc::TIOCSCTTY, 0) == -1 {
+                    return Err(std::io::Error::last_os_error());
+                }
+                Ok(())
+            });
+        }
+        let mut child = command.spawn().expect("couldn't run lk");
+        let screen = terminal.screen();
+        // Once lk's gone its side is closed, so we know it's finished.
+        drop(command);
+        let Terminal { mut ours, lk_side } = terminal;
+        drop(lk_side);
+        let deadline = Instant::now() + Duration::from_secs(20);
+        let mut output = String::new();
+        let mut typed = false;
+        while Instant::now() < deadline {
+            match screen.recv_timeout(Duration::from_millis(50)) {
+                Ok(text) => output.push_str(&text),
+                Err(RecvTimeoutError::Timeout) => {}
+                // lk has gone, and so has everything it started.
+                Err(RecvTimeoutError::Disconnected) => break,
+            }
+            if !typed && strip(&output).contains(ready) {
+                ours.write_all(keys.as_bytes()).unwrap();
+                typed = true;
+            }
+        }
+        if Instant::now() >= deadline {
+            let _ = child.kill();
+        }
+        let status = child.wait().unwrap();
+        Run {
+            code: status.code(),
+            stdout: strip(&output),
+            stderr: String::new(),
+        }
+    }
+
+    /// The runs lk recorded in its history, as JSON.
+    pub fn history(&self) -> Vec<serde_json::Value> {
+        match std::fs::read_to_string(self.state.join("lk/history.jsonl")) {
+            Ok(text) => text
+                .lines()
+                .map(|line| serde_json::from_str(line).unwrap())
+                .collect(),
+            Err(_) => Vec::new(),
+        }
+    }
+
+    pub fn shell_history_file(&self) -> PathBuf {
+        self.home.join(".bash_history")
+    }
+
+    pub fn shell_history(&self) -> String {
+        std::fs::read_to_string(self.shell_history_file()).unwrap()
+    }
+}
+
+pub fn run(mut command: Command) -> Run {
+    let output = command.output().expect("couldn't run lk");
+    Run {
+        code: output.status.code(),
+        stdout: strip(&String::from_utf8_lossy(&output.stdout)),
+        stderr: strip(&String::from_utf8_lossy(&output.stderr)),
+    }
+}
+
+/// The text without colours, cursor movements or anything else for the terminal.
+fn strip(text: &str) -> String {
+    let escapes = Regex::new("\x1b(\\[[0-9;?]*[A-Za-z]|[78])").unwrap();
+    escapes.replace_all(text, "").to_string()
+}
+
+/// A pseudo terminal: lk's side is what it sees as its terminal, and ours is the screen and
+/// keyboard.
+struct Terminal {
+    ours: File,
+    lk_side: OwnedFd,
+}
+
+impl Terminal {
+    fn open() -> Self {
+        let (mut ours, mut lk_side) = (0, 0);
+        let size = libc::winsize {
+            ws_row: 24,
+            ws_col: 120,
+            ws_xpixel: 0,
+            ws_ypixel: 0,
+        };
+        let opened = unsafe {
+            libc::openpty(
+                &mut ours,
+                &mut lk_side,
+                std::ptr::null_mut(),
+                std::ptr::null(),
+                &size,
+            )
+        };
+        assert_eq!(opened, 0, "{}", std::io::Error::last_os_error());
+        unsafe {
+            Self {
+                ours: File::from_raw_fd(ours),
+                lk_side: OwnedFd::from_raw_fd(lk_side),
+            }
+        }
+    }
+
+    fn lk_side(&self) -> Stdio {
+        Stdio::from(self.lk_side.try_clone().unwrap())
+    }
+
+    /// What lk writes to the terminal, as it writes it. We answer when it asks where the cursor
+    /// is, as a terminal would.
+    fn screen(&self) -> Receiver<String> {
+        let (sender, receiver) = std::sync::mpsc::channel();
+        let mut reader = self.ours.try_clone().unwrap();
+        let mut answer = self.ours.try_clone().unwrap();
+        std::thread::spawn(move || {
+            let mut buffer = [0; 4096];
+            // Reading fails once lk's side is closed.
+            while let Ok(read) = reader.read(&mut buffer) {
+                if read == 0 {
+                    break;
+                }
+                let text = String::from_utf8_lossy(&buffer[..read]).to_string();
+                if text.contains("\x1b[6n") {
+                    let _ = answer.write_all(b"\x1b[1;1R");
+                }
+                if sender.send(text).is_err() {
+                    break;
+                }
+            }
+        });
+        receiver
+    }
+}
+
+fn copy(from: &Path, to: &Path) {
+    if from.is_dir() {
+        std::fs::create_dir_all(to).unwrap();
+        for entry in std::fs::read_dir(from).unwrap() {
+            let entry = entry.unwrap();
+            copy(&entry.path(), &to.join(entry.file_name()));
+        }
+    } else {
+        std::fs::create_dir_all(to.parent().unwrap()).unwrap();
+        std::fs::copy(from, to).unwrap();
+    }
+}
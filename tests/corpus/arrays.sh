@@ -0,0 +1,33 @@
+#!/usr/bin/env bash
+# Arrays declared at the top level, which are assignments, not subshells.
+
+SERVICES=(api web worker)
+arr=( "a" "b" )
+TARGETS=(
+    staging # not prod yet
+    "eu west"
+    $(hostname)
+)
+TARGETS+=(prod)
+TARGETS+=(
+    dr
+)
+declare -A PORTS=([api]=8080 [web]=80)
+
+# Deploys every target
+deploy() {
+    local hosts=(
+        "${TARGETS[@]}"
+    )
+    hosts+=(localhost)
+    for host in "${hosts[@]}"; do
+        echo "$host"
+    done
+}
+
+if [[ -n ${CI-} ]]; then
+    # Defining a function doesn't run it.
+    ci_only() {
+        echo "not run when sourced"
+    }
+fi
@@ -0,0 +1,33 @@
+#!/usr/bin/env bash
+# Completion helpers, written the way bash-completion writes them.
+
+_comp_filter() {
+    local i
+    for (( i = 0; i < ${#COMPREPLY[@]}; i++ )); do
+        [[ ${COMPREPLY[i]} == "$1"* ]] || unset -v 'COMPREPLY[i]'
+    done
+}
+
+function complete_hosts
+{
+    local cur=${COMP_WORDS[COMP_CWORD]}
+    COMPREPLY=( $(compgen -W "$(awk '{print $1}' ~/.ssh/known_hosts 2>/dev/null | tr ',' ' ')" -- "$cur") )
+}
+
+function complete_files() {
+    local IFS=$'\n'
+    COMPREPLY=( $(compgen -f -- "${COMP_WORDS[COMP_CWORD]}") )
+    (( ${#COMPREPLY[@]} == 1 )) && [[ -d ${COMPREPLY[0]} ]] && COMPREPLY[0]+=/
+}
+
+complete_git_branches() (
+    cd "${1:-.}" || exit
+    git for-each-ref --format='%(refname:short)' refs/heads/
+)
+
+if [[ -n ${BASH_VERSION-} ]]; then
+    complete -F complete_hosts ssh
+    only_when_interactive() { echo "not top level"; }
+fi
+
+echo "done() { not a function }"
@@ -0,0 +1,54 @@
+#!/usr/bin/env bash
+#
+# Deploys services. A typical ops script: traps, heredocs with
+# braces in them, and functions declared in lots of ways.
+
+set -euo pipefail
+readonly SERVICES=(api web worker)
+
+cleanup() { rm -rf "${TMP_DIR:-/tmp/none}"; }
+trap cleanup EXIT
+
+# Writes a systemd unit. The heredoc contains things that look like functions.
+write_unit()
+{
+    local name="$1"
+    cat > "/tmp/${name}.service" <<-UNIT
+	[Service]
+	ExecStart=/usr/bin/env bash -c 'start() { exec "${name}"; }; start'
+	UNIT
+}
+
+# Deploys every service
+deploy_all() {
+    local service
+    for service in "${SERVICES[@]}"; do
+        write_unit "$service" # write it } out
+        echo "deployed ${service}" | sed -e 's/}/)/g'
+    done
+    while read -r line; do
+        echo "$line"
+    done < <(systemctl list-units --type=service)
+}
+
+rollback()   {
+    local version=${1:?version required}
+    if [[ "$version" =~ ^v([0-9]+)\.([0-9]+)$ ]]; then
+        echo "rolling back to ${BASH_REMATCH[1]}"
+    else
+        echo 'not a version: {' "$version"
+    fi
+}
+
+status() {
+    local count
+    count=$(
+        case "$1" in
+            all) echo "${#SERVICES[@]}" ;;
+            *) echo 1 ;;
+        esac
+    )
+    echo "$count services"
+}
+
+"$@"
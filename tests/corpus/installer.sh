@@ -0,0 +1,64 @@
+#!/bin/sh
+# An installer in the style of the curl | sh scripts many projects ship.
+set -u
+
+say() {
+    printf 'installer: %s\n' "$1"
+}
+
+err() {
+    say "$1" >&2
+    exit 1
+}
+
+need_cmd() {
+    if ! check_cmd "$1"; then
+        err "need '$1' (command not found)"
+    fi
+}
+
+check_cmd() {
+    command -v "$1" > /dev/null 2>&1
+}
+
+get_architecture() {
+    local _ostype _cputype
+    _ostype="$(uname -s)"
+    _cputype="$(uname -m)"
+    case "$_ostype" in
+        Linux)
+            _ostype=unknown-linux-gnu
+            ;;
+        Darwin)
+            _ostype=apple-darwin
+            ;;
+        MINGW* | MSYS* | CYGWIN*)
+            _ostype=pc-windows-gnu
+            ;;
+        *)
+            err "unrecognized OS type: $_ostype"
+            ;;
+    esac
+    RETVAL="$_cputype-$_ostype"
+}
+
+usage() {
+    cat <<EOF
+installer
+
+USAGE:
+    installer [FLAGS]
+
+FLAGS:
+    -y          Disable confirmation prompt.
+    -h, --help  Prints help information
+EOF
+}
+
+main() {
+    need_cmd uname
+    get_architecture || return 1
+    say "installing for $RETVAL"
+}
+
+main "$@" || exit 1
@@ -0,0 +1,146 @@
+/// Tests lk as a library, the way other tools use it. Nothing here depends on the directory the
+/// tests run from: fixtures are found from the manifest dir, and everything else is in a tempdir.
+use lk::provider::Context;
+use lk::{Error, Executables, NonExecutable, Providers, Runner, Script, Workspace};
+use std::fs::Permissions;
+use std::os::unix::fs::PermissionsExt;
+use std::path::{Path, PathBuf};
+
+fn fixtures() -> PathBuf {
+    Path::new(env!("CARGO_MANIFEST_DIR")).join("tests/executables_tests")
+}
+
+fn write_script(dir: &Path, name: &str, text: &str) -> PathBuf {
+    let path = dir.join(name);
+    std::fs::write(&path, text).unwrap();
+    std::fs::set_permissions(&path, Permissions::from_mode(0o755)).unwrap();
+    path
+}
+
+#[test]
+fn finds_scripts_and_their_functions() {
+    let executables = Executables::new(
+        &[format!("{}/**/*.sh", fixtures().display())],
+        &[format!("{}/some_sub_dir", fixtures().display())],
+        NonExecutable::Skip,
+    )
+    .unwrap();
+
+    let names: Vec<String> = executables
+        .executables
+        .iter()
+        .map(|executable| executable.short_name.clone())
+        .collect();
+    assert_eq!(names, vec!["script02.sh", "script04.sh"]);
+    let script = Script::new(&executables.executables[0], false).unwrap();
+    assert!(!script.functions.is_empty());
+    assert!(script.absolute_path.is_absolute());
+}
+
+#[test]
+fn runs_a_function_and_remembers_it() {
+    let project = tempfile::tempdir().unwrap();
+    let state = tempfile::tempdir().unwrap();
+    write_script(
+        project.path(),
+        "notes.sh",
+        "#!/usr/bin/env bash\n\nwrite() {\n    echo \"$1\" > note.txt\n}\n\nfail() {\n    return 3\n}\n",
+    );
+    let executables = Executables::new(
+        &[format!("{}/*", project.path().display())],
+        &[],
+        NonExecutable::Skip,
+    )
+    .unwrap();
+    let script = Script::new(&executables.executables[0], false).unwrap();
+    let runner = Runner::new(state.path());
+
+    let code = runner
+        .run(
+            &script,
+            script.get("write").unwrap(),
+            vec!["hi".into()],
+            false,
+        )
+        .unwrap();
+    let failed = runner
+        .run(&script, script.get("fail").unwrap(), Vec::new(), false)
+        .unwrap();
+
+    assert_eq!(code, 0);
+    assert_eq!(failed, 3);
+    // Functions run from their script's directory, wherever we are.
+    let note = std::fs::read_to_string(project.path().join("note.txt")).unwrap();
+    assert_eq!(note, "hi\n");
+    let records = runner.history.records().unwrap();
+    let functions: Vec<&str> = records.iter().map(|r| r.function.as_str()).collect();
+    assert_eq!(functions, vec!["write", "fail"]);
+}
+
+#[test]
+fn errors_can_be_told_apart() {
+    let no_includes = Executables::new(&[], &[], NonExecutable::Skip);
+    let bad_glob = Executables::new(&["[".to_string()], &[], NonExecutable::Skip);
+    let missing = fixtures().join("missing.sh");
+    let unreadable = Script::new(&lk::Executable::new(&missing), false);
+
+    assert!(matches!(no_includes, Err(Error::NoIncludes)));
+    assert!(matches!(bad_glob, Err(Error::BadGlob { pattern, .. }) if pattern == "["));
+    match unreadable {
+        Err(error @ Error::Read { .. }) => {
+            assert!(error
+                .report()
+                .starts_with(&format!("Couldn't read {}: ", missing.display())))
+        }
+        other => panic!("expected a read error, got {other:?}"),
+    }
+}
+
+#[test]
+fn refuses_scripts_with_side_effects_when_asked() {
+    let project = tempfile::tempdir().unwrap();
+    let state = tempfile::tempdir().unwrap();
+    write_script(
+        project.path(),
+        "noisy.sh",
+        "#!/usr/bin/env bash\n\ntouch sourced\n\nquiet() {\n    true\n}\n",
+    );
+    let executables = Executables::new(
+        &[format!("{}/*", project.path().display())],
+        &[],
+        NonExecutable::Skip,
+    )
+    .unwrap();
+    let script = Script::new(&executables.executables[0], false).unwrap();
+    let mut runner = Runner::new(state.path());
+    runner.refuse_side_effects = true;
+
+    let refused = runner.run(&script, script.get("quiet").unwrap(), Vec::new(), false);
+
+    assert!(matches!(refused, Err(Error::SideEffects { function, .. }) if function == "quiet"));
+    assert!(!project.path().join("sourced").exists());
+}
+
+#[test]
+fn providers_find_more_than_bash() {
+    let project = tempfile::tempdir().unwrap();
+    let root = project.path().canonicalize().unwrap();
+    std::fs::write(root.join("Makefile"), "# Tests it\ntest:\n\ttrue\n").unwrap();
+    std::fs::write(root.join("lk.toml"), "[commands.hi]\nrun = \"echo hi\"\n").unwrap();
+    let executables =
+        Executables::new(&[format!("{}/*", root.display())], &[], NonExecutable::Skip).unwrap();
+    let workspace = Workspace::find(&root);
+    let context = Context::new(&executables, &workspace, &workspace.files, false);
+
+    let found = Providers::builtin()
+        .discover(&["make".to_string(), "commands".to_string()], &context)
+        .unwrap();
+
+    let providers: Vec<&str> = found.scripts.iter().map(|s| s.provider.as_str()).collect();
+    assert_eq!(providers, vec!["make", "commands"]);
+    assert_eq!(
+        found.scripts[0].get("test").unwrap().comment,
+        vec!["Tests it"]
+    );
+    assert!(found.scripts[1].get("hi").is_some());
+}
@@ -0,0 +1,123 @@
+/// Tests how lk puts its settings together, from the user's lk.toml, the project's, the
+/// environment and the command line, and what it says when one of them is wrong.
+mod common;
+use common::Lk;
+
+const SCRIPT: &str = "#!/usr/bin/env bash\n\nrun() {\n    true\n}\n";
+
+/// The line `lk config` prints for a setting, e.g. `default_mode = "tui"  # default`.
+fn setting<'a>(stdout: &'a str, name: &str) -> &'a str {
+    stdout
+        .lines()
+        .find(|line| line.starts_with(&format!("{name} = ")))
+        .unwrap_or_else(|| panic!("{name} isn't in:\n{stdout}"))
+}
+
+#[test]
+fn later_layers_win() {
+    let lk = Lk::new();
+    lk.user_config(
+        "default_mode = \"list\"\nfollow_sources = false\ncapture_output = true\ntimeout_grace = \"1s\"\nexcludes = [\"a\"]\n",
+    )
+    .file(
+        "lk.toml",
+        "follow_sources = true\ncapture_output = false\ntimeout_grace = \"2s\"\n",
+    );
+    let mut command = lk.command(&["config", "--set", "timeout_grace=3s", "--excludes", "b"]);
+    command.env("LK_CAPTURE_OUTPUT", "true");
+    command.env("LK_TIMEOUT_GRACE", "4s");
+
+    let run = common::run(command);
+
+    assert!(run.success(), "{run:?}");
+    let user = lk.config.join("lk/lk.toml");
+    let project = lk.project.join("lk.toml");
+    let out = &run.stdout;
+    assert_eq!(
+        setting(out, "default_mode"),
+        format!("default_mode = \"list\"  # {}", user.display())
+    );
+    assert_eq!(
+        setting(out, "follow_sources"),
+        format!("follow_sources = true  # {}", project.display())
+    );
+    assert_eq!(
+        setting(out, "capture_output"),
+        "capture_output = true  # LK_CAPTURE_OUTPUT"
+    );
+    assert_eq!(
+        setting(out, "timeout_grace"),
+        "timeout_grace = \"3s\"  # --set"
+    );
+    assert_eq!(setting(out, "excludes"), "excludes = [\"b\"]  # --excludes");
+    assert_eq!(
+        setting(out, "hide_prefixes"),
+        "hide_prefixes = [\"_\"]  # default"
+    );
+}
+
+#[test]
+fn nested_lk_toml_files_apply_below_them() {
+    let lk = Lk::new();
+    lk.file("lk.toml", "excludes = [\"old.sh\"]\n")
+        .file("web/lk.toml", "excludes = [\"legacy.sh\"]\n")
+        .script("old.sh", SCRIPT)
+        .script("web/old.sh", SCRIPT)
+        .script("web/legacy.sh", SCRIPT)
+        .script("legacy.sh", SCRIPT);
+    let mut from_web = lk.command(&["--list"]);
+    from_web.current_dir(lk.project.join("web"));
+
+    let from_root = lk.run(&["--list"]);
+    let from_web = common::run(from_web);
+
+    // Globs are relative to the lk.toml they're in.
+    assert!(!from_root.stdout.contains(" old.sh - old.sh"));
+    assert!(from_root.stdout.contains("web/old.sh"));
+    assert!(from_root.stdout.contains("legacy.sh - legacy.sh"));
+    assert!(from_root.stdout.contains("web/legacy.sh"));
+    // In web the nearer lk.toml wins.
+    assert!(from_web.stdout.contains("old.sh - old.sh"));
+    assert!(!from_web.stdout.contains("web/legacy.sh"));
+    assert!(from_web.stdout.contains("legacy.sh - legacy.sh"));
+}
+
+#[test]
+fn says_which_setting_is_wrong_and_where() {
+    let lk = Lk::new();
+    lk.file("lk.toml", "default_mode = \"loud\"\n");
+
+    let bad_file = lk.run(&["--list"]);
+    let bad_set = lk.run(&["--set", "bogus=1", "--list"]);
+    let mut bad_env = lk.command(&["--list"]);
+    bad_env.env("LK_TIMEOUT_GRACE", "soon");
+    std::fs::remove_file(lk.project.join("lk.toml")).unwrap();
+    let bad_env = common::run(bad_env);
+
+    assert_eq!(bad_file.code, Some(1));
+    assert!(bad_file.stderr.contains(&format!(
+        "default_mode in {} is wrong: unknown variant `loud`, expected one of `tui`, `fuzzy`, `list`",
+        lk.project.join("lk.toml").display()
+    )));
+    assert_eq!(bad_set.code, Some(1));
+    assert!(bad_set
+        .stderr
+        .contains("--set bogus=1: lk doesn't know the setting bogus."));
+    assert_eq!(bad_env.code, Some(1));
+    assert!(bad_env
+        .stderr
+        .contains("timeout_grace in LK_TIMEOUT_GRACE is wrong"));
+}
+
+#[test]
+fn warns_about_settings_it_doesnt_know() {
+    let lk = Lk::new();
+    lk.file("lk.toml", "exclude = [\"old.sh\"]\n")
+        .script("old.sh", SCRIPT);
+
+    let run = lk.run(&["--list"]);
+
+    assert!(run.success(), "{run:?}");
+    assert!(run.stderr.contains("exclude"));
+    assert!(run.stdout.contains("old.sh"));
+}
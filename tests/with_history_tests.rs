@@ -0,0 +1,127 @@
+/// Tests what lk remembers about the functions it runs, and where it keeps it.
+mod common;
+use common::Lk;
+
+const DEPLOY: &str = r#"#!/usr/bin/env bash
+
+# @secret token
+deploy() {
+    echo "deploying to $1 with $2"
+}
+"#;
+
+#[test]
+fn records_runs_in_the_state_dir() {
+    let lk = Lk::new();
+    lk.script("deploy.sh", DEPLOY);
+
+    let run = lk.run(&["deploy.sh", "deploy", "prod"]);
+
+    assert!(run.success(), "{run:?}");
+    let history = lk.history();
+    assert_eq!(history.len(), 1);
+    assert_eq!(history[0]["script"], "deploy.sh");
+    assert_eq!(history[0]["function"], "deploy");
+    assert_eq!(history[0]["params"], serde_json::json!(["prod"]));
+    assert!(lk.state.join("lk/lk.log").exists());
+    // Nothing goes in the home directory.
+    assert!(!lk.home.join(".config").exists());
+    assert!(!lk.home.join(".local").exists());
+}
+
+#[test]
+fn secrets_are_masked() {
+    let lk = Lk::new();
+    lk.script("deploy.sh", DEPLOY)
+        .file("lk.toml", "redact_patterns = [\"ghp_[A-Za-z0-9]+\"]\n");
+
+    let run = lk.run(&[
+        "--capture",
+        "deploy.sh",
+        "deploy",
+        "prod",
+        "token=hunter2",
+        "ghp_abc123",
+    ]);
+
+    assert!(run.success(), "{run:?}");
+    assert!(run
+        .stdout
+        .contains("lk: deploy.sh -> deploy (prod token=***** *****)"));
+    let history = lk.history();
+    assert_eq!(
+        history[0]["params"],
+        serde_json::json!(["prod", "token=*****", "*****"])
+    );
+    let log = std::path::PathBuf::from(history[0]["log"].as_str().unwrap());
+    assert!(log.starts_with(lk.state.join("lk/logs")));
+    let captured = std::fs::read_to_string(log).unwrap();
+    assert!(captured.contains("deploying to prod with token=*****"));
+    assert!(!captured.contains("hunter2") && !captured.contains("ghp_abc123"));
+}
+
+#[test]
+fn typed_commands_stay_out_of_the_shell_history() {
+    let lk = Lk::new();
+    lk.script("deploy.sh", DEPLOY);
+
+    let run = lk.run(&["deploy.sh", "deploy", "prod"]);
+
+    // The user's shell already has what they typed.
+    assert!(run.success(), "{run:?}");
+    assert_eq!(lk.shell_history(), "");
+}
+
+#[test]
+fn picked_functions_go_in_the_shell_history() {
+    let lk = Lk::new();
+    lk.script("deploy.sh", DEPLOY).file(
+        "lk.toml",
+        "[aliases]\nship = \"deploy.sh deploy prod token=hunter2\"\n",
+    );
+
+    let function = lk.run_in_terminal(&["--fuzzy"], "deploy.sh - deploy", "deploy\n");
+    let alias = lk.run_in_terminal(&["--fuzzy"], "deploy.sh - deploy", "ship\n");
+
+    assert!(function.success(), "{function:?}");
+    assert!(function.stdout.contains("deploying to  with"));
+    assert!(alias.success(), "{alias:?}");
+    assert!(alias
+        .stdout
+        .contains("deploying to prod with token=hunter2"));
+    // The alias is what goes in the shell's history, not what it runs.
+    assert_eq!(lk.shell_history(), "lk deploy.sh deploy\nlk ship\n");
+    let params: Vec<serde_json::Value> = lk
+        .history()
+        .into_iter()
+        .map(|r| r["params"].clone())
+        .collect();
+    assert_eq!(
+        params,
+        vec![
+            serde_json::json!([]),
+            serde_json::json!(["prod", "token=*****"])
+        ]
+    );
+}
+
+#[test]
+fn moves_history_from_where_it_used_to_be() {
+    let lk = Lk::new();
+    lk.script("deploy.sh", DEPLOY);
+    let old = lk.home.join(".config/lk");
+    std::fs::create_dir_all(&old).unwrap();
+    let record = r#"{"time":"2024-01-01T00:00:00+00:00","script":"deploy.sh","function":"deploy","params":["staging"]}"#;
+    std::fs::write(old.join("history.jsonl"), format!("{record}\n")).unwrap();
+
+    let run = lk.run(&["deploy.sh", "deploy", "prod"]);
+
+    assert!(run.success(), "{run:?}");
+    assert!(!old.join("history.jsonl").exists());
+    let params: Vec<serde_json::Value> = lk
+        .history()
+        .into_iter()
+        .map(|r| r["params"][0].clone())
+        .collect();
+    assert_eq!(params, vec!["staging", "prod"]);
+}
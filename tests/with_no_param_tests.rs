@@ -1,49 +1,96 @@
-//TODO: Fix! Caused by 8bd7fd7a00d2b8c8238794f924ecedb2628577c8 and the previous config.
-// Tests lk's behaviour when no params are passed, i.e. the finding and
-// printing of executables.
-// use std::process::{Command, Output};
-
-// fn run_no_param() -> Output {
-//     Command::new("cargo")
-//         .arg("run")
-//         .output()
-//         .expect("failed to execute process")
-// }
-
-// #[test]
-// fn finds_executables() {
-//     // When...
-//     let output = run_no_param();
-//     let stdout = String::from_utf8(output.stdout).unwrap();
-
-//     // Then...
-//     assert_eq!(output.status.success(), true);
-//     // The scripts should be present
-//     assert_eq!(stdout.contains("script01.sh"), true);
-//     assert_eq!(stdout.contains("script02.sh"), true);
-//     assert_eq!(stdout.contains("script04.sh"), true);
-// }
-
-// #[test]
-// fn binaries_are_ignored() {
-//     // When...
-//     let output = run_no_param();
-//     let stdout = String::from_utf8(output.stdout).unwrap();
-
-//     // Then...
-//     assert_eq!(output.status.success(), true);
-//     // The binary should be ignored
-//     assert_eq!(stdout.contains("mkfifo"), false);
-// }
-
-// #[test]
-// fn must_have_executable_permissions() {
-//     // When...
-//     let output = run_no_param();
-//     let stdout = String::from_utf8(output.stdout).unwrap();
-
-//     // Then...
-//     assert_eq!(output.status.success(), true);
-//     // The script without executable permissions should be ignored
-//     assert_eq!(stdout.contains("script03.sh"), false);
-// }
+/// Tests lk's behaviour when no script is asked for, i.e. the finding and printing of
+/// executables.
+mod common;
+use common::Lk;
+
+#[test]
+fn finds_executables() {
+    let lk = Lk::new();
+    lk.fixture("executables_tests");
+
+    let run = lk.run(&["--list"]);
+
+    assert!(run.success(), "{run:?}");
+    assert!(run
+        .stdout
+        .contains("executables_tests/some_sub_dir/script01.sh"));
+    assert!(run.stdout.contains("executables_tests/script02.sh"));
+    assert!(run.stdout.contains("executables_tests/script04.sh"));
+    assert!(run
+        .stdout
+        .contains("executables_tests/some_sub_dir/script04.sh"));
+}
+
+#[test]
+fn binaries_are_ignored() {
+    let lk = Lk::new();
+    lk.fixture("executables_tests");
+
+    let run = lk.run(&["--list"]);
+
+    assert!(run.success(), "{run:?}");
+    assert!(!run.stdout.contains("mkfifo"));
+}
+
+#[test]
+fn must_have_executable_permissions() {
+    let lk = Lk::new();
+    lk.fixture("executables_tests");
+
+    let run = lk.run(&["--list"]);
+    let with_non_executables = lk.run(&["--list", "--set", "non_executable=extension"]);
+
+    assert!(!run.stdout.contains("script03.sh"));
+    assert!(with_non_executables
+        .stdout
+        .contains("executables_tests/script03.sh (not executable)"));
+}
+
+#[test]
+fn leaves_out_the_default_excludes() {
+    let lk = Lk::new();
+    lk.script("build.sh", "#!/usr/bin/env bash\n\nall() {\n    true\n}\n")
+        .script(
+            "node_modules/dep/install.sh",
+            "#!/usr/bin/env bash\n\ninstall() {\n    true\n}\n",
+        );
+
+    let run = lk.run(&["--list"]);
+    let everything = lk.run(&["--list", "--no-default-excludes"]);
+
+    assert!(run.stdout.contains("build.sh"));
+    assert!(!run.stdout.contains("install.sh"));
+    assert!(everything.stdout.contains("node_modules/dep/install.sh"));
+}
+
+#[test]
+fn lists_the_whole_project_from_anywhere_in_it() {
+    let lk = Lk::new();
+    lk.script("build.sh", "#!/usr/bin/env bash\n\nall() {\n    true\n}\n")
+        .script(
+            "web/serve.sh",
+            "#!/usr/bin/env bash\n\nserve() {\n    true\n}\n",
+        );
+    let mut command = lk.command(&["--list"]);
+    command.current_dir(lk.project.join("web"));
+
+    let run = common::run(command);
+
+    assert!(run.success(), "{run:?}");
+    assert!(run.stdout.contains("build.sh"));
+    assert!(run.stdout.contains("web/serve.sh"));
+}
+
+#[test]
+fn prints_json() {
+    let lk = Lk::new();
+    lk.fixture("script.sh");
+
+    let run = lk.run(&["--json"]);
+
+    assert!(run.success(), "{run:?}");
+    let json: serde_json::Value = serde_json::from_str(&run.stdout).unwrap();
+    let script = &json[0];
+    assert_eq!(script["path"], "script.sh");
+    assert_eq!(script["functions"][0]["name"], "some_function");
+}
@@ -1,71 +1,157 @@
-// /// Tests lk's behaviour when the user asks for a script and a function
-// use std::process::{Command, Output};
-
-// fn run(script_name: &str, function_name: &str, args: &[&str]) -> Output {
-//     Command::new("cargo")
-//         .arg("run")
-//         .arg(script_name)
-//         .arg(function_name)
-//         .args(args)
-//         .output()
-//         .expect("failed to execute process")
-// }
-
-// #[test]
-// fn test_with_function() {
-//     // When...
-//     let output = run("script.sh", "another_function", &[]);
-//     let stdout = String::from_utf8(output.stdout).unwrap();
-
-//     // Then...
-//     assert_eq!(output.status.success(), true);
-//     println!("{}", stdout);
-//     // The function is executed.
-//     assert_eq!(stdout.contains("hello from another function"), true);
-// }
-
-// #[test]
-// fn test_with_bad_function_name() {
-//     // When...
-//     let output = run("script.sh", "bad_function_name", &[]);
-//     let stdout = String::from_utf8(output.stdout).unwrap();
-
-//     // Then...
-//     assert_eq!(output.status.success(), true);
-//     assert_eq!(stdout.contains("Didn't find a function with name"), true);
-// }
-
-// #[test]
-// fn test_function_params() {
-//     // When...
-//     let output = run("script.sh", "printing_function", &["hello", "person"]);
-//     let stdout = String::from_utf8(output.stdout).unwrap();
-
-//     // Then...
-//     assert_eq!(output.status.success(), true);
-//     assert_eq!(stdout.contains("You said hello person"), true);
-// }
-
-// #[test]
-// fn bad_script_path() {
-//     // When...
-//     let output = run("bad_script_path.sh", "another_function", &[]);
-//     let stdout = String::from_utf8(output.stdout).unwrap();
-
-//     // Then...
-//     assert_eq!(output.status.success(), true);
-//     assert_eq!(stdout.contains("Didn't find a script with name"), true);
-// }
-
-// /// If this script isn't executed in its directory then it won't find 'file'
-// /// and won't print the correct thing and the test will fail.
-// #[test]
-// fn executes_in_script_dir() {
-//     // When...
-//     let output = run("depends_on_file.sh", "depends_on_file", &[]);
-//     let stdout = String::from_utf8(output.stdout).unwrap();
-
-//     // Then...
-//     assert_eq!(output.status.success(), true);
-//     assert_eq!(stdout.contains("contents to print"), true);
-// }
+/// Tests lk's behaviour when the user asks for a script and a function
+mod common;
+use common::Lk;
+
+#[test]
+fn test_with_function() {
+    let lk = Lk::new();
+    lk.fixture("script.sh");
+
+    let run = lk.run(&["script.sh", "another_function"]);
+
+    assert!(run.success(), "{run:?}");
+    assert!(run.stdout.contains("lk: script.sh -> another_function"));
+    assert!(run.stdout.contains("hello from another function"));
+}
+
+#[test]
+fn test_with_bad_function_name() {
+    let lk = Lk::new();
+    lk.fixture("script.sh");
+
+    let run = lk.run(&["script.sh", "bad_function_name"]);
+
+    assert!(run.success(), "{run:?}");
+    assert!(run
+        .stdout
+        .contains("Didn't find a function with name bad_function_name!"));
+    assert!(run.stdout.contains("another_function"));
+}
+
+#[test]
+fn test_function_params() {
+    let lk = Lk::new();
+    lk.fixture("script.sh");
+
+    let run = lk.run(&["script.sh", "printing_function", "hello", "person"]);
+
+    assert!(run.success(), "{run:?}");
+    assert!(run.stdout.contains("You said hello person"));
+}
+
+/// If this script isn't executed in its directory then it won't find 'file'
+/// and won't print the correct thing and the test will fail.
+#[test]
+fn executes_in_script_dir() {
+    let lk = Lk::new();
+    lk.fixture("depends_on_file");
+
+    let run = lk.run(&["depends_on_file.sh", "depends_on_file"]);
+
+    assert!(run.success(), "{run:?}");
+    assert!(run.stdout.contains("contents to print"));
+}
+
+#[test]
+fn exits_with_the_functions_exit_code() {
+    let lk = Lk::new();
+    lk.script(
+        "check.sh",
+        "#!/usr/bin/env bash\n\nfail() {\n    echo \"broken\" >&2\n    return 3\n}\n",
+    );
+
+    let run = lk.run(&["check.sh", "fail"]);
+
+    assert_eq!(run.code, Some(3));
+    assert!(run.stderr.contains("broken"));
+}
+
+#[test]
+fn says_why_it_didnt_run_a_function() {
+    let lk = Lk::new();
+    lk.script(
+        "deploy.sh",
+        "#!/usr/bin/env bash\n\n# @confirm \"This deploys to prod\"\nrelease() {\n    touch released\n}\n",
+    )
+    .script(
+        "noisy.sh",
+        "#!/usr/bin/env bash\n\ntouch sourced\n\nquiet() {\n    true\n}\n",
+    );
+
+    // There's no one to say yes.
+    let unconfirmed = lk.run(&["deploy.sh", "release"]);
+    let refused = lk.run(&["--set", "refuse_side_effects=true", "noisy.sh", "quiet"]);
+
+    assert_eq!(unconfirmed.code, Some(1));
+    assert!(unconfirmed.stderr.contains(
+        "Didn't run release because it wasn't confirmed. Use --yes to skip confirmation."
+    ));
+    assert!(!lk.project.join("released").exists());
+    assert_eq!(refused.code, Some(1));
+    assert!(refused.stderr.contains(
+        "Didn't run quiet because noisy.sh runs commands whenever it's sourced: `touch sourced` (line 3)."
+    ));
+    assert!(!lk.project.join("sourced").exists());
+}
+
+#[test]
+fn runs_commands_from_lk_toml() {
+    let lk = Lk::new();
+    lk.file(
+        "lk.toml",
+        "[commands.greet]\nrun = \"echo hello $NAME from $(basename $PWD)\"\ncwd = \"web\"\nenv = { NAME = \"lk\" }\n",
+    )
+    .file("web/.keep", "");
+
+    let run = lk.run(&["greet"]);
+
+    assert!(run.success(), "{run:?}");
+    assert!(run.stdout.contains("hello lk from web"));
+}
+
+#[test]
+fn ctrl_c_stops_lk_once_a_function_has_run() {
+    use std::io::Read;
+    use std::os::unix::process::ExitStatusExt;
+    use std::process::Stdio;
+    use std::time::{Duration, Instant};
+
+    let lk = Lk::new();
+    lk.script(
+        "deploy.sh",
+        "#!/usr/bin/env bash\n\nbuild() {\n    echo built\n}\n\n# @confirm \"Ships it\"\nrelease() {\n    touch released\n}\n",
+    )
+    .file(
+        "lk.toml",
+        "[aliases]\nship = [\"deploy.sh build\", \"deploy.sh release\"]\n",
+    );
+    let mut command = lk.command(&["ship"]);
+    command.stdin(Stdio::piped()).stdout(Stdio::piped());
+    let mut child = command.spawn().unwrap();
+    // No one answers, but stdin stays open, so lk waits at the prompt.
+    let _stdin = child.stdin.take();
+
+    let mut stdout = child.stdout.take().unwrap();
+    let mut output = Vec::new();
+    let mut buffer = [0; 1024];
+    while !String::from_utf8_lossy(&output).contains("Run release? [y/N]") {
+        let read = stdout.read(&mut buffer).unwrap();
+        assert_ne!(read, 0, "{}", String::from_utf8_lossy(&output));
+        output.extend_from_slice(&buffer[..read]);
+    }
+    unsafe { libc::kill(child.id() as libc::pid_t, libc::SIGINT) };
+    let deadline = Instant::now() + Duration::from_secs(5);
+    let status = loop {
+        if let Some(status) = child.try_wait().unwrap() {
+            break status;
+        }
+        if Instant::now() > deadline {
+            child.kill().unwrap();
+            panic!("lk ignored Ctrl-C");
+        }
+        std::thread::sleep(Duration::from_millis(50));
+    };
+
+    assert_eq!(status.signal(), Some(libc::SIGINT));
+    assert!(!lk.project.join("released").exists());
+}
@@ -1,40 +1,52 @@
-// /// Tests lk's behaviour when the user asks for a script
-// use std::process::{Command, Output};
-
-// fn run_with_function(script_name: &str) -> Output {
-//     Command::new("cargo")
-//         .arg("run")
-//         .arg(script_name)
-//         .output()
-//         .expect("failed to execute process")
-// }
-
-// #[test]
-// fn test_no_function() {
-//     // When...
-//     let output = run_with_function("script.sh");
-//     let stdout = String::from_utf8(output.stdout).unwrap();
-
-//     // Then...
-//     assert_eq!(output.status.success(), true);
-//     assert_eq!(stdout.contains("script.sh"), true);
-//     assert_eq!(stdout.contains("First line of file header comment"), true);
-//     assert_eq!(stdout.contains("Second line of file header comment"), true);
-//     assert_eq!(stdout.contains("some_function This function"), true);
-//     assert_eq!(stdout.contains("This function is very clever and"), true);
-//     assert_eq!(stdout.contains("And here is some more detailed"), true);
-//     assert_eq!(stdout.contains("some_function"), true);
-//     assert_eq!(stdout.contains("another_function"), true);
-//     assert_eq!(stdout.contains("yet_more_functions"), true);
-// }
-
-// #[test]
-// fn test_with_empty_script() {
-//     // When...
-//     let output = run_with_function("empty_script.sh");
-//     let stdout = String::from_utf8(output.stdout).unwrap();
-
-//     // Then...
-//     assert_eq!(output.status.success(), true);
-//     assert_eq!(stdout.contains("Could not find any functions!"), true);
-// }
+/// Tests lk's behaviour when the user asks for a script
+mod common;
+use common::Lk;
+
+#[test]
+fn test_no_function() {
+    let lk = Lk::new();
+    lk.fixture("script.sh");
+
+    let run = lk.run(&["script.sh"]);
+
+    assert!(run.success(), "{run:?}");
+    assert!(run.stdout.contains("lk: script.sh"));
+    assert!(run.stdout.contains("First line of file header comment"));
+    assert!(run.stdout.contains("Second line of file header comment"));
+    assert!(run
+        .stdout
+        .contains("some_function This function is very clever and awesome"));
+    assert!(run.stdout.contains("another_function"));
+    assert!(run.stdout.contains("yet_more_functions More functions"));
+    // Hidden functions aren't listed.
+    assert!(!run.stdout.contains("_hidden_function"));
+}
+
+#[test]
+fn finds_the_script_by_its_path_too() {
+    let lk = Lk::new();
+    lk.fixture("depends_on_file");
+
+    let run = lk.run(&["depends_on_file/depends_on_file.sh"]);
+
+    assert!(run.success(), "{run:?}");
+    assert!(run
+        .stdout
+        .contains("lk: depends_on_file/depends_on_file.sh"));
+    assert!(run.stdout.contains("depends_on_file"));
+}
+
+#[test]
+fn bad_script_name() {
+    let lk = Lk::new();
+    lk.fixture("script.sh");
+
+    let run = lk.run(&["bad_script_name.sh"]);
+
+    assert!(run.success(), "{run:?}");
+    assert!(run
+        .stdout
+        .contains("Didn't find a script with name bad_script_name.sh!"));
+    // We show the user what there is instead.
+    assert!(run.stdout.contains("script.sh"));
+}